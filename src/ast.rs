@@ -0,0 +1,568 @@
+//! Tree view of the flat record list.
+//!
+//! The parser keeps records in stream order, but MTEF nests them: a LINE holds objects,
+//! a TMPL holds its slots (and the characters it draws, such as fences or operators),
+//! a PILE holds lines,
+//! and every object list is closed by an END record. Backends walk this tree instead
+//! of counting END records themselves, and so can other code: `MTEquation::tree`
+//! builds it.
+
+use super::constants::selectors::*;
+use super::constants::variations::*;
+use super::eqn::{CharOptions, MTChar, MTEmbell, MTEquation, MTLine, MTPile, MTRecords, MTTmpl, Typeface};
+
+#[derive(Debug)]
+pub enum Node<'a> {
+    Char(Char<'a>),
+    Tmpl(Tmpl<'a>),
+    Pile(Pile<'a>),
+    Marker(Marker<'a>),
+    /// An empty box standing for a slot the template lost, added when the equation
+    /// was parsed with `ParseOptions::fill_missing_slots`.
+    Placeholder,
+}
+
+/// A character with its embellishments (primes, dots, strikes...).
+#[derive(Debug)]
+pub struct Char<'a> {
+    pub(crate) ch: &'a MTChar,
+    pub(crate) embells: Vec<&'a MTEmbell>,
+}
+
+/// A character in the marker typeface: no glyph, a point other parts of the
+/// document refer to.
+#[derive(Debug)]
+pub struct Marker<'a> {
+    pub(crate) ch: &'a MTChar,
+}
+
+impl<'a> Marker<'a> {
+    pub fn ch(&self) -> &'a MTChar {
+        self.ch
+    }
+
+    /// The marker's MTCode, or its font position when it has none.
+    pub fn code(&self) -> Option<u16> {
+        self.ch.mtcode.or(self.ch.fp16).or_else(|| self.ch.fp8.map(u16::from))
+    }
+}
+
+/// A template with its slots in record order.
+#[derive(Debug)]
+pub struct Tmpl<'a> {
+    pub(crate) tmpl: &'a MTTmpl,
+    pub(crate) slots: Vec<Line<'a>>,
+    /// characters belonging to the template itself, e.g. the two fences of a bracket pair
+    pub(crate) chars: Vec<&'a MTChar>,
+}
+
+/// Lines stacked vertically, e.g. the cases after a brace.
+#[derive(Debug)]
+pub struct Pile<'a> {
+    pub(crate) pile: &'a MTPile,
+    pub(crate) lines: Vec<Line<'a>>,
+}
+
+/// A line (slot); null lines are placeholders and have no objects.
+#[derive(Debug)]
+pub struct Line<'a> {
+    pub(crate) line: &'a MTLine,
+    pub(crate) nodes: Vec<Node<'a>>,
+}
+
+/// Delimiter pair drawn by a fence template.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum FenceKind {
+    Angle,
+    Paren,
+    Brace,
+    Brack,
+    Bar,
+    DBar,
+    Floor,
+    Ceiling,
+}
+
+/// Fence template: a main slot between delimiters, either of which may be missing.
+#[derive(Debug)]
+pub(crate) struct Fence<'t, 'a> {
+    pub(crate) kind: FenceKind,
+    pub(crate) body: Option<&'t Line<'a>>,
+    pub(crate) left: bool,
+    pub(crate) right: bool,
+}
+
+/// Horizontal brace or bracket: a main slot with a label slot on the brace side.
+#[cfg(any(feature = "latex", feature = "mathml"))]
+#[derive(Debug)]
+pub(crate) struct HBrace<'t, 'a> {
+    pub(crate) body: Option<&'t Line<'a>>,
+    pub(crate) label: Option<&'t Line<'a>>,
+    /// brace drawn above the body, else below
+    pub(crate) top: bool,
+    /// square bracket instead of a curly brace
+    pub(crate) bracket: bool,
+}
+
+/// Direction and shape of a tmARROW template.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ArrowKind {
+    Right,
+    Left,
+    LeftRight,
+    /// a pair of opposite arrows, as in reversible reactions
+    Double,
+    Harpoons,
+}
+
+/// Arrow with optional text above and below.
+#[derive(Debug)]
+pub(crate) struct Arrow<'t, 'a> {
+    pub(crate) kind: ArrowKind,
+    pub(crate) top: Option<&'t Line<'a>>,
+    pub(crate) bottom: Option<&'t Line<'a>>,
+}
+
+/// Box template and the edges it draws.
+#[cfg(any(feature = "latex", feature = "mathml"))]
+#[derive(Debug)]
+pub(crate) struct Boxed<'t, 'a> {
+    pub(crate) body: Option<&'t Line<'a>>,
+    pub(crate) round: bool,
+    pub(crate) left: bool,
+    pub(crate) right: bool,
+    pub(crate) top: bool,
+    pub(crate) bottom: bool,
+}
+
+#[cfg(any(feature = "latex", feature = "mathml"))]
+impl<'t, 'a> Boxed<'t, 'a> {
+    pub(crate) fn is_full(&self) -> bool {
+        self.left && self.right && self.top && self.bottom
+    }
+}
+
+/// Strokes of a tmSTRIKE template.
+#[cfg(any(feature = "latex", feature = "mathml"))]
+#[derive(Debug)]
+pub(crate) struct Strike<'t, 'a> {
+    pub(crate) body: Option<&'t Line<'a>>,
+    pub(crate) horizontal: bool,
+    pub(crate) up: bool,
+    pub(crate) down: bool,
+}
+
+/// Subscript and superscript of a tmSUB, tmSUP or tmSUBSUP template.
+/// The base is the object before the template, or after it for left scripts.
+#[derive(Debug)]
+pub(crate) struct Scripts<'t, 'a> {
+    pub(crate) sub: Option<&'t Line<'a>>,
+    pub(crate) sup: Option<&'t Line<'a>>,
+    /// tensor-style prescripts, written before the base
+    #[cfg_attr(not(any(feature = "latex", feature = "mathml")), allow(dead_code))]
+    pub(crate) precedes: bool,
+}
+
+/// Operator drawn by a big operator template.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BigOpKind {
+    /// one to three integral signs, contour integrals drawn with a loop
+    Integral { count: u8, contour: bool },
+    Sum,
+    Prod,
+    Coprod,
+    Union,
+    Inter,
+    /// tmINTOP and tmSUMOP, any operator character
+    Other,
+}
+
+/// Big operator template (tmINTEG to tmSUMOP): the operator with its limits, then
+/// the main slot (the integrand or summand).
+#[derive(Debug)]
+pub(crate) struct BigOp<'t, 'a> {
+    pub(crate) kind: BigOpKind,
+    /// the operator character MathType draws, if written
+    pub(crate) op: Option<&'a MTChar>,
+    pub(crate) body: Option<&'t Line<'a>>,
+    pub(crate) lower: Option<&'t Line<'a>>,
+    pub(crate) upper: Option<&'t Line<'a>>,
+    /// limits above and below the operator (summation style), else as side scripts
+    pub(crate) over_under: bool,
+}
+
+/// Limit template (tmLIM): a function name such as "lim" in the main slot, its
+/// limits below and above it.
+#[derive(Debug)]
+pub(crate) struct Limit<'t, 'a> {
+    pub(crate) name: Option<&'t Line<'a>>,
+    pub(crate) lower: Option<&'t Line<'a>>,
+    pub(crate) upper: Option<&'t Line<'a>>,
+}
+
+impl<'a> Char<'a> {
+    pub fn ch(&self) -> &'a MTChar {
+        self.ch
+    }
+
+    pub fn embells(&self) -> &[&'a MTEmbell] {
+        &self.embells
+    }
+}
+
+impl<'a> Tmpl<'a> {
+    pub fn tmpl(&self) -> &'a MTTmpl {
+        self.tmpl
+    }
+
+    /// Slots in record order, see `constants::selectors` for what each one holds.
+    pub fn slots(&self) -> &[Line<'a>] {
+        &self.slots
+    }
+
+    /// Characters belonging to the template itself.
+    pub fn chars(&self) -> &[&'a MTChar] {
+        &self.chars
+    }
+
+    /// Slot `i`, if the template has it and it is not empty.
+    pub(crate) fn slot(&self, i: usize) -> Option<&Line<'a>> {
+        self.slots.get(i).filter(|l| !l.is_empty())
+    }
+
+    /// Fence templates have a single slot, the delimiters come from the selector
+    /// and the variation tells which sides are drawn.
+    pub(crate) fn fence(&self) -> Option<Fence<'_, 'a>> {
+        let kind = match self.tmpl.selector {
+            TM_ANGLE => FenceKind::Angle,
+            TM_PAREN => FenceKind::Paren,
+            TM_BRACE => FenceKind::Brace,
+            TM_BRACK => FenceKind::Brack,
+            TM_BAR => FenceKind::Bar,
+            TM_DBAR => FenceKind::DBar,
+            TM_FLOOR => FenceKind::Floor,
+            TM_CEILING => FenceKind::Ceiling,
+            _ => return None,
+        };
+        Some(Fence {
+            kind,
+            body: self.slot(0),
+            left: TV_FENCE_L == self.tmpl.variation & TV_FENCE_L,
+            right: TV_FENCE_R == self.tmpl.variation & TV_FENCE_R,
+        })
+    }
+
+    /// tmHBRACE and tmHBRACK slots: the main slot first, then the label.
+    #[cfg(any(feature = "latex", feature = "mathml"))]
+    pub(crate) fn hbrace(&self) -> Option<HBrace<'_, 'a>> {
+        let bracket = match self.tmpl.selector {
+            TM_HBRACE => false,
+            TM_HBRACK => true,
+            _ => return None,
+        };
+        Some(HBrace {
+            body: self.slot(0),
+            label: self.slot(1),
+            top: TV_HB_TOP == self.tmpl.variation & TV_HB_TOP,
+            bracket,
+        })
+    }
+
+    /// Big operator slots: the main slot, the lower limit, then the upper limit,
+    /// followed by the operator character. Missing limits are null lines.
+    pub(crate) fn big_op(&self) -> Option<BigOp<'_, 'a>> {
+        let v = self.tmpl.variation;
+        let kind = match self.tmpl.selector {
+            TM_INTEG => BigOpKind::Integral {
+                count: (v & TV_INT_3).max(TV_INT_1) as u8,
+                contour: 0 != v & TV_INT_CCW_LOOP,
+            },
+            TM_SUM => BigOpKind::Sum,
+            TM_PROD => BigOpKind::Prod,
+            TM_COPROD => BigOpKind::Coprod,
+            TM_UNION => BigOpKind::Union,
+            TM_INTER => BigOpKind::Inter,
+            TM_INTOP | TM_SUMOP => BigOpKind::Other,
+            _ => return None,
+        };
+        Some(BigOp {
+            kind,
+            op: self.chars.first().cloned(),
+            body: self.slot(0),
+            lower: self.slot(1),
+            upper: self.slot(2),
+            over_under: TV_BO_SUM == v & TV_BO_SUM,
+        })
+    }
+
+    /// tmLIM slots: the function name, the lower limit, then the upper one.
+    pub(crate) fn limit(&self) -> Option<Limit<'_, 'a>> {
+        if TM_LIM != self.tmpl.selector {
+            return None;
+        }
+        Some(Limit {
+            name: self.slot(0),
+            lower: self.slot(1),
+            upper: self.slot(2),
+        })
+    }
+
+    /// Script templates always carry both slots, the unused one is a null line.
+    pub(crate) fn scripts(&self) -> Option<Scripts<'_, 'a>> {
+        match self.tmpl.selector {
+            TM_SUB | TM_SUP | TM_SUBSUP => Some(Scripts {
+                sub: self.slot(0),
+                sup: self.slot(1),
+                precedes: TV_SU_PRECEDES == self.tmpl.variation & TV_SU_PRECEDES,
+            }),
+            _ => None,
+        }
+    }
+
+    /// tmSTRIKE has a single slot, the variation lists the strokes.
+    #[cfg(any(feature = "latex", feature = "mathml"))]
+    pub(crate) fn strike(&self) -> Option<Strike<'_, 'a>> {
+        if TM_STRIKE != self.tmpl.selector {
+            return None;
+        }
+        let v = self.tmpl.variation;
+        Some(Strike {
+            body: self.slot(0),
+            horizontal: TV_ST_HORIZ == v & TV_ST_HORIZ,
+            up: TV_ST_UP == v & TV_ST_UP,
+            down: TV_ST_DOWN == v & TV_ST_DOWN,
+        })
+    }
+
+    /// tmBOX has a single slot, the variation lists the drawn edges.
+    #[cfg(any(feature = "latex", feature = "mathml"))]
+    pub(crate) fn boxed(&self) -> Option<Boxed<'_, 'a>> {
+        if TM_BOX != self.tmpl.selector {
+            return None;
+        }
+        let v = self.tmpl.variation;
+        Some(Boxed {
+            body: self.slot(0),
+            round: TV_BX_ROUND == v & TV_BX_ROUND,
+            left: TV_BX_LEFT == v & TV_BX_LEFT,
+            right: TV_BX_RIGHT == v & TV_BX_RIGHT,
+            top: TV_BX_TOP == v & TV_BX_TOP,
+            bottom: TV_BX_BOTTOM == v & TV_BX_BOTTOM,
+        })
+    }
+
+    /// tmARROW slots: the top slot first, then the bottom one.
+    /// The variation says which of them are present.
+    pub(crate) fn arrow(&self) -> Option<Arrow<'_, 'a>> {
+        if TM_ARROW != self.tmpl.selector {
+            return None;
+        }
+        let v = self.tmpl.variation;
+        let kind = if TV_AR_DOUBLE == v & TV_AR_DOUBLE {
+            ArrowKind::Double
+        } else if TV_AR_HARPOON == v & TV_AR_HARPOON {
+            ArrowKind::Harpoons
+        } else {
+            match (TV_AR_LEFT == v & TV_AR_LEFT, TV_AR_RIGHT == v & TV_AR_RIGHT) {
+                (true, true) => ArrowKind::LeftRight,
+                (true, false) => ArrowKind::Left,
+                _ => ArrowKind::Right,
+            }
+        };
+        let (top, bottom) = match (TV_AR_TOP == v & TV_AR_TOP, TV_AR_BOTTOM == v & TV_AR_BOTTOM) {
+            (true, true) => (self.slot(0), self.slot(1)),
+            (true, false) => (self.slot(0), None),
+            (false, true) => (None, self.slot(0).or_else(|| self.slot(1))),
+            (false, false) => (None, None),
+        };
+        Some(Arrow { kind, top, bottom })
+    }
+}
+
+impl<'a> Pile<'a> {
+    pub fn pile(&self) -> &'a MTPile {
+        self.pile
+    }
+
+    pub fn lines(&self) -> &[Line<'a>] {
+        &self.lines
+    }
+
+    /// Space in points to add above each line, beyond `normal` baseline-to-baseline
+    /// distance: lines with a spacing of their own (in points, on their LINE record)
+    /// get the difference, the first line never has any.
+    #[cfg(any(feature = "latex", feature = "mathml"))]
+    pub(crate) fn extra_spacing(&self, normal: f32) -> Vec<f32> {
+        self.lines.iter().enumerate()
+            .map(|(i, line)| match (i, line.line.line_spacing) {
+                (0, _) | (_, 0) => 0.0,
+                (_, spacing) => f32::from(spacing) - normal,
+            })
+            .collect()
+    }
+}
+
+impl<'a> Line<'a> {
+    pub fn line(&self) -> &'a MTLine {
+        self.line
+    }
+
+    pub fn nodes(&self) -> &[Node<'a>] {
+        &self.nodes
+    }
+
+    /// Null, or without objects.
+    pub fn is_empty(&self) -> bool {
+        self.line.null || self.nodes.is_empty()
+    }
+
+    /// The pile the line consists of, if nothing else is in it.
+    #[cfg(feature = "latex")]
+    pub(crate) fn as_pile(&self) -> Option<&Pile<'a>> {
+        match self.nodes.as_slice() {
+            [Node::Pile(pile)] => Some(pile),
+            _ => None,
+        }
+    }
+}
+
+impl MTEquation {
+    /// The equation as a tree, see the `ast` module: its top-level lines, those of a
+    /// pile holding the whole equation included. Templates short of slots get lines
+    /// holding a `Node::Placeholder` when the equation was parsed with
+    /// `ParseOptions::fill_missing_slots`.
+    pub fn tree(&self) -> Vec<Line<'_>> {
+        build(&self.records, self.fill_missing_slots)
+    }
+
+    /// Codes of the marker characters of the equation, in order. Backends leave
+    /// markers out; tools resolving cross-references find them here.
+    pub fn markers(&self) -> Vec<u16> {
+        fn walk(line: &Line, codes: &mut Vec<u16>) {
+            for node in &line.nodes {
+                match node {
+                    Node::Marker(marker) => codes.extend(marker.code()),
+                    Node::Tmpl(t) => t.slots.iter().for_each(|slot| walk(slot, codes)),
+                    Node::Pile(p) => p.lines.iter().for_each(|line| walk(line, codes)),
+                    Node::Char(_) | Node::Placeholder => {}
+                }
+            }
+        }
+        let mut codes = vec![];
+        for line in self.tree() {
+            walk(&line, &mut codes);
+        }
+        codes
+    }
+}
+
+/// Line of a template slot without LINE record: a pile written in place of the
+/// LINE, or a placeholder `fill_slots` adds.
+static SLOT_LINE: MTLine = MTLine { nudge: (0, 0), line_spacing: 0, null: false, ruler: None };
+
+/// Builds the top-level lines of an equation, templates short of slots getting
+/// placeholders up to their slot count when `fill_slots` is set.
+pub(crate) fn build(records: &[MTRecords], fill_slots: bool) -> Vec<Line<'_>> {
+    let mut builder = Builder { records, pos: 0, fill_slots };
+    let mut lines = vec![];
+    while let Some(record) = builder.next() {
+        match record {
+            MTRecords::LINE(line) => lines.push(builder.line(line)),
+            // the lines of a top-level pile are the lines of the equation
+            MTRecords::PILE(pile) => lines.extend(builder.pile(pile).lines),
+            MTRecords::END => break,
+            _ => {}
+        }
+    }
+    lines
+}
+
+struct Builder<'a> {
+    records: &'a [MTRecords],
+    pos: usize,
+    fill_slots: bool,
+}
+
+impl<'a> Builder<'a> {
+    fn next(&mut self) -> Option<&'a MTRecords> {
+        let record = self.records.get(self.pos);
+        self.pos += 1;
+        record
+    }
+
+    fn line(&mut self, line: &'a MTLine) -> Line<'a> {
+        let nodes = match line.null {
+            true => vec![],
+            false => self.objects(),
+        };
+        Line { line, nodes }
+    }
+
+    /// Object list up to and including its END record.
+    fn objects(&mut self) -> Vec<Node<'a>> {
+        let mut nodes = vec![];
+        while let Some(record) = self.next() {
+            match record {
+                MTRecords::END => break,
+                MTRecords::CHAR(ch) if ch.typeface == Typeface::Marker => {
+                    // past its embellishments, if any
+                    self.char(ch);
+                    nodes.push(Node::Marker(Marker { ch }))
+                }
+                MTRecords::CHAR(ch) => nodes.push(Node::Char(self.char(ch))),
+                MTRecords::TMPL(tmpl) => nodes.push(Node::Tmpl(self.tmpl(tmpl))),
+                MTRecords::PILE(pile) => nodes.push(Node::Pile(self.pile(pile))),
+                // a nested line without template is kept flat
+                MTRecords::LINE(line) => nodes.extend(self.line(line).nodes),
+                _ => {}
+            }
+        }
+        nodes
+    }
+
+    fn char(&mut self, ch: &'a MTChar) -> Char<'a> {
+        let mut embells = vec![];
+        if ch.options.contains(CharOptions::EMBELL) {
+            while let Some(record) = self.next() {
+                match record {
+                    MTRecords::END => break,
+                    MTRecords::EMBELL(embell) => embells.push(embell),
+                    _ => {}
+                }
+            }
+        }
+        Char { ch, embells }
+    }
+
+    fn pile(&mut self, pile: &'a MTPile) -> Pile<'a> {
+        let mut lines = vec![];
+        while let Some(record) = self.next() {
+            match record {
+                MTRecords::END => break,
+                MTRecords::LINE(line) => lines.push(self.line(line)),
+                _ => {}
+            }
+        }
+        Pile { pile, lines }
+    }
+
+    fn tmpl(&mut self, tmpl: &'a MTTmpl) -> Tmpl<'a> {
+        let mut t = Tmpl { tmpl, slots: vec![], chars: vec![] };
+        while let Some(record) = self.next() {
+            match record {
+                MTRecords::END => break,
+                MTRecords::LINE(line) => t.slots.push(self.line(line)),
+                MTRecords::PILE(pile) => t.slots.push(Line { line: &SLOT_LINE, nodes: vec![Node::Pile(self.pile(pile))] }),
+                MTRecords::CHAR(ch) => t.chars.push(self.char(ch).ch),
+                _ => {}
+            }
+        }
+        if self.fill_slots {
+            let count = slot_count(tmpl.selector).unwrap_or(0);
+            while t.slots.len() < count {
+                t.slots.push(Line { line: &SLOT_LINE, nodes: vec![Node::Placeholder] });
+            }
+        }
+        t
+    }
+}
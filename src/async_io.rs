@@ -0,0 +1,26 @@
+//! Async extraction, enabled by the `async` feature.
+//!
+//! Only the I/O is asynchronous: the OLE file is read into memory without blocking
+//! the runtime, parsing an equation then takes microseconds and runs in place.
+
+use std::path::Path;
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::eqn::MTEquation;
+use super::error::Error;
+
+impl MTEquation {
+    /// Async `from_ole`.
+    pub async fn from_ole_async<P: AsRef<Path>>(path: P) -> Result<MTEquation, Error> {
+        let file = tokio::fs::File::open(path).await?;
+        MTEquation::from_ole_reader_async(file).await
+    }
+
+    /// Async `from_ole_reader`, e.g. over a network stream or an upload body.
+    pub async fn from_ole_reader_async<R: AsyncRead + Unpin>(mut readable: R) -> Result<MTEquation, Error> {
+        let mut buf = vec![];
+        readable.read_to_end(&mut buf).await?;
+        MTEquation::from_ole_reader(&buf[..])
+    }
+}
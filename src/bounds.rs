@@ -0,0 +1,196 @@
+//! Size estimate of an equation, for renderers reserving layout space before the
+//! equation is actually typeset.
+//!
+//! Nothing is measured: characters get average advance widths, scripts are scaled
+//! down, fractions stack, and so on. Expect the result to be within a few tenths of
+//! an em of the typeset size for ordinary equations.
+
+use super::ast::{Line, Node, Pile, Tmpl};
+use super::constants::selectors::*;
+use super::eqn::{EquationLine, MTEquation, Typeface};
+
+/// Estimated extent in ems, around the baseline.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Bounds {
+    pub width: f32,
+    /// above the baseline
+    pub ascent: f32,
+    /// below the baseline
+    pub descent: f32,
+}
+
+impl Bounds {
+    pub fn height(&self) -> f32 {
+        self.ascent + self.descent
+    }
+
+    /// `self` followed by `other` on the same baseline.
+    fn beside(self, other: Bounds) -> Bounds {
+        Bounds {
+            width: self.width + other.width,
+            ascent: self.ascent.max(other.ascent),
+            descent: self.descent.max(other.descent),
+        }
+    }
+
+    fn scale(self, factor: f32) -> Bounds {
+        Bounds {
+            width: self.width * factor,
+            ascent: self.ascent * factor,
+            descent: self.descent * factor,
+        }
+    }
+}
+
+/// Size of a letter, digit or other ordinary character.
+const CHAR: Bounds = Bounds { width: 0.5, ascent: 0.7, descent: 0.2 };
+/// Scripts and limits relative to full size.
+const SCRIPT: f32 = 0.7;
+/// Gap between stacked lines of the equation.
+const LINE_GAP: f32 = 0.25;
+
+impl MTEquation {
+    /// Estimated size of the whole equation, lines stacked.
+    pub fn estimate_bounds(&self) -> Bounds {
+        let lines: Vec<Bounds> = self.lines().map(|line| line.estimate_bounds()).collect();
+        stack(&lines)
+    }
+}
+
+impl EquationLine<'_> {
+    pub fn estimate_bounds(&self) -> Bounds {
+        line(&self.line)
+    }
+}
+
+pub(crate) fn line(line: &Line) -> Bounds {
+    line.nodes.iter().map(node).fold(Bounds::default(), Bounds::beside)
+}
+
+/// Lines one below the other, on the baseline of the first one.
+fn stack(lines: &[Bounds]) -> Bounds {
+    let mut bounds = lines.iter().fold(Bounds::default(), |stack, line| Bounds {
+        width: stack.width.max(line.width),
+        ascent: 0.0,
+        descent: stack.descent + line.height() + LINE_GAP,
+    });
+    if let Some(first) = lines.first() {
+        bounds.ascent = first.ascent;
+        bounds.descent -= first.ascent + LINE_GAP;
+    }
+    bounds
+}
+
+/// Piles are centered on the math axis, whatever their alignment says.
+fn pile(pile: &Pile) -> Bounds {
+    let lines: Vec<Bounds> = pile.lines.iter().map(line).collect();
+    let stacked = stack(&lines);
+    let half = stacked.height() / 2.0;
+    Bounds { width: stacked.width, ascent: half + 0.25, descent: half - 0.25 }
+}
+
+fn slot(slot: Option<&Line>) -> Bounds {
+    slot.map(line).unwrap_or_default()
+}
+
+fn node(node: &Node) -> Bounds {
+    match node {
+        Node::Char(c) => {
+            let space = c.ch.typeface == Typeface::Space;
+            let wide = matches!(c.ch.mtcode, Some(0x2190..=0x22FF | 0x2B | 0x3C..=0x3E));
+            let mut bounds = CHAR;
+            if space {
+                bounds.width = 0.17;
+            } else if wide {
+                // relations and operators come with spacing around them
+                bounds.width = 0.78;
+            }
+            // embellishments above or below
+            if !c.embells.is_empty() {
+                bounds.ascent += 0.15;
+            }
+            bounds
+        }
+        Node::Tmpl(t) => tmpl(t),
+        Node::Pile(p) => pile(p),
+        Node::Marker(_) => Bounds::default(),
+        Node::Placeholder => CHAR,
+    }
+}
+
+fn tmpl(t: &Tmpl) -> Bounds {
+    if let Some(fence) = t.fence() {
+        let body = slot(fence.body);
+        let fences = (fence.left as u8 + fence.right as u8) as f32;
+        return Bounds { width: body.width + 0.4 * fences, ..body.beside(CHAR) };
+    }
+    if let Some(scripts) = t.scripts() {
+        let sub = slot(scripts.sub).scale(SCRIPT);
+        let sup = slot(scripts.sup).scale(SCRIPT);
+        return Bounds {
+            width: sub.width.max(sup.width),
+            ascent: if scripts.sup.is_some() { 0.45 + sup.height() } else { 0.0 },
+            descent: if scripts.sub.is_some() { 0.15 + sub.height() } else { 0.0 },
+        };
+    }
+    if let Some(big_op) = t.big_op() {
+        let op = Bounds { width: 1.0, ascent: 1.0, descent: 0.4 };
+        let op = limits(op, slot(big_op.lower), slot(big_op.upper), big_op.over_under);
+        return op.beside(slot(big_op.body));
+    }
+    if let Some(limit) = t.limit() {
+        return limits(slot(limit.name), slot(limit.lower), slot(limit.upper), true);
+    }
+    match t.tmpl.selector {
+        TM_FRACT => {
+            let (num, den) = (slot(t.slot(0)), slot(t.slot(1)));
+            Bounds {
+                width: num.width.max(den.width) + 0.2,
+                ascent: 0.25 + num.height(),
+                descent: den.height() - 0.25,
+            }
+        }
+        TM_ROOT => {
+            let radicand = slot(t.slot(0));
+            let degree = slot(t.slot(1)).scale(SCRIPT);
+            Bounds {
+                width: radicand.width + 0.6 + degree.width.max(0.2) - 0.2,
+                ascent: radicand.ascent + 0.15,
+                descent: radicand.descent,
+            }
+        }
+        TM_HBRACE | TM_HBRACK | TM_ARROW => {
+            // a stretched glyph and labels stacked on the main slot
+            let slots: Vec<Bounds> = t.slots.iter().map(line).collect();
+            let body = slots.first().copied().unwrap_or(CHAR);
+            let labels = slots.iter().skip(1).map(|s| s.scale(SCRIPT).height()).sum::<f32>();
+            Bounds {
+                width: slots.iter().map(|s| s.width).fold(1.0, f32::max),
+                ascent: body.ascent + 0.3 + labels,
+                descent: body.descent,
+            }
+        }
+        TM_BOX | TM_STRIKE => {
+            let body = slot(t.slot(0));
+            Bounds { width: body.width + 0.3, ascent: body.ascent + 0.15, descent: body.descent + 0.15 }
+        }
+        _ => t.slots.iter().map(line).fold(Bounds::default(), Bounds::beside),
+    }
+}
+
+/// `op` with limits above and below it, or as side scripts.
+fn limits(op: Bounds, lower: Bounds, upper: Bounds, over_under: bool) -> Bounds {
+    let (lower, upper) = (lower.scale(SCRIPT), upper.scale(SCRIPT));
+    match over_under {
+        true => Bounds {
+            width: op.width.max(lower.width).max(upper.width),
+            ascent: op.ascent + upper.height(),
+            descent: op.descent + lower.height(),
+        },
+        false => Bounds {
+            width: op.width + lower.width.max(upper.width),
+            ascent: op.ascent.max(0.45 + upper.height()),
+            descent: op.descent.max(lower.height()),
+        },
+    }
+}
@@ -0,0 +1,64 @@
+//! What the current build supports, so a corpus can be checked before converting it.
+
+use super::constants::record_types::*;
+
+/// Record types, template selectors and output targets supported by this build.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// Record types whose payload is decoded (see `constants::record_types`).
+    pub record_types: Vec<u8>,
+    /// Template selectors with a dedicated translation (see `constants::selectors`),
+    /// others keep their slots but lose the template's meaning.
+    pub template_selectors: Vec<u8>,
+    /// Output targets compiled in through cargo features.
+    pub output_targets: Vec<&'static str>,
+}
+
+impl Capabilities {
+    pub fn supports_record(&self, tag: u8) -> bool {
+        self.record_types.contains(&tag)
+    }
+
+    pub fn supports_selector(&self, selector: u8) -> bool {
+        self.template_selectors.contains(&selector)
+    }
+
+    pub fn supports_target(&self, target: &str) -> bool {
+        self.output_targets.contains(&target)
+    }
+}
+
+/// Record types decoded by `MTEquation::parse`, keep in sync with it.
+/// RULER is decoded after lines and piles only.
+const DECODED_RECORDS: &[u8] = &[
+    END, LINE, CHAR, TMPL, PILE, EMBELL, RULER, FONT_STYLE_DEF, SIZE, FULL, SUB, SUB2, SYM, SUBSYM,
+    COLOR, COLOR_DEF, FONT_DEF, EQN_PREFS, ENCODING_DEF,
+];
+
+pub fn capabilities() -> Capabilities {
+    let output_targets = vec![
+        #[cfg(feature = "latex")]
+        "latex",
+        #[cfg(feature = "mathml")]
+        "mathml",
+    ];
+
+    Capabilities {
+        record_types: DECODED_RECORDS.to_vec(),
+        template_selectors: translated_selectors(),
+        output_targets,
+    }
+}
+
+/// Selectors translated by any of the enabled output targets.
+fn translated_selectors() -> Vec<u8> {
+    #[allow(unused_mut)]
+    let mut selectors = vec![];
+    #[cfg(feature = "latex")]
+    selectors.extend_from_slice(super::latex::TRANSLATED_SELECTORS);
+    #[cfg(feature = "mathml")]
+    selectors.extend_from_slice(super::mathml::TRANSLATED_SELECTORS);
+    selectors.sort_unstable();
+    selectors.dedup();
+    selectors
+}
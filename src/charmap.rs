@@ -0,0 +1,239 @@
+//! Mapping of character codes to Unicode, per font encoding.
+//!
+//! A CHAR record carries its MTCode (Unicode with MathType extensions) and/or a
+//! position in the font's own encoding. Institutions with private math fonts can
+//! supply a `CharMapper` for their encodings, `DefaultCharMapper` covers the
+//! encodings MathType ships with.
+
+use super::eqn::{MTChar, MTEquation, MTRecords};
+
+/// Result of mapping a character code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MappedChar {
+    /// A Unicode character, translated like any other.
+    Char(char),
+    /// Output written as is by the LaTeX backend, for glyphs without a Unicode counterpart.
+    Latex(String),
+}
+
+/// Maps a character code of a font encoding (as named by ENCODING_DEF records:
+/// "MTCode", "Symbol", "MTExtra", or a custom name) to output.
+pub trait CharMapper {
+    fn map(&self, encoding: &str, code: u16) -> Option<MappedChar>;
+}
+
+/// MTCode, Symbol and MTExtra, the encodings MathType predefines.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultCharMapper;
+
+impl CharMapper for DefaultCharMapper {
+    fn map(&self, encoding: &str, code: u16) -> Option<MappedChar> {
+        match encoding {
+            "MTCode" => std::char::from_u32(code as u32).map(MappedChar::Char),
+            "Symbol" => symbol_font(code as u8).map(MappedChar::Char),
+            // MathType writes the MTCode of MT Extra characters, which is mapped as
+            // "MTCode"; their bare font positions are left to custom mappers.
+            "MTExtra" => None,
+            _ => None,
+        }
+    }
+}
+
+/// Encoding and code a character is mapped with: its MTCode when it has one,
+/// its font position otherwise.
+pub(crate) fn char_code<'e>(eqn: &'e MTEquation, ch: &MTChar) -> Option<(&'e str, u16)> {
+    let encoding = || eqn.encoding(ch.typeface).unwrap_or("Unknown");
+    match (ch.mtcode, ch.fp8, ch.fp16) {
+        (Some(code), _, _) => Some(("MTCode", code)),
+        (None, Some(code), _) => Some((encoding(), code as u16)),
+        (None, None, Some(code)) => Some((encoding(), code)),
+        (None, None, None) => None,
+    }
+}
+
+/// A character through `mapper`, then the default mapper.
+pub(crate) fn resolve(eqn: &MTEquation, ch: &MTChar, mapper: Option<&dyn CharMapper>) -> Option<MappedChar> {
+    let (encoding, code) = char_code(eqn, ch)?;
+    mapper.and_then(|mapper| mapper.map(encoding, code))
+        .or_else(|| DefaultCharMapper.map(encoding, code))
+}
+
+impl MTEquation {
+    /// CHAR records of the equation in stream order, each with its Unicode character
+    /// when the default mapping knows it: `None` for glyphs without a Unicode
+    /// counterpart and for positions in fonts of unknown encodings.
+    pub fn chars(&self) -> impl Iterator<Item = (&MTChar, Option<char>)> {
+        self.records().filter_map(move |record| match record {
+            MTRecords::CHAR(ch) => Some((ch, match resolve(self, ch, None) {
+                Some(MappedChar::Char(c)) => Some(c),
+                _ => None,
+            })),
+            _ => None,
+        })
+    }
+}
+
+/// Unicode of a position in the Symbol font (Adobe Symbol encoding), extension
+/// pieces of large delimiters excepted.
+pub(crate) fn symbol_font(code: u8) -> Option<char> {
+    let c = match code {
+        0x22 => '\u{2200}',
+        0x24 => '\u{2203}',
+        0x27 => '\u{220B}',
+        0x2A => '\u{2217}',
+        0x2D => '\u{2212}',
+        0x40 => '\u{2245}',
+        0x41 => '\u{0391}',
+        0x42 => '\u{0392}',
+        0x43 => '\u{03A7}',
+        0x44 => '\u{0394}',
+        0x45 => '\u{0395}',
+        0x46 => '\u{03A6}',
+        0x47 => '\u{0393}',
+        0x48 => '\u{0397}',
+        0x49 => '\u{0399}',
+        0x4A => '\u{03D1}',
+        0x4B => '\u{039A}',
+        0x4C => '\u{039B}',
+        0x4D => '\u{039C}',
+        0x4E => '\u{039D}',
+        0x4F => '\u{039F}',
+        0x50 => '\u{03A0}',
+        0x51 => '\u{0398}',
+        0x52 => '\u{03A1}',
+        0x53 => '\u{03A3}',
+        0x54 => '\u{03A4}',
+        0x55 => '\u{03A5}',
+        0x56 => '\u{03C2}',
+        0x57 => '\u{03A9}',
+        0x58 => '\u{039E}',
+        0x59 => '\u{03A8}',
+        0x5A => '\u{0396}',
+        0x5C => '\u{2234}',
+        0x5E => '\u{22A5}',
+        0x61 => '\u{03B1}',
+        0x62 => '\u{03B2}',
+        0x63 => '\u{03C7}',
+        0x64 => '\u{03B4}',
+        0x65 => '\u{03B5}',
+        0x66 => '\u{03C6}',
+        0x67 => '\u{03B3}',
+        0x68 => '\u{03B7}',
+        0x69 => '\u{03B9}',
+        0x6A => '\u{03D5}',
+        0x6B => '\u{03BA}',
+        0x6C => '\u{03BB}',
+        0x6D => '\u{03BC}',
+        0x6E => '\u{03BD}',
+        0x6F => '\u{03BF}',
+        0x70 => '\u{03C0}',
+        0x71 => '\u{03B8}',
+        0x72 => '\u{03C1}',
+        0x73 => '\u{03C3}',
+        0x74 => '\u{03C4}',
+        0x75 => '\u{03C5}',
+        0x76 => '\u{03D6}',
+        0x77 => '\u{03C9}',
+        0x78 => '\u{03BE}',
+        0x79 => '\u{03C8}',
+        0x7A => '\u{03B6}',
+        0x7E => '\u{223C}',
+        0x20..=0x7D => code as char,
+        0xA0 => '\u{20AC}',
+        0xA1 => '\u{03D2}',
+        0xA2 => '\u{2032}',
+        0xA3 => '\u{2264}',
+        0xA4 => '\u{2044}',
+        0xA5 => '\u{221E}',
+        0xA6 => '\u{0192}',
+        0xA7 => '\u{2663}',
+        0xA8 => '\u{2666}',
+        0xA9 => '\u{2665}',
+        0xAA => '\u{2660}',
+        0xAB => '\u{2194}',
+        0xAC => '\u{2190}',
+        0xAD => '\u{2191}',
+        0xAE => '\u{2192}',
+        0xAF => '\u{2193}',
+        0xB0 => '\u{00B0}',
+        0xB1 => '\u{00B1}',
+        0xB2 => '\u{2033}',
+        0xB3 => '\u{2265}',
+        0xB4 => '\u{00D7}',
+        0xB5 => '\u{221D}',
+        0xB6 => '\u{2202}',
+        0xB7 => '\u{2022}',
+        0xB8 => '\u{00F7}',
+        0xB9 => '\u{2260}',
+        0xBA => '\u{2261}',
+        0xBB => '\u{2248}',
+        0xBC => '\u{2026}',
+        0xBF => '\u{21B5}',
+        0xC0 => '\u{2135}',
+        0xC1 => '\u{2111}',
+        0xC2 => '\u{211C}',
+        0xC3 => '\u{2118}',
+        0xC4 => '\u{2297}',
+        0xC5 => '\u{2295}',
+        0xC6 => '\u{2205}',
+        0xC7 => '\u{2229}',
+        0xC8 => '\u{222A}',
+        0xC9 => '\u{2283}',
+        0xCA => '\u{2287}',
+        0xCB => '\u{2284}',
+        0xCC => '\u{2282}',
+        0xCD => '\u{2286}',
+        0xCE => '\u{2208}',
+        0xCF => '\u{2209}',
+        0xD0 => '\u{2220}',
+        0xD1 => '\u{2207}',
+        0xD2 | 0xE2 => '\u{00AE}',
+        0xD3 | 0xE3 => '\u{00A9}',
+        0xD4 | 0xE4 => '\u{2122}',
+        0xD5 => '\u{220F}',
+        0xD6 => '\u{221A}',
+        0xD7 => '\u{22C5}',
+        0xD8 => '\u{00AC}',
+        0xD9 => '\u{2227}',
+        0xDA => '\u{2228}',
+        0xDB => '\u{21D4}',
+        0xDC => '\u{21D0}',
+        0xDD => '\u{21D1}',
+        0xDE => '\u{21D2}',
+        0xDF => '\u{21D3}',
+        0xE0 => '\u{25CA}',
+        0xE1 => '\u{2329}',
+        0xE5 => '\u{2211}',
+        0xF1 => '\u{232A}',
+        0xF2 => '\u{222B}',
+        _ => return None,
+    };
+    Some(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::eqn::{MTEquation, MTRecords};
+
+    /// "中文x" in a font named "宋体", written in GBK.
+    fn cjk_text() -> MTEquation {
+        MTEquation::from_bytes(include_bytes!("../tests/fixtures/cjk_text.mtef")).unwrap()
+    }
+
+    #[test]
+    fn font_names_are_gbk() {
+        let eqn = cjk_text();
+        let names: Vec<&str> = eqn.records().filter_map(|record| match record {
+            MTRecords::FONT_DEF { name, .. } => Some(name.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(names, ["宋体"]);
+    }
+
+    #[test]
+    fn cjk_mtcodes_map_to_unicode() {
+        let eqn = cjk_text();
+        let chars: Vec<Option<char>> = eqn.chars().map(|(_, c)| c).collect();
+        assert_eq!(chars, [Some('中'), Some('文'), Some('x')]);
+    }
+}
@@ -0,0 +1,72 @@
+//! What each record contributes to an equation, for consumers that only want part
+//! of it: search and CAS export read the structure and can drop how MathType laid
+//! it out, migrations between MathType versions keep everything.
+
+use super::eqn::{CharOptions, MTEquation, MTRecords};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RecordClass {
+    /// Lines, characters, templates, piles, embellishments and the ENDs closing them.
+    Structural,
+    /// Typesizes and colors. Nudges, line spacing and rulers, kept in structural
+    /// records, are presentation too.
+    Presentation,
+    /// Encodings, fonts, styles, colors and preferences referred to by other records.
+    Definition,
+    /// Records of types the parser does not know.
+    Unknown,
+}
+
+impl MTRecords {
+    pub fn class(&self) -> RecordClass {
+        match self {
+            MTRecords::END | MTRecords::LINE(_) | MTRecords::CHAR(_) | MTRecords::TMPL(_) | MTRecords::PILE(_)
+            | MTRecords::EMBELL(_) => RecordClass::Structural,
+            MTRecords::SIZE(_) | MTRecords::FULL | MTRecords::SUB | MTRecords::SUB2 | MTRecords::SYM
+            | MTRecords::SUBSYM | MTRecords::COLOR(_) => RecordClass::Presentation,
+            MTRecords::ENCODING_DEF(_) | MTRecords::FONT_DEF { .. } | MTRecords::FONT_STYLE_DEF { .. }
+            | MTRecords::EQN_PREFS { .. } | MTRecords::COLOR_DEF(_) => RecordClass::Definition,
+            MTRecords::FUTURE(_) => RecordClass::Unknown,
+        }
+    }
+}
+
+impl MTEquation {
+    /// Drops the records of `class`. Stripping presentation also resets the nudges,
+    /// line spacing and rulers of structural records; structural records themselves
+    /// hold the equation together and are never stripped.
+    ///
+    /// Stripped definitions leave characters in the predefined fonts and styles, so
+    /// translation output may differ, and MathType does not read MTEF without
+    /// EQN_PREFS: strip them only for consumers that ignore fonts.
+    pub fn strip(&mut self, class: RecordClass) {
+        match class {
+            RecordClass::Structural => return,
+            RecordClass::Presentation => self.records.iter_mut().for_each(strip_layout),
+            RecordClass::Definition | RecordClass::Unknown => {}
+        }
+        self.records.retain(|record| record.class() != class);
+    }
+}
+
+/// Resets the layout fields of a structural record.
+fn strip_layout(record: &mut MTRecords) {
+    match record {
+        MTRecords::LINE(line) => {
+            line.nudge = (0, 0);
+            line.line_spacing = 0;
+            line.ruler = None;
+        }
+        MTRecords::CHAR(ch) => {
+            ch.nudge = (0, 0);
+            ch.options.remove(CharOptions::NUDGE);
+        }
+        MTRecords::TMPL(tmpl) => tmpl.nudge = (0, 0),
+        MTRecords::PILE(pile) => {
+            pile.nudge = (0, 0);
+            pile.ruler = None;
+        }
+        MTRecords::EMBELL(embell) => embell.nudge = (0, 0),
+        _ => {}
+    }
+}
@@ -0,0 +1,56 @@
+//! Equations on the Windows clipboard, enabled by the `clipboard` feature on Windows:
+//! MathType and Word paste data in the "MathType EF" format as an equation.
+
+use windows_sys::Win32::Foundation::GlobalFree;
+use windows_sys::Win32::System::DataExchange::{CloseClipboard, EmptyClipboard, OpenClipboard,
+                                               RegisterClipboardFormatW, SetClipboardData};
+use windows_sys::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+
+use super::eqn::MTEquation;
+use super::error::Error;
+
+/// Clipboard format MathType registers for MTEF, the bytes of `to_bytes`.
+const MATHTYPE_EF: &str = "MathType EF";
+
+/// Replaces the clipboard content with `eqn`, ready to paste into MathType or Word.
+/// The MTEF must pass `validate_strict`, MathType may crash on anything else.
+pub fn put_equation_on_clipboard(eqn: &MTEquation) -> Result<(), Error> {
+    let mtef = eqn.to_bytes_strict()?;
+    let name: Vec<u16> = MATHTYPE_EF.encode_utf16().chain(Some(0)).collect();
+    unsafe {
+        let format = RegisterClipboardFormatW(name.as_ptr());
+        if format == 0 {
+            return Err(last_error());
+        }
+        let mem = GlobalAlloc(GMEM_MOVEABLE, mtef.len());
+        if mem.is_null() {
+            return Err(last_error());
+        }
+        let data = GlobalLock(mem);
+        if data.is_null() {
+            let error = last_error();
+            GlobalFree(mem);
+            return Err(error);
+        }
+        std::ptr::copy_nonoverlapping(mtef.as_ptr(), data as *mut u8, mtef.len());
+        GlobalUnlock(mem);
+        if OpenClipboard(std::ptr::null_mut()) == 0 {
+            let error = last_error();
+            GlobalFree(mem);
+            return Err(error);
+        }
+        let placed = EmptyClipboard() != 0 && !SetClipboardData(format, mem).is_null();
+        let error = last_error();
+        CloseClipboard();
+        // the clipboard owns the memory once the data is placed
+        if !placed {
+            GlobalFree(mem);
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+fn last_error() -> Error {
+    Error::IOError(std::io::Error::last_os_error())
+}
@@ -0,0 +1,155 @@
+//! Colors of COLOR_DEF records as the LaTeX and MathML outputs write them.
+//!
+//! MathType names the colors of its palette, "Red", "Dark Blue"... A `ColorPalette`
+//! writes the names it knows as their xcolor and CSS counterparts, and every other
+//! color, unnamed or named by the user, by its values: `[RGB]{..}` or `[cmyk]{..}`
+//! for xcolor and `#rrggbb` for CSS, which has no CMYK.
+
+use std::collections::HashMap;
+
+use super::eqn::{ColorDefOptions, ColorValue, MTColorDef, MTEquation, MTRecords};
+
+/// MathType's color menu, with the xcolor and CSS colors of the same values.
+const MATHTYPE_COLORS: &[(&str, &str, &str)] = &[
+    ("Black", "black", "black"),
+    ("White", "white", "white"),
+    ("Red", "red", "red"),
+    ("Green", "green", "lime"),
+    ("Blue", "blue", "blue"),
+    ("Yellow", "yellow", "yellow"),
+    ("Cyan", "cyan", "cyan"),
+    ("Magenta", "magenta", "magenta"),
+    ("Dark Red", "red!50!black", "maroon"),
+    ("Dark Green", "green!50!black", "green"),
+    ("Dark Blue", "blue!50!black", "navy"),
+    ("Dark Yellow", "olive", "olive"),
+    ("Dark Cyan", "teal", "teal"),
+    ("Dark Magenta", "magenta!50!black", "purple"),
+    ("Gray", "gray", "gray"),
+    ("Light Gray", "lightgray", "silver"),
+];
+
+/// A color by name, for xcolor (`\color{..}`) and for CSS.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamedColor {
+    /// a color name or expression of xcolor, e.g. "red" or "blue!50!black"
+    pub xcolor: String,
+    pub css: String,
+}
+
+/// Names of colors, matched without regard to case; MathType's palette by default.
+#[derive(Debug, Clone)]
+pub struct ColorPalette {
+    names: HashMap<String, NamedColor>,
+}
+
+impl Default for ColorPalette {
+    fn default() -> ColorPalette {
+        let mut palette = ColorPalette::empty();
+        for (name, xcolor, css) in MATHTYPE_COLORS {
+            palette.insert(name, xcolor, css);
+        }
+        palette
+    }
+}
+
+impl ColorPalette {
+    /// A palette knowing no names, writing every color by its values.
+    pub fn empty() -> ColorPalette {
+        ColorPalette { names: HashMap::new() }
+    }
+
+    /// Adds `name`, or replaces what it stood for.
+    pub fn insert(&mut self, name: &str, xcolor: &str, css: &str) {
+        self.names.insert(key(name), NamedColor { xcolor: xcolor.to_string(), css: css.to_string() });
+    }
+
+    pub fn get(&self, name: &str) -> Option<&NamedColor> {
+        self.names.get(&key(name))
+    }
+
+    fn named(&self, def: &MTColorDef) -> Option<&NamedColor> {
+        self.get(def.name.as_deref()?)
+    }
+
+    /// Argument of `\color` and `\textcolor` for `def`: `{red}`, `[RGB]{255,0,0}`
+    /// or `[cmyk]{0,1,1,0}`.
+    pub fn latex(&self, def: &MTColorDef) -> String {
+        if let Some(named) = self.named(def) {
+            return format!("{{{}}}", named.xcolor);
+        }
+        match def.value {
+            ColorValue::Rgb(rgb) => format!("[RGB]{{{}}}", rgb.map(|c| byte(c).to_string()).join(",")),
+            ColorValue::Cmyk(cmyk) => format!("[cmyk]{{{}}}", cmyk.map(fraction).join(",")),
+        }
+    }
+
+    /// CSS color of `def`: a name, or `#rrggbb`, CMYK converted without a profile.
+    pub fn css(&self, def: &MTColorDef) -> String {
+        if let Some(named) = self.named(def) {
+            return named.css.clone();
+        }
+        let [r, g, b] = match def.value {
+            ColorValue::Rgb(rgb) => rgb.map(byte),
+            ColorValue::Cmyk([c, m, y, k]) => {
+                let black = 1.0 - f32::from(k) / 65535.0;
+                [c, m, y].map(|c| ((1.0 - f32::from(c) / 65535.0) * black * 255.0).round() as u8)
+            }
+        };
+        format!("#{:02x}{:02x}{:02x}", r, g, b)
+    }
+}
+
+fn key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// A 16-bit component scaled to 0..255.
+fn byte(component: u16) -> u8 {
+    ((u32::from(component) * 255 + 32767) / 65535) as u8
+}
+
+/// A 16-bit component scaled to 0..1, to three places.
+fn fraction(component: u16) -> String {
+    let s = format!("{:.3}", f32::from(component) / 65535.0);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+impl MTColorDef {
+    /// Option flags the definition is written with.
+    pub fn options(&self) -> ColorDefOptions {
+        let mut options = ColorDefOptions::default();
+        if let ColorValue::Cmyk(_) = self.value {
+            options |= ColorDefOptions::CMYK;
+        }
+        if self.spot {
+            options |= ColorDefOptions::SPOT;
+        }
+        if self.name.is_some() {
+            options |= ColorDefOptions::NAME;
+        }
+        options
+    }
+
+    pub fn value(&self) -> ColorValue {
+        self.value
+    }
+
+    pub fn is_spot(&self) -> bool {
+        self.spot
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+}
+
+impl MTEquation {
+    /// COLOR_DEF records of the equation, in stream order.
+    pub fn color_defs(&self) -> impl Iterator<Item = &MTColorDef> {
+        self.records.iter().filter_map(|record| match record {
+            MTRecords::COLOR_DEF(def) => Some(def),
+            _ => None,
+        })
+    }
+}
@@ -0,0 +1,149 @@
+//! CompObj stream
+//!
+//! Every embedded OLE object carries a `\x01CompObj` stream naming the class it belongs to.
+//! Containers such as Word's ObjectPool mix equations with charts, drawings and packages,
+//! reading this stream is enough to tell them apart without touching the native data.
+//!
+//! | field | size | description |
+//! | ----- | ---- | ----------- |
+//! |Reserved1        |4 |0xFFFE0001|
+//! |Version          |4 |format version|
+//! |Marker           |4 |0xFFFFFFFF|
+//! |CLSID            |16|class identifier|
+//! |AnsiUserType     |variable|length-prefixed display name, e.g. "MathType 6.0 Equation"|
+//! |AnsiClipboardFormat|variable|marker/length followed by a format id or name|
+//! |ProgID           |variable|length-prefixed ProgID, e.g. "Equation.DSMT4"|
+
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use super::error::Error;
+
+/// Name of the stream holding the object class information.
+const COMP_OBJ: &str = "\u{1}CompObj";
+
+/// {0002CE03-0000-0000-C000-000000000046}, Equation.DSMT4 and later MathType versions
+pub const CLSID_MATHTYPE: Clsid = Clsid([0x03, 0xCE, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46]);
+/// {0002CE02-0000-0000-C000-000000000046}, Microsoft Equation 3.0
+pub const CLSID_EQUATION_EDITOR: Clsid = Clsid([0x02, 0xCE, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46]);
+
+/// A COM class identifier, stored as in the file (little-endian first three groups).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Clsid(pub [u8; 16]);
+
+impl fmt::Display for Clsid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let b = &self.0;
+        write!(f, "{{{:02X}{:02X}{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-",
+               b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9])?;
+        for x in &b[10..] {
+            write!(f, "{:02X}", x)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// What an embedded object is, as far as equation extraction is concerned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObjectKind {
+    /// MathType equation (Equation.DSMT*)
+    MathType,
+    /// Microsoft Equation Editor 3.x equation (Equation.3)
+    EquationEditor,
+    /// Anything else: Excel charts, Visio drawings, packages...
+    Other(Clsid),
+}
+
+impl ObjectKind {
+    /// Whether the object stores its data as MTEF in an "Equation Native" stream.
+    pub fn is_equation(&self) -> bool {
+        match self {
+            ObjectKind::MathType | ObjectKind::EquationEditor => true,
+            ObjectKind::Other(_) => false,
+        }
+    }
+}
+
+/// Parsed content of a `\x01CompObj` stream.
+#[derive(Debug, Clone)]
+pub struct CompObj {
+    pub clsid: Clsid,
+    pub user_type: String,
+    pub prog_id: Option<String>,
+}
+
+impl CompObj {
+    pub fn parse(buf: &[u8]) -> Result<CompObj, Error> {
+        if buf.len() < 28 {
+            return Err(Error::BadSizeValue("CompObj"));
+        }
+        let mut clsid = [0u8; 16];
+        clsid.copy_from_slice(&buf[12..28]);
+
+        let mut cur = Cursor::new(&buf[28..]);
+        let user_type = read_length_prefixed_string(&mut cur)?;
+        // clipboard format: 0 for none, -1/-2 followed by a standard format id, else a name
+        match cur.read_u32::<LittleEndian>()? {
+            0 => {}
+            0xFFFF_FFFF | 0xFFFF_FFFE => { cur.read_u32::<LittleEndian>()?; }
+            len => { cur.set_position(cur.position() + len as u64); }
+        }
+        // older producers stop after the clipboard format
+        let prog_id = read_length_prefixed_string(&mut cur).ok().filter(|s| !s.is_empty());
+
+        Ok(CompObj { clsid: Clsid(clsid), user_type, prog_id })
+    }
+
+    pub fn kind(&self) -> ObjectKind {
+        match self.prog_id {
+            Some(ref p) if p.starts_with("Equation.DSMT") => return ObjectKind::MathType,
+            Some(ref p) if p.starts_with("Equation.3") => return ObjectKind::EquationEditor,
+            _ => {}
+        }
+        match self.clsid {
+            CLSID_MATHTYPE => ObjectKind::MathType,
+            CLSID_EQUATION_EDITOR => ObjectKind::EquationEditor,
+            clsid => ObjectKind::Other(clsid),
+        }
+    }
+}
+
+/// Lists the CompObj information of every object found in the OLE file, in directory order.
+pub fn embedded_objects<P: AsRef<Path>>(path: P) -> Result<Vec<CompObj>, Error> {
+    let reader = ole::Reader::new(std::fs::File::open(path)?).map_err(|_| Error::InvalidOLEFile)?;
+    let mut objects = vec![];
+    for entry in reader.iterate() {
+        if entry.name() == COMP_OBJ {
+            let buf = super::eqn::read_entry(&reader, entry)?;
+            objects.push(CompObj::parse(&buf)?);
+        }
+    }
+    Ok(objects)
+}
+
+/// Cheap pre-check before `MTEquation::from_ole`: true when the file holds an equation object.
+pub fn is_equation_object<P: AsRef<Path>>(path: P) -> bool {
+    match embedded_objects(path) {
+        Ok(objects) => objects.iter().any(|o| o.kind().is_equation()),
+        Err(_) => false,
+    }
+}
+
+fn read_length_prefixed_string(cur: &mut Cursor<&[u8]>) -> Result<String, Error> {
+    let len = cur.read_u32::<LittleEndian>()? as usize;
+    if len > cur.get_ref().len() {
+        return Err(Error::BadSizeValue("CompObj string"));
+    }
+    let mut buf = vec![0; len];
+    cur.read_exact(&mut buf)?;
+    // ANSI strings are null-terminated
+    while buf.last() == Some(&0) {
+        buf.pop();
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
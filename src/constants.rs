@@ -1,12 +1,15 @@
 //! MTEF version history:
 //!
 //! MTEF data exists in the following versions:
-//! - 0	MathType for Mac 1.x (this format is not described here)
-//! - 1	MathType for Mac 2.x and MathType for Windows 1.x
-//! - 2	MathType 3.x and Equation Editor 1.x
-//! - 3	Equation Editor 3.x (this format is not described here)
-//! - 4	MathType 3.5
-//! - 5	MathType 4.0 and later
+//! - 0 MathType for Mac 1.x (this format is not described here)
+//! - 1 MathType for Mac 2.x and MathType for Windows 1.x
+//! - 2 MathType 3.x and Equation Editor 1.x
+//! - 3 Equation Editor 3.x (this format is not described here)
+//! - 4 MathType 3.5
+//! - 5 MathType 4.0 and later
+
+use core::convert::TryFrom;
+use core::fmt;
 
 
 /// Record types:
@@ -35,55 +38,152 @@
 /// |17 |FONT_DEF       |font definition|
 /// |18 |EQN_PREFS      |equation preferences (sizes, styles, spacing)|
 /// |19 |ENCODING_DEF   |encoding definition|
-/// |≥ 100	|FUTURE	    |for future expansion (see below)|
+/// |≥ 100  |FUTURE     |for future expansion (see below)|
 ///
 /// If the record type is 100 or greater, it represents a record that will be defined in a future version of MTEF.
 /// For now, readers can assume that an unsigned integer follows the record type and is the number of bytes following it in the record (i.e. it doesn't include the record type and length).
 /// This makes it easy for software that reads MTEF to skip these records. Although it might be handy if all records had such a length value,
 /// it will only be present on future expansion records (i.e. those with record types ≥ 100).
 pub mod record_types {
+    use super::RecordType;
+
+    pub const END: u8 = RecordType::End as u8;
+    pub const LINE: u8 = RecordType::Line as u8;
+    pub const CHAR: u8 = RecordType::Char as u8;
+    pub const TMPL: u8 = RecordType::Tmpl as u8;
+    pub const PILE: u8 = RecordType::Pile as u8;
+    pub const MATRIX: u8 = RecordType::Matrix as u8;
+    pub const EMBELL: u8 = RecordType::Embell as u8;
+    pub const RULER: u8 = RecordType::Ruler as u8;
+    pub const FONT_STYLE_DEF: u8 = RecordType::FontStyleDef as u8;
+    pub const SIZE: u8 = RecordType::Size as u8;
+    pub const FULL: u8 = RecordType::Full as u8;
+    pub const SUB: u8 = RecordType::Sub as u8;
+    pub const SUB2: u8 = RecordType::Sub2 as u8;
+    pub const SYM: u8 = RecordType::Sym as u8;
+    pub const SUBSYM: u8 = RecordType::SubSym as u8;
+    pub const COLOR: u8 = RecordType::Color as u8;
+    pub const COLOR_DEF: u8 = RecordType::ColorDef as u8;
+    pub const FONT_DEF: u8 = RecordType::FontDef as u8;
+    pub const EQN_PREFS: u8 = RecordType::EqnPrefs as u8;
+    pub const ENCODING_DEF: u8 = RecordType::EncodingDef as u8;
+    /// first of the future expansion types
+    pub const FUTURE: u8 = RecordType::Future as u8;
+}
+
+/// Record types, see `record_types` for their values. Every tag from 100 up is a
+/// `Future` record; tags 20 to 99 are undefined and fail to convert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u8)]
+pub enum RecordType {
     /// end of MTEF, pile, line, embellishment list, or template
-    pub const END: u8 = 0;
+    End = 0,
     /// line (slot)
-    pub const LINE: u8 = 1;
+    Line = 1,
     /// character
-    pub const CHAR: u8 = 2;
+    Char = 2,
     /// template
-    pub const TMPL: u8 = 3;
+    Tmpl = 3,
     /// pile (vertical stack of lines)
-    pub const PILE: u8 = 4;
+    Pile = 4,
     /// matrix
-    pub const MATRIX: u8 = 5;
+    Matrix = 5,
     /// character embellishment (e.g. hat, prime)
-    pub const EMBELL: u8 = 6;
+    Embell = 6,
     /// ruler (tab-stop location)
-    pub const RULER: u8 = 7;
+    Ruler = 7,
     /// font/char style definition
-    pub const FONT_STYLE_DEF: u8 = 8;
+    FontStyleDef = 8,
     /// general size
-    pub const SIZE: u8 = 9;
+    Size = 9,
     /// full size
-    pub const FULL: u8 = 10;
+    Full = 10,
     /// subscript size
-    pub const SUB: u8 = 11;
+    Sub = 11,
     /// sub-subscript size
-    pub const SUB2: u8 = 12;
+    Sub2 = 12,
     /// symbol size
-    pub const SYM: u8 = 13;
+    Sym = 13,
     /// sub-symbol size
-    pub const SUBSYM: u8 = 14;
+    SubSym = 14,
     /// color
-    pub const COLOR: u8 = 15;
+    Color = 15,
     /// color definition
-    pub const COLOR_DEF: u8 = 16;
+    ColorDef = 16,
     /// font definition
-    pub const FONT_DEF: u8 = 17;
+    FontDef = 17,
     /// equation preferences (sizes, styles, spacing)
-    pub const EQN_PREFS: u8 = 18;
+    EqnPrefs = 18,
     /// encoding definition
-    pub const ENCODING_DEF: u8 = 19;
+    EncodingDef = 19,
     /// for future expansion
-    pub const FUTURE: u8 = 100;
+    Future = 100,
+}
+
+impl RecordType {
+    /// Name of the record type in the MTEF specification, e.g. "FONT_DEF".
+    pub fn name(self) -> &'static str {
+        match self {
+            RecordType::End => "END",
+            RecordType::Line => "LINE",
+            RecordType::Char => "CHAR",
+            RecordType::Tmpl => "TMPL",
+            RecordType::Pile => "PILE",
+            RecordType::Matrix => "MATRIX",
+            RecordType::Embell => "EMBELL",
+            RecordType::Ruler => "RULER",
+            RecordType::FontStyleDef => "FONT_STYLE_DEF",
+            RecordType::Size => "SIZE",
+            RecordType::Full => "FULL",
+            RecordType::Sub => "SUB",
+            RecordType::Sub2 => "SUB2",
+            RecordType::Sym => "SYM",
+            RecordType::SubSym => "SUBSYM",
+            RecordType::Color => "COLOR",
+            RecordType::ColorDef => "COLOR_DEF",
+            RecordType::FontDef => "FONT_DEF",
+            RecordType::EqnPrefs => "EQN_PREFS",
+            RecordType::EncodingDef => "ENCODING_DEF",
+            RecordType::Future => "FUTURE",
+        }
+    }
+}
+
+impl TryFrom<u8> for RecordType {
+    type Error = super::error::Error;
+
+    fn try_from(tag: u8) -> Result<RecordType, super::error::Error> {
+        Ok(match tag {
+            0 => RecordType::End,
+            1 => RecordType::Line,
+            2 => RecordType::Char,
+            3 => RecordType::Tmpl,
+            4 => RecordType::Pile,
+            5 => RecordType::Matrix,
+            6 => RecordType::Embell,
+            7 => RecordType::Ruler,
+            8 => RecordType::FontStyleDef,
+            9 => RecordType::Size,
+            10 => RecordType::Full,
+            11 => RecordType::Sub,
+            12 => RecordType::Sub2,
+            13 => RecordType::Sym,
+            14 => RecordType::SubSym,
+            15 => RecordType::Color,
+            16 => RecordType::ColorDef,
+            17 => RecordType::FontDef,
+            18 => RecordType::EqnPrefs,
+            19 => RecordType::EncodingDef,
+            100.. => RecordType::Future,
+            _ => return Err(super::error::Error::MalformedMTEF("undefined record type")),
+        })
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
 }
 
 /// Option values:
@@ -93,25 +193,25 @@ pub mod record_types {
 ///
 /// The option flag values are record-dependent:
 ///
-/// |value	|symbol	|description|
+/// |value  |symbol |description|
 /// |-----  |-----  |-----      |
 /// |Option flag values for all equation structure records:|
-/// |0x08	|MTEF_OPT_NUDGE	|nudge values follow tag|
+/// |0x08   |MTEF_OPT_NUDGE |nudge values follow tag|
 /// |Option flag values for CHAR records:|
-/// |0x01	|MTEF_OPT_CHAR_EMBELL	|character is followed by an embellishment list|
-/// |0x02	|MTEF_OPT_CHAR_FUNC_START	|character starts a function (sin, cos, etc.)|
-/// |0x04	|MTEF_OPT_CHAR_ENC_CHAR_8	|character is written with an 8-bit encoded value|
-/// |0x10	|MTEF_OPT_CHAR_ENC_CHAR_16	|character is written with an 16-bit encoded value|
-/// |0x20	|MTEF_OPT_CHAR_ENC_NO_MTCODE	|character is written without an 16-bit MTCode value|
+/// |0x01   |MTEF_OPT_CHAR_EMBELL   |character is followed by an embellishment list|
+/// |0x02   |MTEF_OPT_CHAR_FUNC_START   |character starts a function (sin, cos, etc.)|
+/// |0x04   |MTEF_OPT_CHAR_ENC_CHAR_8   |character is written with an 8-bit encoded value|
+/// |0x10   |MTEF_OPT_CHAR_ENC_CHAR_16  |character is written with an 16-bit encoded value|
+/// |0x20   |MTEF_OPT_CHAR_ENC_NO_MTCODE    |character is written without an 16-bit MTCode value|
 /// |Option flag values for LINE records:|
-/// |0x01	|MTEF_OPT_LINE_NULL	|line is a placeholder only (i.e. not displayed)|
-/// |0x04	|MTEF_OPT_LINE_LSPACE	|line spacing value follows tag|
+/// |0x01   |MTEF_OPT_LINE_NULL |line is a placeholder only (i.e. not displayed)|
+/// |0x04   |MTEF_OPT_LINE_LSPACE   |line spacing value follows tag|
 /// |Option flag values for LINE and PILE records:|
-/// |0x02	|MTEF_OPT_LP_RULER	|RULER record follows LINE or PILE record|
+/// |0x02   |MTEF_OPT_LP_RULER  |RULER record follows LINE or PILE record|
 /// |Option flag values for COLOR_DEF records:|
-/// |0x01	|MTEF_COLOR_CMYK	|color model is CMYK, else RGB|
-/// |0x02	|MTEF_COLOR_SPOT	|color is a spot color, else a process color|
-/// |0x04	|MTEF_COLOR_NAME	|color has a name, else no name|
+/// |0x01   |MTEF_COLOR_CMYK    |color model is CMYK, else RGB|
+/// |0x02   |MTEF_COLOR_SPOT    |color is a spot color, else a process color|
+/// |0x04   |MTEF_COLOR_NAME    |color has a name, else no name|
 pub mod options {
     pub const MTEF_OPT_NUDGE: u8 = 0x08;
     pub const MTEF_OPT_CHAR_EMBELL: u8 = 0x01;
@@ -155,16 +255,16 @@ pub mod typeface {
 /// Typesize values (sometimes referred to as lsizes) are used in several MTEF records.
 /// Not all values may be valid in a particular record. Their meaning is as follows:
 ///
-/// |value	|symbol	|description|
+/// |value  |symbol |description|
 /// |-----  |-----  |------ |
-/// |0	|SZ_FULL |full|
-/// |1	|SZ_SUB |subscript|
-/// |2	|SZ_SUB2 |sub-subscript|
-/// |3	|SZ_SYM |symbol|
-/// |4	|SZ_SUBSYM |sub-symbol|
-/// |5	|SZ_USER1 |user 1|
-/// |6	|SZ_USER2 |user 2|
-/// |7	|SZ_DELTA |delta increment|
+/// |0  |SZ_FULL |full|
+/// |1  |SZ_SUB |subscript|
+/// |2  |SZ_SUB2 |sub-subscript|
+/// |3  |SZ_SYM |symbol|
+/// |4  |SZ_SUBSYM |sub-symbol|
+/// |5  |SZ_USER1 |user 1|
+/// |6  |SZ_USER2 |user 2|
+/// |7  |SZ_DELTA |delta increment|
 pub mod typesize {
     /// full
     pub const SZ_FULL: u8 = 0;
@@ -183,3 +283,363 @@ pub mod typesize {
     /// delta increment
     pub const SZ_DELTA: u8 = 7;
 }
+
+/// Tab-stop types:
+///
+/// Each tab stop of a RULER record has a type, saying how the text following a tab
+/// character is aligned on the stop.
+///
+/// |value  |symbol |description|
+/// |-----  |-----  |------ |
+/// |0  |TAB_LEFT |left|
+/// |1  |TAB_CENTER |center|
+/// |2  |TAB_RIGHT |right|
+/// |3  |TAB_RELATION |relational (aligned on the relation operator)|
+/// |4  |TAB_DECIMAL |decimal (aligned on the decimal point)|
+pub mod tab_stops {
+    pub const TAB_LEFT: u8 = 0;
+    pub const TAB_CENTER: u8 = 1;
+    pub const TAB_RIGHT: u8 = 2;
+    pub const TAB_RELATION: u8 = 3;
+    pub const TAB_DECIMAL: u8 = 4;
+}
+
+/// Pile alignments:
+///
+/// A PILE record gives the horizontal alignment of its lines against each other,
+/// and the vertical alignment of the pile on the baseline of the line holding it.
+///
+/// |value  |symbol |description|
+/// |-----  |-----  |------ |
+/// |1  |PHA_LEFT |lines aligned left|
+/// |2  |PHA_CENTER |lines centered|
+/// |3  |PHA_RIGHT |lines aligned right|
+/// |4  |PHA_RELOP |lines aligned on their relational operator|
+/// |5  |PHA_DECIMAL |lines aligned on their decimal point|
+/// |0  |PVA_TOP |baseline of the first line on the baseline|
+/// |1  |PVA_CENTER |baseline of the middle line on the baseline|
+/// |2  |PVA_BOTTOM |baseline of the last line on the baseline|
+/// |3  |PVA_CENTERING |pile centered on the baseline|
+/// |4  |PVA_MATH |pile centered on the math axis|
+pub mod pile_align {
+    pub const PHA_LEFT: u8 = 1;
+    pub const PHA_CENTER: u8 = 2;
+    pub const PHA_RIGHT: u8 = 3;
+    pub const PHA_RELOP: u8 = 4;
+    pub const PHA_DECIMAL: u8 = 5;
+    pub const PVA_TOP: u8 = 0;
+    pub const PVA_CENTER: u8 = 1;
+    pub const PVA_BOTTOM: u8 = 2;
+    pub const PVA_CENTERING: u8 = 3;
+    pub const PVA_MATH: u8 = 4;
+}
+
+/// Template selectors:
+///
+/// The selector byte of a TMPL record says which template it is,
+/// the variation refines it (e.g. which fences of a bracket pair are drawn).
+///
+/// |value  |symbol |description|
+/// |-----  |-----  |------ |
+/// |0  |tmANGLE |angle brackets|
+/// |1  |tmPAREN |parentheses|
+/// |2  |tmBRACE |braces (curly brackets)|
+/// |3  |tmBRACK |square brackets|
+/// |4  |tmBAR |vertical bars|
+/// |5  |tmDBAR |double vertical bars|
+/// |6  |tmFLOOR |floor brackets|
+/// |7  |tmCEILING |ceiling brackets|
+/// |8  |tmOBRACK |open (white) brackets|
+/// |9  |tmINTERVAL |interval|
+/// |10 |tmROOT |radical|
+/// |11 |tmFRACT |fraction|
+/// |12 |tmUBAR |underbar|
+/// |13 |tmOBAR |overbar|
+/// |14 |tmARROW |arrow|
+/// |15 |tmINTEG |integral|
+/// |16 |tmSUM |summation|
+/// |17 |tmPROD |product|
+/// |18 |tmCOPROD |coproduct|
+/// |19 |tmUNION |union|
+/// |20 |tmINTER |intersection|
+/// |21 |tmINTOP |integral-style big operator|
+/// |22 |tmSUMOP |summation-style big operator|
+/// |23 |tmLIM |limit|
+/// |24 |tmHBRACE |horizontal brace|
+/// |25 |tmHBRACK |horizontal bracket|
+/// |26 |tmLDIV |long division|
+/// |27 |tmSUB |subscript|
+/// |28 |tmSUP |superscript|
+/// |29 |tmSUBSUP |subscript and superscript|
+/// |30 |tmDIRAC |Dirac bra-ket notation|
+/// |31 |tmVEC |vector|
+/// |32 |tmTILDE |tilde|
+/// |33 |tmHAT |hat|
+/// |34 |tmARC |arc|
+/// |35 |tmJSTATUS |joint status|
+/// |36 |tmSTRIKE |overstrike|
+/// |37 |tmBOX |box|
+pub mod selectors {
+    pub const TM_ANGLE: u8 = 0;
+    pub const TM_PAREN: u8 = 1;
+    pub const TM_BRACE: u8 = 2;
+    pub const TM_BRACK: u8 = 3;
+    pub const TM_BAR: u8 = 4;
+    pub const TM_DBAR: u8 = 5;
+    pub const TM_FLOOR: u8 = 6;
+    pub const TM_CEILING: u8 = 7;
+    pub const TM_OBRACK: u8 = 8;
+    pub const TM_INTERVAL: u8 = 9;
+    pub const TM_ROOT: u8 = 10;
+    pub const TM_FRACT: u8 = 11;
+    pub const TM_UBAR: u8 = 12;
+    pub const TM_OBAR: u8 = 13;
+    pub const TM_ARROW: u8 = 14;
+    pub const TM_INTEG: u8 = 15;
+    pub const TM_SUM: u8 = 16;
+    pub const TM_PROD: u8 = 17;
+    pub const TM_COPROD: u8 = 18;
+    pub const TM_UNION: u8 = 19;
+    pub const TM_INTER: u8 = 20;
+    pub const TM_INTOP: u8 = 21;
+    pub const TM_SUMOP: u8 = 22;
+    pub const TM_LIM: u8 = 23;
+    pub const TM_HBRACE: u8 = 24;
+    pub const TM_HBRACK: u8 = 25;
+    pub const TM_LDIV: u8 = 26;
+    pub const TM_SUB: u8 = 27;
+    pub const TM_SUP: u8 = 28;
+    pub const TM_SUBSUP: u8 = 29;
+    pub const TM_DIRAC: u8 = 30;
+    pub const TM_VEC: u8 = 31;
+    pub const TM_TILDE: u8 = 32;
+    pub const TM_HAT: u8 = 33;
+    pub const TM_ARC: u8 = 34;
+    pub const TM_JSTATUS: u8 = 35;
+    pub const TM_STRIKE: u8 = 36;
+    pub const TM_BOX: u8 = 37;
+
+    /// Symbol of a selector as in the MTEF specification, e.g. "tmFRACT".
+    pub fn selector_name(selector: u8) -> Option<&'static str> {
+        const NAMES: [&str; 38] = [
+            "tmANGLE", "tmPAREN", "tmBRACE", "tmBRACK", "tmBAR", "tmDBAR", "tmFLOOR", "tmCEILING",
+            "tmOBRACK", "tmINTERVAL", "tmROOT", "tmFRACT", "tmUBAR", "tmOBAR", "tmARROW", "tmINTEG",
+            "tmSUM", "tmPROD", "tmCOPROD", "tmUNION", "tmINTER", "tmINTOP", "tmSUMOP", "tmLIM",
+            "tmHBRACE", "tmHBRACK", "tmLDIV", "tmSUB", "tmSUP", "tmSUBSUP", "tmDIRAC", "tmVEC",
+            "tmTILDE", "tmHAT", "tmARC", "tmJSTATUS", "tmSTRIKE", "tmBOX",
+        ];
+        NAMES.get(selector as usize).cloned()
+    }
+
+    /// Number of slots (LINE records) MathType writes for a selector, null lines
+    /// included: a radicand and its index, a fraction's numerator and denominator...
+    pub fn slot_count(selector: u8) -> Option<usize> {
+        const SLOTS: [usize; 38] = [
+            1, 1, 1, 1, 1, 1, 1, 1,
+            1, 1, 2, 2, 1, 1, 2, 3,
+            3, 3, 3, 3, 3, 3, 3, 3,
+            2, 2, 2, 2, 2, 2, 2, 1,
+            1, 1, 1, 1, 1, 1,
+        ];
+        SLOTS.get(selector as usize).cloned()
+    }
+}
+
+/// Template variations:
+///
+/// Variation bits are selector-dependent.
+///
+/// |selector   |value  |symbol |description|
+/// |-----  |-----  |-----  |------ |
+/// |fences (tmANGLE..tmOBRACK) |0x0001 |tvFENCE_L  |left fence is present|
+/// |fences (tmANGLE..tmOBRACK) |0x0002 |tvFENCE_R  |right fence is present|
+/// |tmROOT |0  |tvROOT_SQ  |square root, the index slot is unused|
+/// |tmROOT |1  |tvROOT_NTH |nth root, the second slot holds the index|
+/// |tmHBRACE, tmHBRACK |0x0001 |tvHB_TOP   |brace above the main slot, else below|
+/// |tmINTEG    |0x0001 |tvINT_1    |single integral sign|
+/// |tmINTEG    |0x0002 |tvINT_2    |double integral sign|
+/// |tmINTEG    |0x0003 |tvINT_3    |triple integral sign|
+/// |tmINTEG    |0x0004 |tvINT_LOOP |contour integral|
+/// |tmINTEG    |0x0008 |tvINT_CW_LOOP  |clockwise contour integral|
+/// |tmINTEG    |0x000C |tvINT_CCW_LOOP |counter-clockwise contour integral|
+/// |tmINTEG    |0x0100 |tvINT_EXPAND   |integral sign expands to the height of the main slot|
+/// |big operators (tmINTEG..tmSUMOP), tmLIM    |0x0010 |tvBO_LOWER |lower limit is present|
+/// |big operators (tmINTEG..tmSUMOP), tmLIM    |0x0020 |tvBO_UPPER |upper limit is present|
+/// |big operators (tmINTEG..tmSUMOP)   |0x0040 |tvBO_SUM   |summation-style limits, above and below the operator, else beside it|
+/// |tmARROW    |0x0000 |tvAR_SINGLE    |single arrow|
+/// |tmARROW    |0x0001 |tvAR_DOUBLE    |double arrow (pair of opposite arrows)|
+/// |tmARROW    |0x0002 |tvAR_HARPOON   |harpoons|
+/// |tmARROW    |0x0004 |tvAR_TOP   |top slot is present|
+/// |tmARROW    |0x0008 |tvAR_BOTTOM    |bottom slot is present|
+/// |tmARROW    |0x0010 |tvAR_LEFT  |single arrow points left|
+/// |tmARROW    |0x0020 |tvAR_RIGHT |single arrow points right|
+/// |tmARROW    |0x0010 |tvAR_LOS   |double or harpoon, large over small|
+/// |tmARROW    |0x0020 |tvAR_SOL   |double or harpoon, small over large|
+/// |tmSUB, tmSUP, tmSUBSUP |0x0001 |tvSU_PRECEDES  |scripts precede the base (left scripts)|
+/// |tmSTRIKE   |0x0001 |tvST_HORIZ |horizontal strike|
+/// |tmSTRIKE   |0x0002 |tvST_UP    |diagonal strike, bottom-left to top-right|
+/// |tmSTRIKE   |0x0004 |tvST_DOWN  |diagonal strike, top-left to bottom-right|
+/// |tmBOX  |0x0001 |tvBX_ROUND |box has rounded corners|
+/// |tmBOX  |0x0002 |tvBX_LEFT  |left edge is drawn|
+/// |tmBOX  |0x0004 |tvBX_RIGHT |right edge is drawn|
+/// |tmBOX  |0x0008 |tvBX_TOP   |top edge is drawn|
+/// |tmBOX  |0x0010 |tvBX_BOTTOM    |bottom edge is drawn|
+pub mod variations {
+    /// left fence is present
+    pub const TV_FENCE_L: u16 = 0x0001;
+    /// right fence is present
+    pub const TV_FENCE_R: u16 = 0x0002;
+    /// square root
+    pub const TV_ROOT_SQ: u16 = 0x0000;
+    /// nth root
+    pub const TV_ROOT_NTH: u16 = 0x0001;
+    /// horizontal brace or bracket on top
+    pub const TV_HB_TOP: u16 = 0x0001;
+    /// single integral
+    pub const TV_INT_1: u16 = 0x0001;
+    /// double integral
+    pub const TV_INT_2: u16 = 0x0002;
+    /// triple integral
+    pub const TV_INT_3: u16 = 0x0003;
+    /// contour integral
+    pub const TV_INT_LOOP: u16 = 0x0004;
+    /// clockwise contour integral
+    pub const TV_INT_CW_LOOP: u16 = 0x0008;
+    /// counter-clockwise contour integral
+    pub const TV_INT_CCW_LOOP: u16 = 0x000C;
+    /// expanding integral sign
+    pub const TV_INT_EXPAND: u16 = 0x0100;
+    /// lower limit is present
+    pub const TV_BO_LOWER: u16 = 0x0010;
+    /// upper limit is present
+    pub const TV_BO_UPPER: u16 = 0x0020;
+    /// summation-style limits
+    pub const TV_BO_SUM: u16 = 0x0040;
+    /// single arrow
+    pub const TV_AR_SINGLE: u16 = 0x0000;
+    /// double arrow
+    pub const TV_AR_DOUBLE: u16 = 0x0001;
+    /// harpoons
+    pub const TV_AR_HARPOON: u16 = 0x0002;
+    /// top slot is present
+    pub const TV_AR_TOP: u16 = 0x0004;
+    /// bottom slot is present
+    pub const TV_AR_BOTTOM: u16 = 0x0008;
+    /// single arrow points left
+    pub const TV_AR_LEFT: u16 = 0x0010;
+    /// single arrow points right
+    pub const TV_AR_RIGHT: u16 = 0x0020;
+    /// double or harpoon, large over small
+    pub const TV_AR_LOS: u16 = 0x0010;
+    /// double or harpoon, small over large
+    pub const TV_AR_SOL: u16 = 0x0020;
+    /// scripts precede the base
+    pub const TV_SU_PRECEDES: u16 = 0x0001;
+    /// horizontal strike
+    pub const TV_ST_HORIZ: u16 = 0x0001;
+    /// upward diagonal strike
+    pub const TV_ST_UP: u16 = 0x0002;
+    /// downward diagonal strike
+    pub const TV_ST_DOWN: u16 = 0x0004;
+    /// rounded corners
+    pub const TV_BX_ROUND: u16 = 0x0001;
+    /// left edge
+    pub const TV_BX_LEFT: u16 = 0x0002;
+    /// right edge
+    pub const TV_BX_RIGHT: u16 = 0x0004;
+    /// top edge
+    pub const TV_BX_TOP: u16 = 0x0008;
+    /// bottom edge
+    pub const TV_BX_BOTTOM: u16 = 0x0010;
+}
+
+/// Embellishment types:
+///
+/// |value  |symbol |description|
+/// |-----  |-----  |------ |
+/// |2  |emb1DOT |over dot|
+/// |3  |emb2DOT |double over dot|
+/// |4  |emb3DOT |triple over dot|
+/// |5  |emb1PRIME |single prime|
+/// |6  |emb2PRIME |double prime|
+/// |7  |embBPRIME |backwards prime (left of character)|
+/// |8  |embTILDE |tilde|
+/// |9  |embHAT |hat (circumflex)|
+/// |10 |embNOT |diagonal slash through character|
+/// |11 |embRARROW |over right arrow|
+/// |12 |embLARROW |over left arrow|
+/// |13 |embBARROW |over left-right arrow|
+/// |14 |embR1ARROW |over right harpoon|
+/// |15 |embL1ARROW |over left harpoon|
+/// |16 |embMBAR |mid-height horizontal bar|
+/// |17 |embOBAR |over-bar|
+/// |18 |emb3PRIME |triple prime|
+/// |19 |embFROWN |over-arc, concave downward|
+/// |20 |embSMILE |over-arc, concave upward|
+/// |21 |embX_BARS |double diagonal bars|
+/// |22 |embUP_BAR |bottom-left to top-right diagonal bar|
+/// |23 |embDOWN_BAR |top-left to bottom-right diagonal bar|
+/// |24 |emb4DOT |four over dots|
+/// |25 |embU_1DOT |under dot|
+/// |26 |embU_2DOT |double under dot|
+/// |27 |embU_3DOT |triple under dot|
+/// |28 |embU_4DOT |four under dots|
+/// |29 |embU_BAR |under bar|
+/// |30 |embU_TILDE |under tilde (~)|
+/// |31 |embU_FROWN |under arc (ends point down)|
+/// |32 |embU_SMILE |under arc (ends point up)|
+/// |33 |embU_RARROW |under right arrow|
+/// |34 |embU_LARROW |under left arrow|
+/// |35 |embU_BARROW |under left-right arrow|
+/// |36 |embU_R1ARROW |under right arrow (1-barb)|
+/// |37 |embU_L1ARROW |under left arrow (1-barb)|
+pub mod embellishments {
+    pub const EMB_1DOT: u8 = 2;
+    pub const EMB_2DOT: u8 = 3;
+    pub const EMB_3DOT: u8 = 4;
+    pub const EMB_1PRIME: u8 = 5;
+    pub const EMB_2PRIME: u8 = 6;
+    pub const EMB_BPRIME: u8 = 7;
+    pub const EMB_TILDE: u8 = 8;
+    pub const EMB_HAT: u8 = 9;
+    pub const EMB_NOT: u8 = 10;
+    pub const EMB_RARROW: u8 = 11;
+    pub const EMB_LARROW: u8 = 12;
+    pub const EMB_BARROW: u8 = 13;
+    pub const EMB_R1ARROW: u8 = 14;
+    pub const EMB_L1ARROW: u8 = 15;
+    pub const EMB_MBAR: u8 = 16;
+    pub const EMB_OBAR: u8 = 17;
+    pub const EMB_3PRIME: u8 = 18;
+    pub const EMB_FROWN: u8 = 19;
+    pub const EMB_SMILE: u8 = 20;
+    pub const EMB_X_BARS: u8 = 21;
+    pub const EMB_UP_BAR: u8 = 22;
+    pub const EMB_DOWN_BAR: u8 = 23;
+    pub const EMB_4DOT: u8 = 24;
+    pub const EMB_U_1DOT: u8 = 25;
+    pub const EMB_U_2DOT: u8 = 26;
+    pub const EMB_U_3DOT: u8 = 27;
+    pub const EMB_U_4DOT: u8 = 28;
+    pub const EMB_U_BAR: u8 = 29;
+    pub const EMB_U_TILDE: u8 = 30;
+    pub const EMB_U_FROWN: u8 = 31;
+    pub const EMB_U_SMILE: u8 = 32;
+    pub const EMB_U_RARROW: u8 = 33;
+    pub const EMB_U_LARROW: u8 = 34;
+    pub const EMB_U_BARROW: u8 = 35;
+    pub const EMB_U_R1ARROW: u8 = 36;
+    pub const EMB_U_L1ARROW: u8 = 37;
+
+    /// Symbol of an embellishment as in the MTEF specification, e.g. "emb1DOT".
+    pub fn embellishment_name(embell: u8) -> Option<&'static str> {
+        const NAMES: [&str; 36] = [
+            "emb1DOT", "emb2DOT", "emb3DOT", "emb1PRIME", "emb2PRIME", "embBPRIME", "embTILDE", "embHAT",
+            "embNOT", "embRARROW", "embLARROW", "embBARROW", "embR1ARROW", "embL1ARROW", "embMBAR", "embOBAR",
+            "emb3PRIME", "embFROWN", "embSMILE", "embX_BARS", "embUP_BAR", "embDOWN_BAR", "emb4DOT", "embU_1DOT",
+            "embU_2DOT", "embU_3DOT", "embU_4DOT", "embU_BAR", "embU_TILDE", "embU_FROWN", "embU_SMILE", "embU_RARROW",
+            "embU_LARROW", "embU_BARROW", "embU_R1ARROW", "embU_L1ARROW",
+        ];
+        NAMES.get((embell as usize).checked_sub(EMB_1DOT as usize)?).cloned()
+    }
+}
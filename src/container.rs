@@ -0,0 +1,15 @@
+//! Files and objects equations are stored in: OLE objects, .doc files, OpenDocument
+//! packages, HTML exports and the clipboard.
+
+pub use super::compobj::{embedded_objects, is_equation_object, Clsid, CompObj, ObjectKind};
+pub use super::eqn::{EqnOleFileHdr, EqnOleFileHdrBuilder};
+#[cfg(feature = "embed")]
+pub use super::embed::{equation_objects, replace_equation_native};
+#[cfg(feature = "odt")]
+pub use super::odt::equations as odt_equations;
+pub use super::html::equations as html_equations;
+pub use super::extract::raw_mtef;
+pub use super::detect::{detect, InputFormat};
+pub use super::presentation::{picture_size, PictureFormat, PictureSize};
+#[cfg(all(feature = "clipboard", windows))]
+pub use super::clipboard::put_equation_on_clipboard;
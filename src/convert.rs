@@ -0,0 +1,43 @@
+//! One-call conversions with the default options, for scripts that only need the
+//! translation of an equation object.
+//!
+//! ```ignore
+//! let latex = mtef::ole_to_latex("word/embeddings/oleObject1.bin")?;
+//! ```
+//!
+//! Everything else (several equations per file, options, line by line output)
+//! goes through `MTEquation`.
+
+use std::path::Path;
+
+use super::eqn::MTEquation;
+#[cfg(feature = "latex")]
+use super::eqn::CFB_MAGIC;
+use super::error::Error;
+
+/// LaTeX of the equation in the OLE object file at `path`.
+#[cfg(feature = "latex")]
+pub fn ole_to_latex<P: AsRef<Path>>(path: P) -> Result<String, Error> {
+    MTEquation::from_ole(path)?.translate()
+}
+
+/// Presentation MathML of the equation in the OLE object file at `path`.
+#[cfg(feature = "mathml")]
+pub fn ole_to_mathml<P: AsRef<Path>>(path: P) -> Result<String, Error> {
+    MTEquation::from_ole(path)?.to_mathml()
+}
+
+/// LaTeX of the equation in `bytes`: an OLE object, or the content of its
+/// "Equation Native" stream.
+#[cfg(feature = "latex")]
+pub fn bytes_to_latex(bytes: &[u8]) -> Result<String, Error> {
+    from_bytes(bytes)?.translate()
+}
+
+#[cfg(feature = "latex")]
+fn from_bytes(bytes: &[u8]) -> Result<MTEquation, Error> {
+    match bytes.starts_with(&CFB_MAGIC) {
+        true => MTEquation::from_ole_reader(bytes),
+        false => MTEquation::from_equation_native(bytes),
+    }
+}
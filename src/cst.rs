@@ -0,0 +1,111 @@
+//! Lossless equation model for editing tools.
+
+use std::io::Cursor;
+
+use super::eqn::{decode_header, decode_record, DecodeError, MTEquation, MTHeader, MTRecords};
+use super::error::Error;
+use super::writer::write_record;
+
+/// A record and the bytes it was read from.
+#[derive(Debug, Clone)]
+pub struct CstRecord {
+    /// Original bytes, `None` once the record has been edited or inserted.
+    bytes: Option<Vec<u8>>,
+    /// Decoded record, `None` for bytes the parser skips (payloads of record types it
+    /// does not decode yet), which are kept as is.
+    record: Option<MTRecords>,
+}
+
+impl CstRecord {
+    pub fn record(&self) -> Option<&MTRecords> {
+        self.record.as_ref()
+    }
+
+    /// Whether the record will be written from its original bytes.
+    pub fn is_pristine(&self) -> bool {
+        self.bytes.is_some()
+    }
+}
+
+/// Concrete syntax tree of an equation, a lossless model for editing tools.
+///
+/// `MTEquation` keeps what translation needs and `to_bytes` rewrites it in a
+/// canonical form. `Cst` keeps the bytes every record was read from instead, so
+/// options, nudges, padding and records the parser does not understand survive;
+/// serializing an untouched `Cst` gives back the input byte for byte, and only the
+/// records changed through the edit API are re-encoded.
+#[derive(Debug, Clone)]
+pub struct Cst {
+    header_bytes: Vec<u8>,
+    header: MTHeader,
+    records: Vec<CstRecord>,
+}
+
+impl Cst {
+    pub fn parse(buf: &[u8]) -> Result<Cst, Error> {
+        let mut cur = Cursor::new(buf);
+        let header = decode_header(&mut cur).map_err(invalid)?;
+        let header_bytes = buf[..cur.position() as usize].to_vec();
+        let mut records = vec![];
+        while (cur.position() as usize) < buf.len() {
+            let start = cur.position() as usize;
+            let record = decode_record(&mut cur).map_err(invalid)?;
+            records.push(CstRecord {
+                bytes: Some(buf[start..cur.position() as usize].to_vec()),
+                record,
+            });
+        }
+        Ok(Cst { header_bytes, header, records })
+    }
+
+    pub fn records(&self) -> &[CstRecord] {
+        &self.records
+    }
+
+    /// Changes the record at `index`, which is re-encoded when serializing.
+    /// Returns false when there is no decoded record there.
+    pub fn edit<F: FnOnce(&mut MTRecords)>(&mut self, index: usize, f: F) -> bool {
+        match self.records.get_mut(index) {
+            Some(CstRecord { bytes, record: Some(record) }) => {
+                f(record);
+                *bytes = None;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn insert(&mut self, index: usize, record: MTRecords) {
+        self.records.insert(index, CstRecord { bytes: None, record: Some(record) });
+    }
+
+    pub fn remove(&mut self, index: usize) -> CstRecord {
+        self.records.remove(index)
+    }
+
+    /// Pristine records as read, edited ones through the MTEF writer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = self.header_bytes.clone();
+        for r in &self.records {
+            match (&r.bytes, &r.record) {
+                (Some(bytes), _) => buf.extend_from_slice(bytes),
+                (None, Some(record)) => write_record(&mut buf, record),
+                (None, None) => {}
+            }
+        }
+        buf
+    }
+
+    /// Semantic view, for the AST and translation.
+    pub fn to_equation(&self) -> MTEquation {
+        let records = self.records.iter().filter_map(|r| r.record.clone()).collect();
+        MTEquation::from_parts(self.header.clone(), records)
+    }
+}
+
+fn invalid(e: DecodeError) -> Error {
+    match e {
+        DecodeError::Incomplete => Error::MalformedMTEF("truncated MTEF"),
+        DecodeError::Invalid(e) => e,
+    }
+}
@@ -0,0 +1,176 @@
+//! Telling how an equation was packaged from its bytes.
+
+use std::io::Cursor;
+use std::str::FromStr;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use super::eqn::{is_zlib, MTEquation, CFB_MAGIC, EQN_OLE_FILE_HDR_LEN};
+use super::error::Error;
+use super::extract::{hex, ole1_native_data, rtf, RTF_MAGIC};
+
+const WMF_PLACEABLE: [u8; 4] = [0xD7, 0xCD, 0xC6, 0x9A];
+const WMF_STANDARD: [u8; 4] = [0x01, 0x00, 0x09, 0x00];
+/// size of the placeable header, before the standard one
+const WMF_PLACEABLE_LEN: usize = 22;
+const WMF_HEADER_LEN: usize = 18;
+const META_ESCAPE: u16 = 0x0626;
+const MFCOMMENT: u16 = 15;
+const MTEF_COMMENT: &[u8] = b"AppsMFCC";
+const DSI_SIGNATURE: &[u8] = b"Design Science, Inc.\0";
+const OLE1_VERSION: [u8; 4] = [0x01, 0x05, 0x00, 0x00];
+const HTML_KEY: &str = "MathType@MTEF@";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputFormat {
+    CompoundFile,
+    Wmf,
+    Rtf,
+    Ole1,
+    EquationNative,
+    Mtef,
+    Html,
+}
+
+/// Format of `bytes`, for callers handed legacy content of unknown origin; `None`
+/// when it is none of these:
+///
+/// | format | recognized by |
+/// | ------ | ------------- |
+/// |OLE compound file|its magic number|
+/// |Windows metafile|placeable or standard metafile header, MTEF in a GDI comment|
+/// |RTF|`{\rtf`, MTEF in the objects of `\objdata` groups|
+/// |OLE1 object|OLE version 0x0501, as in `\x01Ole10Native` streams and RTF|
+/// |"Equation Native" stream|the 28 bytes EQNOLEFILEHDR|
+/// |MTEF|a zlib header, or a version from 1 to 5 followed by platform and product bytes|
+/// |HTML|a `MathType@MTEF@` comment|
+///
+/// Metafiles written by MathType keep the MTEF in comment records starting with
+/// "AppsMFCC", a version, the total and chunk lengths, then "Design Science, Inc."
+/// before the data; long equations span several comments.
+pub fn detect(bytes: &[u8]) -> Option<InputFormat> {
+    if bytes.starts_with(&CFB_MAGIC) {
+        Some(InputFormat::CompoundFile)
+    } else if bytes.starts_with(&WMF_PLACEABLE) || bytes.starts_with(&WMF_STANDARD) {
+        Some(InputFormat::Wmf)
+    } else if bytes.starts_with(RTF_MAGIC) {
+        Some(InputFormat::Rtf)
+    } else if bytes.starts_with(&OLE1_VERSION) {
+        Some(InputFormat::Ole1)
+    } else if bytes.len() >= EQN_OLE_FILE_HDR_LEN && bytes[..6] == [28, 0, 0, 0, 2, 0] {
+        Some(InputFormat::EquationNative)
+    } else if is_zlib(bytes) || is_mtef(bytes) {
+        Some(InputFormat::Mtef)
+    } else if String::from_utf8_lossy(bytes).contains(HTML_KEY) {
+        Some(InputFormat::Html)
+    } else {
+        None
+    }
+}
+
+fn is_mtef(bytes: &[u8]) -> bool {
+    matches!(bytes, [1..=5, 0..=1, 0..=1, ..])
+}
+
+impl MTEquation {
+    /// The first equation in `bytes`, however it was packaged, see `detect`.
+    pub fn from_any(bytes: &[u8]) -> Result<MTEquation, Error> {
+        let unrecognized = Error::MalformedMTEF("no equation in a recognized format");
+        match detect(bytes).ok_or(unrecognized)? {
+            InputFormat::CompoundFile => MTEquation::from_ole_reader(bytes),
+            InputFormat::Wmf => MTEquation::from_bytes(&wmf_mtef(bytes).ok_or(Error::MalformedMTEF("no MTEF in metafile"))?),
+            InputFormat::Rtf => {
+                let payload = rtf(&String::from_utf8_lossy(bytes))?.into_iter().next();
+                MTEquation::from_bytes(&payload.ok_or(Error::MalformedMTEF("no equation object in RTF"))?)
+            }
+            InputFormat::Ole1 => {
+                let native = ole1_native_data(bytes).ok_or(Error::BadSizeValue("OLE1 object"))?;
+                match native.starts_with(&CFB_MAGIC) {
+                    true => MTEquation::from_ole_reader(native),
+                    false => MTEquation::from_equation_native(native),
+                }
+            }
+            InputFormat::EquationNative => MTEquation::from_equation_native(bytes),
+            InputFormat::Mtef => MTEquation::from_bytes(bytes),
+            InputFormat::Html => {
+                let equations = super::html::equations(&String::from_utf8_lossy(bytes))?;
+                equations.into_iter().next().map(|(_, eqn)| eqn).ok_or(Error::MalformedMTEF("no equation in HTML"))
+            }
+        }
+    }
+
+    /// The equation in hex digits, as the `\objdata` of RTF holds it: an OLE1 object,
+    /// or any other packaging `from_any` reads. Whitespace and line breaks between the
+    /// digits are ignored.
+    pub fn from_hex(text: &str) -> Result<MTEquation, Error> {
+        MTEquation::from_any(&hex(text).ok_or(Error::MalformedMTEF("invalid hex"))?)
+    }
+
+    /// The equation in base64, as HTML and XML exports put it in attributes: MTEF,
+    /// compressed or not, an OLE compound file or any other packaging `from_any`
+    /// reads. Whitespace between the characters is ignored, and padding optional.
+    #[cfg(feature = "base64")]
+    pub fn from_base64(text: &str) -> Result<MTEquation, Error> {
+        use base64::alphabet::STANDARD;
+        use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+        use base64::Engine;
+        let config = GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+        let text: String = text.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+        let bytes = GeneralPurpose::new(&STANDARD, config).decode(text)
+            .map_err(|_| Error::MalformedMTEF("invalid base64"))?;
+        MTEquation::from_any(&bytes)
+    }
+}
+
+/// `MTEquation::from_hex`.
+impl FromStr for MTEquation {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<MTEquation, Error> {
+        MTEquation::from_hex(text)
+    }
+}
+
+/// MTEF of the MathType comments of a metafile, chunks joined in record order.
+fn wmf_mtef(wmf: &[u8]) -> Option<Vec<u8>> {
+    let start = match wmf.starts_with(&WMF_PLACEABLE) {
+        true => WMF_PLACEABLE_LEN,
+        false => 0,
+    };
+    let mut cur = Cursor::new(wmf);
+    cur.set_position((start + WMF_HEADER_LEN) as u64);
+    let mut mtef = vec![];
+    let mut total = None;
+    // records: size in 16-bit words, function, parameters
+    while let (Ok(size), Ok(function)) = (cur.read_u32::<LittleEndian>(), cur.read_u16::<LittleEndian>()) {
+        let record_start = cur.position() as usize - 6;
+        let record_end = record_start.checked_add(size as usize * 2)?;
+        if size < 3 || record_end > wmf.len() {
+            break;
+        }
+        if function == META_ESCAPE && cur.read_u16::<LittleEndian>().ok()? == MFCOMMENT {
+            let len = cur.read_u16::<LittleEndian>().ok()? as usize;
+            let data_start = cur.position() as usize;
+            let data = wmf.get(data_start..(data_start + len).min(record_end))?;
+            if let Some((chunk_total, chunk)) = mtef_chunk(data) {
+                total.get_or_insert(chunk_total);
+                mtef.extend_from_slice(chunk);
+            }
+        }
+        cur.set_position(record_end as u64);
+    }
+    // the last chunk may be padded
+    mtef.truncate(total?);
+    Some(mtef)
+}
+
+/// Total MTEF length and the chunk of a MathType comment.
+fn mtef_chunk(comment: &[u8]) -> Option<(usize, &[u8])> {
+    let mut cur = Cursor::new(comment.strip_prefix(MTEF_COMMENT)?);
+    let _version = cur.read_u16::<LittleEndian>().ok()?;
+    let total = cur.read_u32::<LittleEndian>().ok()? as usize;
+    let len = cur.read_u32::<LittleEndian>().ok()? as usize;
+    let data = &cur.get_ref()[cur.position() as usize..];
+    let data = data.get(..len.min(data.len()))?;
+    Some((total, data.strip_prefix(DSI_SIGNATURE).unwrap_or(data)))
+}
@@ -0,0 +1,53 @@
+//! Writing equations back into compound files, enabled by the `embed` feature.
+//!
+//! Word keeps the OLE objects of a .doc in its `ObjectPool` storage, one sub-storage
+//! per object (`/ObjectPool/_1234567890`). Replacing the "Equation Native" stream of
+//! such a storage updates the equation in place, the object keeps its identity,
+//! CompObj, ObjInfo and the field code referring to it.
+
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::eqn::{EqnOleFileHdr, MTEquation, EQN_OLE_FILE_HDR_LEN};
+use super::error::Error;
+
+const EQUATION_NATIVE: &str = "Equation Native";
+
+/// Storage paths of the objects holding an "Equation Native" stream, in directory order.
+pub fn equation_objects<P: AsRef<Path>>(path: P) -> Result<Vec<String>, Error> {
+    let comp = cfb::open(path)?;
+    let objects = comp.walk()
+        .filter(|entry| entry.is_stream() && entry.name() == EQUATION_NATIVE)
+        .filter_map(|entry| entry.path().parent().map(|p| p.to_string_lossy().into_owned()))
+        .collect();
+    Ok(objects)
+}
+
+/// Replaces the equation of the object storage `object` (from `equation_objects`,
+/// "/" for a standalone object file) with `eqn`. The MTEF is checked with
+/// `validate_strict` first, the stream header keeps its clipboard format.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(path, eqn),
+                                                    fields(path = %path.as_ref().display())))]
+pub fn replace_equation_native<P: AsRef<Path>>(path: P, object: &str, eqn: &MTEquation) -> Result<(), Error> {
+    let mtef = eqn.to_bytes_strict()?;
+    let mut comp = cfb::open_rw(path)?;
+    let stream_path = Path::new(object).join(EQUATION_NATIVE);
+    if !comp.is_stream(&stream_path) {
+        return Err(Error::EmptyEntry);
+    }
+
+    let mut stream = comp.open_stream(&stream_path)?;
+    let mut hdr = [0u8; EQN_OLE_FILE_HDR_LEN];
+    stream.read_exact(&mut hdr).map_err(|_| Error::BadSizeValue("EQNOLEFILEHDR"))?;
+    let cf = EqnOleFileHdr::parse_ole_hdr(&hdr)?.cf();
+    let hdr = EqnOleFileHdr::builder().cf(cf).build(&mtef)?;
+
+    stream.seek(SeekFrom::Start(0))?;
+    stream.write_all(&hdr.to_bytes())?;
+    stream.write_all(&mtef)?;
+    stream.set_len((EQN_OLE_FILE_HDR_LEN + mtef.len()) as u64)?;
+    stream.flush()?;
+    drop(stream);
+    comp.flush()?;
+    Ok(())
+}
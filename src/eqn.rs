@@ -1,82 +1,823 @@
-use std::io::{Cursor, Read};
+use alloc::string::String;
+#[cfg(feature = "std")]
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::io::{Seek, SeekFrom};
+#[cfg(feature = "std")]
+use std::path::Path;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
 
-use byteorder::{LittleEndian, ReadBytesExt};
-use std::io::BufRead;
+use byteorder::LittleEndian;
+#[cfg(feature = "std")]
 use encoding::{Encoding, DecoderTrap};
-use encoding::all::{GBK, UTF_8};
-use std::borrow::Cow;
+#[cfg(feature = "std")]
+use encoding::all::GBK;
+use super::io::{BufRead, Cursor, Read, ReadBytesExt};
+#[cfg(feature = "std")]
+use super::stream::StreamParser;
+use super::constants::options::MTEF_OPT_NUDGE;
+use super::constants::record_types::RULER;
+use super::constants::typeface::*;
+use super::constants::typesize::SZ_DELTA;
+use super::constants::RecordType;
+use super::constants::selectors::{TM_ANGLE, TM_INTEG, TM_INTERVAL, TM_SUMOP};
 
 
-#[derive(Debug)]
+/// Limits for parsing untrusted input, e.g. uploads, whether linked objects are
+/// followed and how incomplete templates are read. No size nor time limit by default,
+/// a nesting depth of `DEFAULT_MAX_DEPTH`; exceeding one fails with `Error::LimitExceeded`.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Largest input accepted, in bytes: the OLE file, and the MTEF once inflated.
+    pub max_bytes: Option<usize>,
+    /// Wall-clock time extraction and parsing may take, checked between records.
+    pub time_budget: Option<Duration>,
+    /// Most lines, templates, piles and embellishment lists open at once. The tree
+    /// and the backends recurse into each, and a stack overflow can not be caught:
+    /// raise this only for trusted input, or on a thread with a larger stack.
+    pub max_depth: Option<usize>,
+    /// Read the equation of a linked object from its source file, when readable,
+    /// instead of failing with `Error::LinkedObjectNotEmbedded`. The path comes from
+    /// the input, leave this off for untrusted files.
+    pub resolve_links: bool,
+    /// Give templates that lost slots, e.g. when an author deleted their content,
+    /// placeholders up to the count of their selector (`constants::selectors::slot_count`),
+    /// so that backends write every argument of the command they translate to: an
+    /// empty box, or `TranslateOptions::placeholder` in LaTeX.
+    pub fill_missing_slots: bool,
+    /// How records the parser does not understand, and damaged MTEF, are handled.
+    pub mode: ParseMode,
+}
+
+#[cfg(feature = "std")]
+impl ParseOptions {
+    /// Default `max_depth`, some ten times what equations typed in MathType reach.
+    pub const DEFAULT_MAX_DEPTH: usize = 256;
+}
+
+#[cfg(feature = "std")]
+impl Default for ParseOptions {
+    fn default() -> ParseOptions {
+        ParseOptions {
+            max_bytes: None,
+            time_budget: None,
+            max_depth: Some(ParseOptions::DEFAULT_MAX_DEPTH),
+            resolve_links: false,
+            fill_missing_slots: false,
+            mode: ParseMode::default(),
+        }
+    }
+}
+
+/// How MTEF 5 is parsed, see `ParseOptions::mode`.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// Records of unknown types are kept as `MTRecords::FUTURE`, damaged MTEF fails.
+    /// Matrices are left out, each reported as an `output::Warning::SkippedMatrix`.
+    #[default]
+    Standard,
+    /// Records of unknown types, END records not matching what they close and matrices
+    /// (not decoded yet) fail too.
+    Strict,
+    /// Whatever decodes before damage or an unknown record type below 100 (whose
+    /// length can not be known) is kept, scopes left open are closed; what was
+    /// skipped is listed by `MTEquation::warnings`.
+    Lenient,
+}
+
+/// Limits of a `ParseOptions`, the time budget running from the start of extraction.
+#[cfg(feature = "std")]
+struct Budget {
+    max_bytes: Option<usize>,
+    deadline: Option<Instant>,
+    max_depth: Option<usize>,
+    mode: ParseMode,
+}
+
+#[cfg(feature = "std")]
+impl Budget {
+    fn new(options: &ParseOptions) -> Budget {
+        Budget {
+            max_bytes: options.max_bytes,
+            deadline: options.time_budget.map(|budget| Instant::now() + budget),
+            max_depth: options.max_depth,
+            mode: options.mode,
+        }
+    }
+
+    /// Bytes worth reading to tell whether input is over the size limit.
+    fn read_limit(&self) -> u64 {
+        self.max_bytes.map_or(u64::MAX, |max| max as u64 + 1)
+    }
+
+    fn check_size(&self, len: usize) -> Result<(), super::error::Error> {
+        match self.max_bytes {
+            Some(max) if len > max => Err(super::error::Error::LimitExceeded("input over max_bytes")),
+            _ => Ok(()),
+        }
+    }
+
+    /// `depth` counting the scope of the equation itself.
+    fn check_depth(&self, depth: usize) -> Result<(), super::error::Error> {
+        match self.max_depth {
+            Some(max) if depth > max + 1 => Err(super::error::Error::LimitExceeded("nesting over max_depth")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Deepest nesting of `records`, for equations not parsed record by record.
+    fn check_records_depth(&self, records: &[MTRecords]) -> Result<(), super::error::Error> {
+        let mut open = 1usize;
+        for record in records {
+            match record {
+                MTRecords::END => open = open.saturating_sub(1),
+                record if opens_scope(record) => {
+                    open += 1;
+                    self.check_depth(open)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn check_time(&self) -> Result<(), super::error::Error> {
+        match self.deadline {
+            Some(deadline) if Instant::now() > deadline => Err(super::error::Error::LimitExceeded("parsing over time_budget")),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A parsed equation: its header and records.
+///
+/// Equations are equal when their headers and records are, record by record and
+/// field by field; two equations MathType saved with different nudges, sizes or
+/// font numbering are not. How the equation was read (its source picture size,
+/// the parse options and the warnings of a lenient parse) is left out.
+/// `content_hash` compares what equations show instead.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MTEquation {
-    m_mtef_ver: u8,
-    m_platform: u8,
-    m_product: u8,
-    m_version: u8,
-    m_version_sub: u8,
-    m_application: String,
-    m_inline: u8,
+    pub(crate) m_mtef_ver: u8,
+    pub(crate) m_platform: u8,
+    pub(crate) m_product: u8,
+    pub(crate) m_version: u8,
+    pub(crate) m_version_sub: u8,
+    pub(crate) m_application: String,
+    pub(crate) m_inline: u8,
 
     encoding_defs: Vec<MTRecords>,
-    records: Vec<MTRecords>,
+    pub(crate) records: Vec<MTRecords>,
+    /// bytes of the MTEF each record was read from, empty when not known
+    pub(crate) spans: Vec<Range<usize>>,
+    /// size of the cached picture of the OLE object the equation was read from
+    pub(crate) presentation: Option<super::presentation::PictureSize>,
+    /// `ParseOptions::fill_missing_slots` the equation was read with
+    pub(crate) fill_missing_slots: bool,
+    /// what a `ParseMode::Lenient` parse skipped
+    pub(crate) warnings: Vec<super::repair::Repair>,
+    /// offsets of the MATRIX records left out, not being decoded yet
+    pub(crate) skipped_matrices: Vec<usize>,
 }
 
-#[derive(Debug)]
-enum MTRecords {
+#[cfg(feature = "std")]
+impl PartialEq for MTEquation {
+    fn eq(&self, other: &MTEquation) -> bool {
+        self.header_fields() == other.header_fields() && self.records == other.records
+    }
+}
+
+#[cfg(feature = "std")]
+impl Eq for MTEquation {}
+
+#[cfg(feature = "std")]
+impl std::hash::Hash for MTEquation {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.header_fields().hash(state);
+        self.records.hash(state);
+    }
+}
+
+#[cfg(feature = "std")]
+impl MTEquation {
+    /// Header fields compared by `PartialEq` and `Hash`.
+    fn header_fields(&self) -> (u8, u8, u8, u8, u8, &str, u8) {
+        (self.m_mtef_ver, self.m_platform, self.m_product, self.m_version, self.m_version_sub,
+         &self.m_application, self.m_inline)
+    }
+}
+
+/// Variants are named after the MTEF record types.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MTRecords {
     END,
     LINE(MTLine),
     CHAR(MTChar),
     TMPL(MTTmpl),
+    PILE(MTPile),
+    EMBELL(MTEmbell),
     ENCODING_DEF(String),
     FONT_DEF { enc_def_index: u8, name: String },
     FONT_STYLE_DEF { font_def_index: u8, char_style: u8 },
-    EQN_PREFS { sizes: Vec<String>, spaces: Vec<String>, styles: Vec<Option<u8>> },
+    EQN_PREFS { sizes: Vec<String>, spaces: Vec<String>, styles: Vec<Option<(u8, u8)>> },
+    SIZE(MTSize),
     FULL, SUB, SUB2, SYM, SUBSYM,
-    FUTURE,
+    /// index of the COLOR_DEF record giving the color of what follows
+    COLOR(u8),
+    COLOR_DEF(MTColorDef),
+    FUTURE(UnknownRecord),
 }
 
+impl MTRecords {
+    pub fn record_type(&self) -> RecordType {
+        match self {
+            MTRecords::END => RecordType::End,
+            MTRecords::LINE(_) => RecordType::Line,
+            MTRecords::CHAR(_) => RecordType::Char,
+            MTRecords::TMPL(_) => RecordType::Tmpl,
+            MTRecords::PILE(_) => RecordType::Pile,
+            MTRecords::EMBELL(_) => RecordType::Embell,
+            MTRecords::ENCODING_DEF(_) => RecordType::EncodingDef,
+            MTRecords::FONT_DEF { .. } => RecordType::FontDef,
+            MTRecords::FONT_STYLE_DEF { .. } => RecordType::FontStyleDef,
+            MTRecords::EQN_PREFS { .. } => RecordType::EqnPrefs,
+            MTRecords::SIZE(_) => RecordType::Size,
+            MTRecords::FULL => RecordType::Full,
+            MTRecords::SUB => RecordType::Sub,
+            MTRecords::SUB2 => RecordType::Sub2,
+            MTRecords::SYM => RecordType::Sym,
+            MTRecords::SUBSYM => RecordType::SubSym,
+            MTRecords::COLOR(_) => RecordType::Color,
+            MTRecords::COLOR_DEF(_) => RecordType::ColorDef,
+            MTRecords::FUTURE(_) => RecordType::Future,
+        }
+    }
+}
 
-#[derive(Debug)]
-struct MTLine {
-    nudge: (u16, u16),
-    line_spacing: u8,
-    null: bool,
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MTLine {
+    pub(crate) nudge: (i16, i16),
+    pub(crate) line_spacing: u8,
+    pub(crate) null: bool,
+    pub(crate) ruler: Option<MTRuler>,
 }
 
-#[derive(Debug)]
-struct MTTmpl {
-    nudge: (u16, u16),
-    selector: u8,
-    variation: u16,
-    options: u8
+impl MTLine {
+    /// Option flags the line is written with.
+    pub fn options(&self) -> LineOptions {
+        let mut options = LineOptions::default();
+        if self.null {
+            options |= LineOptions::NULL;
+        }
+        if self.ruler.is_some() {
+            options |= LineOptions::RULER;
+        }
+        if self.line_spacing != 0 {
+            options |= LineOptions::LSPACE;
+        }
+        if self.nudge != (0, 0) {
+            options |= LineOptions::NUDGE;
+        }
+        options
+    }
 }
 
-#[derive(Debug)]
-struct MTChar {
-    nudge: (u16, u16),
-    typeface: u8,
-    mtcode: Option<u16>,
-    fp8: Option<u8>,
-    fp16: Option<u16>,
+/// Vertical stack of lines, from a PILE record. The lines follow it up to its END.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MTPile {
+    pub(crate) nudge: (i16, i16),
+    /// alignment of the lines against each other, see `constants::pile_align`
+    pub(crate) halign: u8,
+    /// alignment of the pile on the baseline, see `constants::pile_align`
+    pub(crate) valign: u8,
+    pub(crate) ruler: Option<MTRuler>,
+}
+
+impl MTPile {
+    /// Option flags the pile is written with.
+    pub fn options(&self) -> PileOptions {
+        let mut options = PileOptions::default();
+        if self.ruler.is_some() {
+            options |= PileOptions::RULER;
+        }
+        if self.nudge != (0, 0) {
+            options |= PileOptions::NUDGE;
+        }
+        options
+    }
+}
+
+/// Tab stops of a line, from the RULER record following it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MTRuler {
+    pub(crate) tab_stops: Vec<MTTabStop>,
+}
+
+/// Record of a type this version does not know, kept as read for analysis and
+/// written back unchanged.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnknownRecord {
+    pub tag: u8,
+    /// Payload after the 16-bit length of records from 100 on. Records below 100 have
+    /// no length to skip them by, their payload is read as the records following.
+    pub bytes: Vec<u8>,
+}
+
+/// Size of the following characters, from a SIZE record, until the next size record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MTSize {
+    /// explicit point size, in 32nds of a point
+    Points(u16),
+    /// `delta` 32nds of a point away from typesize `lsize` (see `constants::typesize`),
+    /// or from the current size when `lsize` is `SZ_DELTA`
+    Delta { lsize: u8, delta: i16 },
+}
+
+impl MTSize {
+    /// `delta` 32nds of a point away from the current size.
+    pub fn relative(delta: i16) -> MTSize {
+        MTSize::Delta { lsize: SZ_DELTA, delta }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MTTabStop {
+    /// alignment, see `constants::tab_stops`
+    pub(crate) kind: u8,
+    /// offset from the left end of the slot, in 32nds of a point
+    pub(crate) offset: i16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MTTmpl {
+    pub(crate) nudge: (i16, i16),
+    pub(crate) selector: u8,
+    pub(crate) variation: u16,
+    pub(crate) options: TemplateOptions,
+}
+
+impl MTTmpl {
+    pub fn options(&self) -> TemplateOptions {
+        self.options
+    }
 }
 
+/// Template-specific options byte of a TMPL record, read according to the family of
+/// its selector. Bits without a meaning in the family are kept in `other`, and
+/// written back as read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TemplateOptions {
+    /// Fences, tmANGLE to tmINTERVAL.
+    Fence {
+        /// fences centered on their content instead of the math axis
+        center_on_content: bool,
+        other: u8,
+    },
+    /// Integrals and big operators, tmINTEG to tmSUMOP. Where limits go is told by
+    /// the variation (`TV_BO_SUM`).
+    BigOp {
+        /// empty limit slots take no room
+        hide_empty_limits: bool,
+        other: u8,
+    },
+    Other(u8),
+}
+
+const TMPL_OPT_FIRST: u8 = 0x01;
+
+impl TemplateOptions {
+    pub fn decode(selector: u8, bits: u8) -> TemplateOptions {
+        let (first, other) = (TMPL_OPT_FIRST == bits & TMPL_OPT_FIRST, bits & !TMPL_OPT_FIRST);
+        match selector {
+            TM_ANGLE..=TM_INTERVAL => TemplateOptions::Fence { center_on_content: first, other },
+            TM_INTEG..=TM_SUMOP => TemplateOptions::BigOp { hide_empty_limits: first, other },
+            _ => TemplateOptions::Other(bits),
+        }
+    }
+
+    pub fn bits(self) -> u8 {
+        let (first, other) = match self {
+            TemplateOptions::Fence { center_on_content, other } => (center_on_content, other),
+            TemplateOptions::BigOp { hide_empty_limits, other } => (hide_empty_limits, other),
+            TemplateOptions::Other(bits) => return bits,
+        };
+        match first {
+            true => other | TMPL_OPT_FIRST,
+            false => other,
+        }
+    }
+}
+
+/// Option byte of a record as a set of flags. Bits without a name are kept, and
+/// written back as read.
+macro_rules! option_flags {
+    ($(#[$doc:meta])* $name:ident { $($(#[$flag_doc:meta])* $flag:ident = $bits:expr,)* }) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        pub struct $name(u8);
+
+        impl $name {
+            $($(#[$flag_doc])* pub const $flag: $name = $name($bits);)*
+
+            pub fn from_bits(bits: u8) -> $name {
+                $name(bits)
+            }
+
+            pub fn bits(self) -> u8 {
+                self.0
+            }
+
+            /// Whether all flags of `other` are set.
+            pub fn contains(self, other: $name) -> bool {
+                other.0 == self.0 & other.0
+            }
+
+            /// Clears the flags of `other`.
+            pub fn remove(&mut self, other: $name) {
+                self.0 &= !other.0;
+            }
+        }
+
+        impl core::ops::BitOr for $name {
+            type Output = $name;
+
+            fn bitor(self, other: $name) -> $name {
+                $name(self.0 | other.0)
+            }
+        }
+
+        impl core::ops::BitOrAssign for $name {
+            fn bitor_assign(&mut self, other: $name) {
+                self.0 |= other.0;
+            }
+        }
+    };
+}
+
+option_flags! {
+    /// Option flags of a CHAR record, see `constants::options`.
+    CharOptions {
+        /// character is followed by an embellishment list
+        EMBELL = 0x01,
+        /// character starts a function (sin, cos, etc.)
+        FUNC_START = 0x02,
+        /// character is written with an 8-bit encoded value
+        ENC_CHAR_8 = 0x04,
+        /// nudge values follow tag
+        NUDGE = 0x08,
+        /// character is written with an 16-bit encoded value
+        ENC_CHAR_16 = 0x10,
+        /// character is written without an 16-bit MTCode value
+        ENC_NO_MTCODE = 0x20,
+    }
+}
+
+option_flags! {
+    /// Option flags of a LINE record, see `constants::options`.
+    LineOptions {
+        /// line is a placeholder only (i.e. not displayed)
+        NULL = 0x01,
+        /// RULER record follows the line
+        RULER = 0x02,
+        /// line spacing value follows tag
+        LSPACE = 0x04,
+        /// nudge values follow tag
+        NUDGE = 0x08,
+    }
+}
+
+option_flags! {
+    /// Option flags of a PILE record, see `constants::options`.
+    PileOptions {
+        /// RULER record follows the pile
+        RULER = 0x02,
+        /// nudge values follow tag
+        NUDGE = 0x08,
+    }
+}
+
+option_flags! {
+    /// Option flags of a COLOR_DEF record, see `constants::options`.
+    ColorDefOptions {
+        /// color model is CMYK, else RGB
+        CMYK = 0x01,
+        /// color is a spot color, else a process color
+        SPOT = 0x02,
+        /// color has a name, else no name
+        NAME = 0x04,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MTChar {
+    pub(crate) options: CharOptions,
+    pub(crate) nudge: (i16, i16),
+    pub(crate) typeface: Typeface,
+    pub(crate) mtcode: Option<u16>,
+    pub(crate) fp8: Option<u8>,
+    pub(crate) fp16: Option<u16>,
+}
+
+impl MTChar {
+    pub fn options(&self) -> CharOptions {
+        self.options
+    }
+
+    pub fn typeface(&self) -> Typeface {
+        self.typeface
+    }
+
+    pub fn set_typeface(&mut self, typeface: Typeface) {
+        self.typeface = typeface;
+    }
+
+    pub fn mtcode(&self) -> Option<u16> {
+        self.mtcode
+    }
+
+    /// Makes this another character: `mtcode` replaces the MTCode and the font
+    /// positions, which stood for the old character, are dropped.
+    pub fn set_mtcode(&mut self, mtcode: u16) {
+        self.mtcode = Some(mtcode);
+        self.fp8 = None;
+        self.fp16 = None;
+        self.options.remove(CharOptions::ENC_NO_MTCODE | CharOptions::ENC_CHAR_8 | CharOptions::ENC_CHAR_16);
+    }
+}
+
+/// Typeface of a CHAR record, written biased by 128: a style of the EQN_PREFS style
+/// table when positive (see `constants::typeface`), an explicit FONT_STYLE_DEF when
+/// negative.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Typeface {
+    Text,
+    Function,
+    Variable,
+    LcGreek,
+    UcGreek,
+    Symbol,
+    Vector,
+    Number,
+    User1,
+    User2,
+    MtExtra,
+    TextFe,
+    Expand,
+    Marker,
+    Space,
+    /// A style without a name: 0, 13 to 21, or from 25 on.
+    Style(u8),
+    /// -n for the n-th FONT_STYLE_DEF record.
+    ExplicitFont(i8),
+}
+
+impl Typeface {
+    /// The typeface of a CHAR record's biased byte.
+    pub fn from_biased(byte: u8) -> Typeface {
+        let style = match byte.checked_sub(128) {
+            Some(style) => style,
+            None => return Typeface::ExplicitFont((i16::from(byte) - 128) as i8),
+        };
+        match style {
+            FN_TEXT => Typeface::Text,
+            FN_FUNCTION => Typeface::Function,
+            FN_VARIABLE => Typeface::Variable,
+            FN_LCGREEK => Typeface::LcGreek,
+            FN_UCGREEK => Typeface::UcGreek,
+            FN_SYMBOL => Typeface::Symbol,
+            FN_VECTOR => Typeface::Vector,
+            FN_NUMBER => Typeface::Number,
+            FN_USER1 => Typeface::User1,
+            FN_USER2 => Typeface::User2,
+            FN_MTEXTRA => Typeface::MtExtra,
+            FN_TEXT_FE => Typeface::TextFe,
+            FN_EXPAND => Typeface::Expand,
+            FN_MARKER => Typeface::Marker,
+            FN_SPACE => Typeface::Space,
+            style => Typeface::Style(style),
+        }
+    }
+
+    /// The byte of a CHAR record, biased by 128.
+    pub fn biased(self) -> u8 {
+        match self {
+            Typeface::ExplicitFont(index) => (i16::from(index) + 128) as u8,
+            style => 128u8.wrapping_add(style.style().unwrap_or(0)),
+        }
+    }
+
+    /// Style number (`constants::typeface`), `None` for explicit fonts.
+    pub fn style(self) -> Option<u8> {
+        Some(match self {
+            Typeface::Text => FN_TEXT,
+            Typeface::Function => FN_FUNCTION,
+            Typeface::Variable => FN_VARIABLE,
+            Typeface::LcGreek => FN_LCGREEK,
+            Typeface::UcGreek => FN_UCGREEK,
+            Typeface::Symbol => FN_SYMBOL,
+            Typeface::Vector => FN_VECTOR,
+            Typeface::Number => FN_NUMBER,
+            Typeface::User1 => FN_USER1,
+            Typeface::User2 => FN_USER2,
+            Typeface::MtExtra => FN_MTEXTRA,
+            Typeface::TextFe => FN_TEXT_FE,
+            Typeface::Expand => FN_EXPAND,
+            Typeface::Marker => FN_MARKER,
+            Typeface::Space => FN_SPACE,
+            Typeface::Style(style) => style,
+            Typeface::ExplicitFont(_) => return None,
+        })
+    }
+}
+
+/// Character embellishment, the list of them follows a CHAR record flagged
+/// with `CharOptions::EMBELL` and is closed by an END record.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MTEmbell {
+    pub(crate) nudge: (i16, i16),
+    pub(crate) embell: u8,
+}
+
+/// Color definition, from a COLOR_DEF record.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MTColorDef {
+    pub(crate) value: ColorValue,
+    /// a spot color, else a process color
+    pub(crate) spot: bool,
+    pub(crate) name: Option<String>,
+}
+
+/// Components of a color, each from 0 to 65535.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorValue {
+    Rgb([u16; 3]),
+    Cmyk([u16; 4]),
+}
+
+/// Line spacing MathType uses unless its preferences say otherwise, 150%.
+#[cfg(any(feature = "latex", feature = "mathml"))]
+pub(crate) const DEFAULT_LINE_SPACING: f32 = 1.5;
+
+#[cfg(feature = "std")]
 impl MTEquation {
     /// How MTEF is stored in files and objects
     /// https://docs.wiris.com/en/mathtype/mathtype_desktop/mathtype-sdk/mtefstorage
-    pub fn from_ole(path: &str) -> Result<MTEquation, super::error::Error> {
-        let reader = ole::Reader::from_path(path).unwrap();
-        for entry in reader.iterate() {
-            if entry.name() == "Equation Native" {
-                let mut slice = reader.get_entry_slice(entry).unwrap();
-                let mut buf = vec![0; slice.len()];
-                slice.read(&mut buf).unwrap();
-                let hdr = EqnOleFileHdr::parse_ole_hdr(&buf).unwrap();
-                let body = buf[hdr.cb_hdr as usize..(hdr.cb_hdr as usize + hdr.size as usize)].to_vec();
-                let t = MTEquation::parse(body).unwrap();
-                return Ok(t);
+    ///
+    /// Equations inserted through an `EMBED Equation.DSMT4` field code live in their own
+    /// storage next to `\x01CompObj` and `\x03ObjInfo` streams. Objects converted from OLE1
+    /// have no "Equation Native" stream, their native data is kept in `\x01Ole10Native` instead.
+    ///
+    /// Paths go through `std::fs` as they are, so paths that are not UTF-8, UNC paths
+    /// and paths longer than `MAX_PATH` on Windows open like any other.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(path),
+                                                        fields(path = %path.as_ref().display())))]
+    pub fn from_ole<P: AsRef<Path>>(path: P) -> Result<MTEquation, super::error::Error> {
+        MTEquation::from_ole_file(std::fs::File::open(path)?)
+    }
+
+    /// Same as `from_ole`, for an OLE file already opened.
+    pub fn from_ole_file(file: std::fs::File) -> Result<MTEquation, super::error::Error> {
+        let reader = ole::Reader::new(file).map_err(|_| super::error::Error::InvalidOLEFile)?;
+        MTEquation::from_ole_entries(&reader, &Budget::new(&ParseOptions::default()))
+    }
+
+    /// Same as `from_ole`, for an OLE file read from anything implementing `Read`.
+    pub fn from_ole_reader<R: Read>(readable: R) -> Result<MTEquation, super::error::Error> {
+        MTEquation::from_ole_reader_with(readable, &ParseOptions::default())
+    }
+
+    /// `from_ole` within the limits of `options`.
+    pub fn from_ole_with<P: AsRef<Path>>(path: P, options: &ParseOptions) -> Result<MTEquation, super::error::Error> {
+        MTEquation::from_ole_reader_with(std::fs::File::open(path)?, options)
+    }
+
+    /// `from_ole_reader` within the limits of `options`. At most `max_bytes` and one
+    /// more are read from `readable`.
+    pub fn from_ole_reader_with<R: Read>(readable: R, options: &ParseOptions) -> Result<MTEquation, super::error::Error> {
+        let budget = Budget::new(options);
+        let mut buf = vec![];
+        readable.take(budget.read_limit()).read_to_end(&mut buf)?;
+        budget.check_size(buf.len())?;
+        MTEquation::from_ole_buf(&buf, &budget, options)
+    }
+
+    /// Same as `from_ole_reader`, for sources that can seek, e.g. a `Cursor` over a blob
+    /// taken out of a zip: the OLE file runs from the current position to the end.
+    pub fn from_reader<R: Read + Seek>(readable: R) -> Result<MTEquation, super::error::Error> {
+        MTEquation::from_reader_with(readable, &ParseOptions::default())
+    }
+
+    /// `from_reader` within the limits of `options`. The size is known before reading,
+    /// input over `max_bytes` is rejected without reading any of it.
+    pub fn from_reader_with<R: Read + Seek>(mut readable: R, options: &ParseOptions) -> Result<MTEquation, super::error::Error> {
+        let budget = Budget::new(options);
+        let start = readable.stream_position()?;
+        let len = readable.seek(SeekFrom::End(0))?.saturating_sub(start);
+        budget.check_size(usize::try_from(len).unwrap_or(usize::MAX))?;
+        readable.seek(SeekFrom::Start(start))?;
+        let mut buf = Vec::with_capacity(len as usize);
+        readable.read_to_end(&mut buf)?;
+        MTEquation::from_ole_buf(&buf, &budget, options)
+    }
+
+    fn from_ole_buf(buf: &[u8], budget: &Budget, options: &ParseOptions) -> Result<MTEquation, super::error::Error> {
+        let reader = ole::Reader::new(buf).map_err(|_| super::error::Error::InvalidOLEFile)?;
+        match MTEquation::from_ole_entries(&reader, budget) {
+            Err(super::error::Error::LinkedObjectNotEmbedded(source)) if options.resolve_links => {
+                // the source holds the equation itself, links are not followed further
+                let options = ParseOptions { resolve_links: false, ..options.clone() };
+                match std::fs::File::open(&source) {
+                    Ok(file) => MTEquation::from_ole_reader_with(file, &options),
+                    Err(_) => Err(super::error::Error::LinkedObjectNotEmbedded(source)),
+                }
             }
+            result => result.map(|mut eqn| {
+                eqn.fill_missing_slots = options.fill_missing_slots;
+                eqn
+            }),
         }
-        Err(super::error::Error::InvalidOLEFile)
+    }
+
+    fn from_ole_entries(reader: &ole::Reader, budget: &Budget) -> Result<MTEquation, super::error::Error> {
+        let mut eqn = MTEquation::from_equation_native_within(&native_data(reader)?, budget)?;
+        eqn.presentation = super::presentation::from_ole(reader);
+        Ok(eqn)
+    }
+
+    pub(crate) fn from_equation_native(buf: &[u8]) -> Result<MTEquation, super::error::Error> {
+        MTEquation::from_equation_native_within(buf, &Budget::new(&ParseOptions::default()))
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(buf, budget), fields(len = buf.len())))]
+    fn from_equation_native_within(buf: &[u8], budget: &Budget) -> Result<MTEquation, super::error::Error> {
+        let hdr = EqnOleFileHdr::parse_ole_hdr(buf)?;
+        let start = hdr.cb_hdr as usize;
+        let end = start + hdr.size as usize;
+        if end > buf.len() {
+            return Err(super::error::Error::BadSizeValue("EQNOLEFILEHDR size"));
+        }
+        MTEquation::from_mtef_within(&buf[start..end], budget)
+    }
+
+    /// MTEF on its own, without the OLE file or the EQNOLEFILEHDR around it, as some
+    /// pipelines hand it over. Compressed MTEF and MTEF older than version 5 are read
+    /// as in objects.
+    pub fn from_bytes(mtef: &[u8]) -> Result<MTEquation, super::error::Error> {
+        MTEquation::from_bytes_with(mtef, &ParseOptions::default())
+    }
+
+    /// `from_bytes` within the limits of `options`.
+    pub fn from_bytes_with(mtef: &[u8], options: &ParseOptions) -> Result<MTEquation, super::error::Error> {
+        let budget = Budget::new(options);
+        budget.check_size(mtef.len())?;
+        let mut eqn = MTEquation::from_mtef_within(mtef, &budget)?;
+        eqn.fill_missing_slots = options.fill_missing_slots;
+        Ok(eqn)
+    }
+
+    fn from_mtef_within(payload: &[u8], budget: &Budget) -> Result<MTEquation, super::error::Error> {
+        let mtef = match is_zlib(payload) {
+            true => inflate(payload, budget.read_limit())?,
+            false => payload.to_vec(),
+        };
+        budget.check_size(mtef.len())?;
+        // Equation Editor 3.0 and MathType 3/4 objects carry older MTEF versions
+        let eqn = match super::upgrade::needs_upgrade(&mtef) {
+            true => {
+                let eqn = super::upgrade::upgrade(&mtef)?;
+                budget.check_records_depth(&eqn.records)?;
+                eqn
+            }
+            false => MTEquation::parse_within(mtef, budget)?,
+        };
+        budget.check_time()?;
+        Ok(eqn)
     }
 
     /// Introduction
@@ -96,182 +837,216 @@ impl MTEquation {
     ///
     /// This document sometimes refers to MathType's internal names for values (e.g. parmLINESPACE).
     /// These are given for reference purposes and are handy for reducing error when such values are communicated by humans.
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(buf), fields(len = buf.len())))]
     pub fn parse(buf: Vec<u8>) -> Result<MTEquation, super::error::Error> {
-        let mut cur = Cursor::new(buf);
-        let mut eqn = MTEquation {
-            m_mtef_ver: cur.read_u8().unwrap(),
-            m_platform: cur.read_u8().unwrap(),
-            m_product: cur.read_u8().unwrap(),
-            m_version: cur.read_u8().unwrap(),
-            m_version_sub: cur.read_u8().unwrap(),
-            m_application: read_null_terminated_string(&mut cur).unwrap(),
-            m_inline: cur.read_u8().unwrap(),
+        MTEquation::parse_with(buf, &ParseOptions::default())
+    }
+
+    /// `parse` within the limits of `options`.
+    pub fn parse_with(buf: Vec<u8>, options: &ParseOptions) -> Result<MTEquation, super::error::Error> {
+        let budget = Budget::new(options);
+        budget.check_size(buf.len())?;
+        let mut eqn = MTEquation::parse_within(buf, &budget)?;
+        eqn.fill_missing_slots = options.fill_missing_slots;
+        Ok(eqn)
+    }
+
+    fn parse_within(buf: Vec<u8>, budget: &Budget) -> Result<MTEquation, super::error::Error> {
+        if super::upgrade::needs_upgrade(&buf) {
+            return Err(super::error::Error::MalformedMTEF("MTEF older than version 5"));
+        }
+        if ParseMode::Lenient == budget.mode {
+            let (mut eqn, report) = MTEquation::parse_repaired(&buf)?;
+            eqn.warnings = report.repairs;
+            budget.check_records_depth(&eqn.records)?;
+            budget.check_time()?;
+            return Ok(eqn);
+        }
+        let strict = ParseMode::Strict == budget.mode;
+        let mut parser = StreamParser::new();
+        parser.push(&buf);
+        let mut records = vec![];
+        let mut spans = vec![];
+        // the equation itself is closed by the last END
+        let mut open = 1usize;
+        while let Some(record) = parser.next_record()? {
+            budget.check_time()?;
+            open = match &record {
+                MTRecords::FUTURE(_) if strict => return Err(super::error::Error::MalformedMTEF("unknown record type")),
+                MTRecords::END if strict => open.checked_sub(1).ok_or(super::error::Error::MalformedMTEF("unbalanced END record"))?,
+                MTRecords::END => open.saturating_sub(1),
+                record if opens_scope(record) => open + 1,
+                _ => open,
+            };
+            budget.check_depth(open)?;
+            records.push(record);
+            spans.extend(parser.last_span());
+        }
+        if strict && !parser.skipped_matrices().is_empty() {
+            return Err(super::error::Error::MalformedMTEF("MATRIX records are not supported"));
+        }
+        let skipped_matrices = parser.skipped_matrices().to_vec();
+        let header = parser.finish()?;
+        if strict && open != 0 {
+            return Err(super::error::Error::MalformedMTEF("unbalanced END record"));
+        }
+        let mut eqn = MTEquation::from_parts(header, records);
+        eqn.spans = spans;
+        eqn.skipped_matrices = skipped_matrices;
+        #[cfg(feature = "tracing")]
+        tracing::debug!(records = eqn.records.len(), "parsed MTEF");
+        Ok(eqn)
+    }
+
+    pub(crate) fn from_parts(header: MTHeader, records: Vec<MTRecords>) -> MTEquation {
+        MTEquation {
+            m_mtef_ver: header.mtef_ver,
+            m_platform: header.platform,
+            m_product: header.product,
+            m_version: header.version,
+            m_version_sub: header.version_sub,
+            m_application: header.application,
+            m_inline: header.inline,
             encoding_defs: vec![
                 MTRecords::ENCODING_DEF("MTCode".to_string()),
                 MTRecords::ENCODING_DEF("Unknown".to_string()),
                 MTRecords::ENCODING_DEF("Symbol".to_string()),
                 MTRecords::ENCODING_DEF("MTExtra".to_string()),
             ],
-            records: vec![],
-        };
-        loop {
-            match cur.read_u8() {
-                Ok(END) => eqn.records.push(MTRecords::END),
-                Ok(LINE) => {
-                    let options = cur.read_u8().unwrap();
-                    let mut line = MTLine {
-                        nudge: (0, 0),
-                        line_spacing: 0,
-                        null: false,
-                    };
-                    if MTEF_OPT_NUDGE == MTEF_OPT_NUDGE & options {
-                        line.nudge = read_nudge_values(&mut cur)
-                    }
-                    if MTEF_OPT_LINE_LSPACE == MTEF_OPT_LINE_LSPACE & options {
-                        line.line_spacing = cur.read_u8().unwrap()
-                    }
-                    if MTEF_OPT_LINE_NULL == MTEF_OPT_LINE_NULL & options {
-                        line.null = true
-                    }
-                    eqn.records.push(MTRecords::LINE(line))
-                }
-                Ok(CHAR) => {
-                    let mut ch = MTChar { nudge: (0, 0), typeface: 0,
-                        mtcode: None, fp8: None, fp16: None };
-                    let options = cur.read_u8().unwrap();
-                    if MTEF_OPT_NUDGE == MTEF_OPT_NUDGE & options {
-                        ch.nudge = read_nudge_values(&mut cur)
-                    }
-                    ch.typeface = cur.read_u8().unwrap();
-
-                    if MTEF_OPT_CHAR_ENC_NO_MTCODE != MTEF_OPT_CHAR_ENC_NO_MTCODE & options {
-                        ch.mtcode = Some(cur.read_u16::<LittleEndian>().unwrap())
-                    }
-                    if MTEF_OPT_CHAR_ENC_CHAR_8 == MTEF_OPT_CHAR_ENC_CHAR_8 & options {
-                        ch.fp8 = Some(cur.read_u8().unwrap());
-                    }
-                    if MTEF_OPT_CHAR_ENC_CHAR_16 == MTEF_OPT_CHAR_ENC_CHAR_16 & options {
-                        ch.fp16 = Some(cur.read_u16::<LittleEndian>().unwrap());
-                    }
-                    let record = MTRecords::CHAR(ch);
-                    eqn.records.push(record)
-                }
-                Ok(TMPL) => {
-                    let mut tmpl = MTTmpl { nudge: (0, 0), selector: 0, variation: 0, options: 0 };
-                    let options = cur.read_u8().unwrap();
-                    if MTEF_OPT_NUDGE == MTEF_OPT_NUDGE & options {
-                        tmpl.nudge = read_nudge_values(&mut cur)
-                    }
-                    tmpl.selector = cur.read_u8().unwrap();
-
-                    // variation, 1 or 2 bytes
-                    let byte1 = cur.read_u8().unwrap() as u16;
-                    tmpl.variation = match 0x80 == byte1 & 0x80 {
-                        true => {
-                            let byte2 = cur.read_u8().unwrap() as u16;
-                            (byte1 & 0x7F) | (byte2 << 8)
-                        },
-                        false => { byte1 }
-                    };
-                    tmpl.options = cur.read_u8().unwrap();
-                    let record = MTRecords::TMPL(tmpl);
-                    eqn.records.push(record)
-                }
-                Ok(PILE) => { println!("PILE") }
-                Ok(EMBELL) => { println!("EMBELL") }
-                Ok(MATRIX) => { println!("MATRIX") }
-                Ok(RULER) => { println!("RULER") }
-                Ok(FONT_STYLE_DEF) => {
-                    let record = MTRecords::FONT_STYLE_DEF {
-                        font_def_index: cur.read_u8().unwrap(),
-                        char_style: cur.read_u8().unwrap()
-                    };
-                    eqn.records.push(record)
-                }
-                Ok(SIZE) => { println!("SIZE") }
-                Ok(FULL) => eqn.records.push(MTRecords::FULL),
-                Ok(SUB) => eqn.records.push(MTRecords::SUB),
-                Ok(SUB2) => eqn.records.push(MTRecords::SUB2),
-                Ok(SYM) => eqn.records.push(MTRecords::SYM),
-                Ok(SUBSYM) => eqn.records.push(MTRecords::SUBSYM),
-                Ok(COLOR) => { println!("COLOR") }
-                Ok(COLOR_DEF) => { println!("COLOR_DEF") }
-                Ok(FONT_DEF) => {
-                    let record = MTRecords::FONT_DEF {
-                        enc_def_index: cur.read_u8().unwrap(),
-                        name: read_null_terminated_string(&mut cur).unwrap(),
-                    };
-                    eqn.records.push(record)
-                }
-                Ok(EQN_PREFS) => {
-                    let _options = cur.read_u8().unwrap();
-
-                    // sizes
-                    let size = cur.read_u8().unwrap();
-                    let sizes = read_dimension_arrays(&mut cur, size).unwrap();
-
-                    // spaces
-                    let size = cur.read_u8().unwrap();
-                    let spaces = read_dimension_arrays(&mut cur, size).unwrap();
-
-                    // styles
-                    let size = cur.read_u8().unwrap();
-                    let mut styles = vec![];
-                    for _i in 0..size {
-                        let c = cur.read_u8().unwrap();
-                        match c == 0 {
-                            true => { styles.push(None) },
-                            false => { styles.push(Some(cur.read_u8().unwrap())) }
-                        }
-                    }
-                    let record = MTRecords::EQN_PREFS { sizes, spaces, styles };
-                    eqn.records.push(record)
-                }
-                Ok(ENCODING_DEF) => eqn.records.push(
-                    MTRecords::ENCODING_DEF(read_null_terminated_string(&mut cur).unwrap())),
-                Ok(FUTURE) => eqn.records.push(MTRecords::FUTURE),
-                Ok(_) => eqn.records.push(MTRecords::FUTURE),
-                Err(_e) => break
-            }
+            records,
+            spans: vec![],
+            presentation: None,
+            fill_missing_slots: false,
+            warnings: vec![],
+            skipped_matrices: vec![],
         }
-        Ok(eqn)
     }
-}
 
+    /// What parsing in `ParseMode::Lenient` skipped and closed, empty for equations
+    /// parsed whole.
+    pub fn warnings(&self) -> &[super::repair::Repair] {
+        &self.warnings
+    }
 
-impl MTEquation {
-    pub fn translate(&self) -> Result<String, super::error::Error> {
-        let mut latex = String::new();
+    /// Name of the encoding used by characters in `typeface`: a style (FN_TEXT...) is
+    /// resolved through the EQN_PREFS style table, an explicit font through its
+    /// FONT_STYLE_DEF.
+    pub(crate) fn encoding(&self, typeface: Typeface) -> Option<&str> {
+        let encodings: Vec<&str> = self.encoding_defs.iter().chain(self.records.iter())
+            .filter_map(|r| match r {
+                MTRecords::ENCODING_DEF(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        let (enc_def_index, _, _) = self.font(typeface)?;
+        // indices are 1-based
+        encodings.get((enc_def_index as usize).checked_sub(1)?).cloned()
+    }
 
-        let tx_char = |record: &MTChar, tx: &mut String| {
-            if let Some(mtcode) = record.mtcode {
-                let s = String::from_utf16_lossy(&[mtcode]);
-                tx.push_str(&s);
-            }
-        };
+    /// Name of the font used by characters in `typeface`, resolved as for `encoding`.
+    pub(crate) fn font_name(&self, typeface: Typeface) -> Option<&str> {
+        self.font(typeface).map(|(_, name, _)| name)
+    }
+
+    /// Character style (bit 0 italic, bit 1 bold) of `typeface`, resolved as for `encoding`.
+    pub(crate) fn char_style(&self, typeface: Typeface) -> Option<u8> {
+        self.font(typeface).map(|(_, _, style)| style)
+    }
+
+    /// Full size from EQN_PREFS, when given in points.
+    #[cfg(any(feature = "latex", feature = "mathml"))]
+    pub(crate) fn full_size(&self) -> Option<f32> {
+        self.records.iter().find_map(|record| match record {
+            MTRecords::EQN_PREFS { sizes, .. } => sizes.first()?.strip_prefix("pt")?.parse().ok(),
+            _ => None,
+        }).filter(|size| *size > 0.0)
+    }
+
+    /// Distance between the baselines of piled lines relative to the full size, from
+    /// the line spacing of EQN_PREFS when given as a percentage.
+    #[cfg(any(feature = "latex", feature = "mathml"))]
+    pub(crate) fn line_spacing(&self) -> f32 {
+        self.records.iter().find_map(|record| match record {
+            MTRecords::EQN_PREFS { spaces, .. } => spaces.first()?.strip_prefix('%')?.parse::<f32>().ok(),
+            _ => None,
+        }).filter(|percent| *percent > 0.0).map_or(DEFAULT_LINE_SPACING, |percent| percent / 100.0)
+    }
+
+    /// ENCODING_DEF index and name of the FONT_DEF used by characters in `typeface`,
+    /// and their character style.
+    fn font(&self, typeface: Typeface) -> Option<(u8, &str, u8)> {
+        let mut fonts = vec![];
+        let mut font_styles = vec![];
+        let mut styles: &[Option<(u8, u8)>] = &[];
         for record in &self.records {
             match record {
-                MTRecords::CHAR(ch) => tx_char(ch, &mut latex),
+                MTRecords::FONT_DEF { enc_def_index, name } => fonts.push((*enc_def_index, name.as_str())),
+                MTRecords::FONT_STYLE_DEF { font_def_index, char_style } => font_styles.push((*font_def_index, *char_style)),
+                MTRecords::EQN_PREFS { styles: s, .. } => styles = s,
                 _ => {}
             }
         }
-        println!("{:?}", latex);
-        Ok("hello".to_string())
+        let (font_def_index, char_style) = match typeface {
+            Typeface::ExplicitFont(index) => *font_styles.get((-i16::from(index) as usize).checked_sub(1)?)?,
+            Typeface::Style(0) => return None,
+            style => (*styles.get(style.style()? as usize - 1)?)?,
+        };
+        // indices are 1-based
+        let (enc_def_index, name) = *fonts.get((font_def_index as usize).checked_sub(1)?)?;
+        Some((enc_def_index, name, char_style))
+    }
+
+    /// Records of the equation, in stream order.
+    pub fn records(&self) -> impl Iterator<Item = &MTRecords> {
+        self.records.iter()
+    }
+
+    /// Records of the equation, to add, drop or change. `to_bytes` writes them as they
+    /// are left: records opening a scope need their END, which `validate_strict` checks.
+    pub fn records_mut(&mut self) -> &mut Vec<MTRecords> {
+        // records may move, their spans cannot be kept
+        self.spans.clear();
+        &mut self.records
     }
+
+    /// Bytes of the MTEF record `index` was read from, its tag included, for tools
+    /// annotating dumps and for locating damage. `None` for equations not parsed from
+    /// MTEF 5, and for all records once `records_mut` was called. Records changed in
+    /// place keep the span they were read from.
+    pub fn span(&self, index: usize) -> Option<Range<usize>> {
+        self.spans.get(index).cloned()
+    }
+
+    /// Top-level lines of the equation, each one translatable on its own.
+    pub fn lines(&self) -> impl Iterator<Item = EquationLine<'_>> {
+        super::ast::build(&self.records, self.fill_missing_slots).into_iter().map(move |line| EquationLine { eqn: self, line })
+    }
+}
+
+/// One displayed line of a (possibly multi-line) equation.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct EquationLine<'a> {
+    #[cfg_attr(not(any(feature = "latex", feature = "mathml")), allow(dead_code))]
+    pub(crate) eqn: &'a MTEquation,
+    pub(crate) line: super::ast::Line<'a>,
 }
+
+
 /// How MTEF is Stored in Files and Objects
 /// http://web.archive.org/web/20010304111449/http://mathtype.com/support/tech/MTEF_storage.htm#OLE%20Objects
 /// OLE Equation Objects
 /// MTEF data is saved as the native data format of the object.
 /// Whenever an equation object is to be written to an OLE "stream", a 28- byte header is written, followed by the MTEF data.
+#[cfg(feature = "std")]
 #[derive(Debug)]
-struct EqnOleFileHdr {
+pub struct EqnOleFileHdr {
     // length of header, sizeof(EQNOLEFILEHDR) = 28 bytes
-    cb_hdr: u16,
+    pub(crate) cb_hdr: u16,
     // hiword = 2, loword = 0
     version: u32,
     cf: u16,
-    size: u32,
+    pub(crate) size: u32,
     reserved1: u32,
     reserved2: u32,
     reserved3: u32,
@@ -279,8 +1054,12 @@ struct EqnOleFileHdr {
 }
 
 
+#[cfg(feature = "std")]
 impl EqnOleFileHdr {
-    fn parse_ole_hdr(buf: &Vec<u8>) -> Result<EqnOleFileHdr, super::error::Error> {
+    pub(crate) fn parse_ole_hdr(buf: &[u8]) -> Result<EqnOleFileHdr, super::error::Error> {
+        if buf.len() < 28 {
+            return Err(super::error::Error::BadSizeValue("EQNOLEFILEHDR"));
+        }
         let mut cur = Cursor::new(buf);
         let hdr = EqnOleFileHdr {
             cb_hdr: cur.read_u16::<LittleEndian>().unwrap(),
@@ -298,82 +1077,624 @@ impl EqnOleFileHdr {
             Ok(hdr)
         }
     }
+
+    pub fn builder() -> EqnOleFileHdrBuilder {
+        EqnOleFileHdrBuilder::default()
+    }
+
+    /// Clipboard format the MTEF was registered under.
+    pub fn cf(&self) -> u16 {
+        self.cf
+    }
+
+    /// Bytes of MTEF following the header.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn to_bytes(&self) -> [u8; EQN_OLE_FILE_HDR_LEN] {
+        let mut buf = [0u8; EQN_OLE_FILE_HDR_LEN];
+        buf[0..2].copy_from_slice(&self.cb_hdr.to_le_bytes());
+        buf[2..6].copy_from_slice(&self.version.to_le_bytes());
+        buf[6..8].copy_from_slice(&self.cf.to_le_bytes());
+        buf[8..12].copy_from_slice(&self.size.to_le_bytes());
+        for (i, reserved) in [self.reserved1, self.reserved2, self.reserved3, self.reserved4].iter().enumerate() {
+            buf[12 + 4 * i..16 + 4 * i].copy_from_slice(&reserved.to_le_bytes());
+        }
+        buf
+    }
 }
 
+/// sizeof(EQNOLEFILEHDR)
+#[cfg(feature = "std")]
+pub(crate) const EQN_OLE_FILE_HDR_LEN: usize = 28;
+/// hiword = 2, loword = 0
+#[cfg(feature = "std")]
+const EQN_OLE_FILE_HDR_VERSION: u32 = 0x0002_0000;
+/// Clipboard format ids `RegisterClipboardFormat` hands out, the "MathType EF" format
+/// MTEF is registered under among them.
+#[cfg(feature = "std")]
+const REGISTERED_FORMATS: std::ops::RangeInclusive<u16> = 0xC000..=0xFFFF;
 
-/// value 	symbol 	description
-/// 0 	END 	end of MTEF, pile, line, embellishment list, or template
-const END: u8 = 0;
-/// 1 	LINE 	line (slot)
-const LINE: u8 = 1;
-/// 2 	CHAR 	character
-const CHAR: u8 = 2;
-/// 3 	TMPL 	template
-const TMPL: u8 = 3;
-/// 4 	PILE 	pile (vertical stack of lines)
-const PILE: u8 = 4;
-/// 5 	MATRIX 	matrix
-const EMBELL: u8 = 5;
-/// 6 	EMBELL 	character embellishment (e.g. hat, prime)
-const MATRIX: u8 = 6;
-/// 7 	RULER 	ruler (tab-stop location)
-const RULER: u8 = 7;
-/// 8 	FONT_STYLE_DEF 	font/char style definition
-const FONT_STYLE_DEF: u8 = 8;
-/// 9 	SIZE 	general size
-const SIZE: u8 = 9;
-/// 10 	FULL 	full size
-const FULL: u8 = 10;
-/// 11 	SUB 	subscript size
-const SUB: u8 = 11;
-/// 12 	SUB2 	sub-subscript size
-const SUB2: u8 = 12;
-/// 13 	SYM 	symbol size
-const SYM: u8 = 13;
-/// 14 	SUBSYM 	sub-symbol size
-const SUBSYM: u8 = 14;
-/// 15 	COLOR 	color
-const COLOR: u8 = 15;
-/// 16 	COLOR_DEF 	color definition
-const COLOR_DEF: u8 = 16;
-/// 17 	FONT_DEF 	font definition
-const FONT_DEF: u8 = 17;
-/// 18 	EQN_PREFS 	equation preferences (sizes, styles, spacing)
-const EQN_PREFS: u8 = 18;
-/// 19 	ENCODING_DEF 	encoding definition
-const ENCODING_DEF: u8 = 19;
-/// >= 100 	FUTURE 	for future expansion (see below)
-const FUTURE: u8 = 100;
-
-/// nudge values follow tag
-const MTEF_OPT_NUDGE: u8 = 0x08;
-// line is a placeholder only (i.e. not displayed)
-const MTEF_OPT_LINE_NULL: u8 = 0x01;
-// line spacing value follows tag
-const MTEF_OPT_LINE_LSPACE: u8 = 0x04;
-// RULER record follows LINE or PILE record
-const MTEF_OPT_LP_RULER: u8 = 0x02;
-/// Option flag values for CHAR records:
-// character is followed by an embellishment list
-const MTEF_OPT_CHAR_EMBELL: u8 = 0x01;
-// character starts a function (sin, cos, etc.)
-const MTEF_OPT_CHAR_FUNC_START: u8 = 0x02;
-// character is written with an 8-bit encoded value
-const MTEF_OPT_CHAR_ENC_CHAR_8: u8 = 0x04;
-// character is written with an 16-bit encoded value
-const MTEF_OPT_CHAR_ENC_CHAR_16: u8 = 0x10;
-// character is written without an 16-bit MTCode value
-const MTEF_OPT_CHAR_ENC_NO_MTCODE: u8 = 0x20;
-
-fn read_null_terminated_string(cur: &mut Cursor<Vec<u8>>) -> Result<String, Cow<'static, str>> {
+/// Header of the MTEF written to an "Equation Native" stream: `cb_hdr`, `version`
+/// and `size` come from the MTEF, the clipboard format from the caller.
+///
+/// The clipboard format is not derived from the MathType version the MTEF targets:
+/// it is whatever id `RegisterClipboardFormat("MathType EF")` returned in the
+/// Windows session that wrote the object (0xC39F in `assets/oleObject1.bin`), and
+/// differs from one session to the next for the same MathType. Only the header of
+/// the stream being replaced, or a call on the machine writing it, gives it.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct EqnOleFileHdrBuilder {
+    cf: Option<u16>,
+}
+
+#[cfg(feature = "std")]
+impl EqnOleFileHdrBuilder {
+    /// Id of the registered "MathType EF" clipboard format, as found in the header of
+    /// the stream being replaced or returned by `RegisterClipboardFormat`.
+    pub fn cf(mut self, cf: u16) -> EqnOleFileHdrBuilder {
+        self.cf = Some(cf);
+        self
+    }
+
+    /// Header for `mtef`, the bytes of `MTEquation::to_bytes`, compressed or not.
+    pub fn build(&self, mtef: &[u8]) -> Result<EqnOleFileHdr, super::error::Error> {
+        let cf = self.cf.ok_or(super::error::Error::MalformedMTEF("EQNOLEFILEHDR without a clipboard format"))?;
+        if !REGISTERED_FORMATS.contains(&cf) {
+            return Err(super::error::Error::MalformedMTEF("EQNOLEFILEHDR clipboard format is not a registered one"));
+        }
+        match mtef.first() {
+            Some(1..=5) => {}
+            _ if is_zlib(mtef) => {}
+            _ => return Err(super::error::Error::MalformedMTEF("unknown MTEF version")),
+        }
+        if mtef.len() > u32::MAX as usize {
+            return Err(super::error::Error::BadSizeValue("EQNOLEFILEHDR size"));
+        }
+        Ok(EqnOleFileHdr {
+            cb_hdr: EQN_OLE_FILE_HDR_LEN as u16,
+            version: EQN_OLE_FILE_HDR_VERSION,
+            cf,
+            size: mtef.len() as u32,
+            reserved1: 0,
+            reserved2: 0,
+            reserved3: 0,
+            reserved4: 0,
+        })
+    }
+}
+
+
+/// Signature of OLE compound files.
+#[cfg(feature = "std")]
+pub(crate) const CFB_MAGIC: [u8; 8] = [0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+/// Name of the stream MathType writes the EQNOLEFILEHDR and MTEF data to.
+#[cfg(feature = "std")]
+pub(crate) const EQUATION_NATIVE: &str = "Equation Native";
+/// Native data of objects converted from OLE1, prefixed by a 4-byte length.
+#[cfg(feature = "std")]
+const OLE10_NATIVE: &str = "\u{1}Ole10Native";
+/// Word's per-object information stream (ODT structure).
+#[cfg(feature = "std")]
+const OBJ_INFO: &str = "\u{3}ObjInfo";
+
+/// Object information Word stores next to each embedded object (`\x03ObjInfo`).
+/// Only the flags are of interest here, the rest of the stream is ignored.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+struct ObjInfo {
+    // fIsOle1, the object is an OLE1 object
+    is_ole1: bool,
+}
+
+#[cfg(feature = "std")]
+impl ObjInfo {
+    /// Returns `None` on streams too short to hold the flags,
+    /// some producers write an empty ObjInfo.
+    fn parse(buf: &[u8]) -> Option<ObjInfo> {
+        let mut cur = Cursor::new(buf);
+        let flags = cur.read_u16::<LittleEndian>().ok()?;
+        // ODTPersist1: fIsOle1 is bit 7, bit 5 being reserved
+        Some(ObjInfo {
+            is_ole1: 0x0080 == flags & 0x0080,
+        })
+    }
+}
+
+/// Content of the "Equation Native" stream of the object, or of the Ole10Native one
+/// when the object was converted from OLE1, without its length prefix.
+#[cfg(feature = "std")]
+pub(crate) fn native_data(reader: &ole::Reader) -> Result<Vec<u8>, super::error::Error> {
+    let mut ole10_native = None;
+    let mut obj_info = None;
+    for entry in reader.iterate() {
+        match entry.name() {
+            EQUATION_NATIVE => return read_entry(reader, entry),
+            OLE10_NATIVE => ole10_native = Some(read_entry(reader, entry)?),
+            OBJ_INFO => obj_info = ObjInfo::parse(&read_entry(reader, entry)?),
+            _ => {}
+        }
+    }
+    if obj_info.map_or(true, |info| info.is_ole1) {
+        if let Some(mut buf) = ole10_native {
+            // Ole10Native data is prefixed by its own length
+            if buf.len() < 4 {
+                return Err(super::error::Error::BadSizeValue("Ole10Native"));
+            }
+            buf.drain(..4);
+            return Ok(buf);
+        }
+    }
+    match super::link::link_source(reader) {
+        Some(source) => Err(super::error::Error::LinkedObjectNotEmbedded(source)),
+        None => Err(super::error::Error::InvalidOLEFile),
+    }
+}
+
+#[cfg(feature = "std")]
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(reader, entry), fields(name = entry.name())))]
+pub(crate) fn read_entry(reader: &ole::Reader, entry: &ole::Entry) -> Result<Vec<u8>, super::error::Error> {
+    let mut slice = reader.get_entry_slice(entry).map_err(|_| super::error::Error::EmptyEntry)?;
     let mut buf = vec![];
-    cur.read_until(b'\0', &mut buf).unwrap();
-    buf.pop();
+    slice.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+
+/// Some generators compress the MTEF after the header. MTEF starts with its version
+/// (1 to 5), a zlib stream with a CMF byte for deflate (0x78) and a valid check value.
+#[cfg(feature = "std")]
+pub(crate) fn is_zlib(buf: &[u8]) -> bool {
+    buf.len() >= 2 && buf[0] == 0x78 && (u16::from(buf[0]) << 8 | u16::from(buf[1])) % 31 == 0
+}
+
+/// Inflates at most `limit` bytes of MTEF.
+#[cfg(feature = "compression")]
+pub(crate) fn inflate(buf: &[u8], limit: u64) -> Result<Vec<u8>, super::error::Error> {
+    let mut mtef = vec![];
+    flate2::read::ZlibDecoder::new(buf).take(limit).read_to_end(&mut mtef)?;
+    Ok(mtef)
+}
+
+#[cfg(all(feature = "std", not(feature = "compression")))]
+pub(crate) fn inflate(_buf: &[u8], _limit: u64) -> Result<Vec<u8>, super::error::Error> {
+    Err(super::error::Error::MalformedMTEF("compressed MTEF, build with the compression feature"))
+}
+
+/// Header preceding the records.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MTHeader {
+    pub(crate) mtef_ver: u8,
+    pub(crate) platform: u8,
+    pub(crate) product: u8,
+    pub(crate) version: u8,
+    pub(crate) version_sub: u8,
+    pub(crate) application: String,
+    pub(crate) inline: u8,
+}
+
+/// Why nothing could be decoded from the buffered bytes.
+#[derive(Debug)]
+pub enum DecodeError {
+    /// The bytes end in the middle of the header or record, more are needed.
+    Incomplete,
+    /// The bytes can not be decoded, whatever follows.
+    Invalid(super::error::Error),
+}
+
+impl core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            DecodeError::Incomplete => write!(f, "incomplete MTEF"),
+            DecodeError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for DecodeError {
+    fn from(e: std::io::Error) -> DecodeError {
+        match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => DecodeError::Incomplete,
+            _ => DecodeError::Invalid(e.into()),
+        }
+    }
+}
+
+impl From<super::error::Error> for DecodeError {
+    fn from(e: super::error::Error) -> DecodeError {
+        DecodeError::Invalid(e)
+    }
+}
+
+pub(crate) fn decode_header(cur: &mut Cursor<&[u8]>) -> Result<MTHeader, DecodeError> {
+    Ok(MTHeader {
+        mtef_ver: cur.read_u8()?,
+        platform: cur.read_u8()?,
+        product: cur.read_u8()?,
+        version: cur.read_u8()?,
+        version_sub: cur.read_u8()?,
+        application: read_null_terminated_string(cur)?,
+        inline: cur.read_u8()?,
+    })
+}
+
+/// Decodes the record at the cursor. Records whose payload is not decoded yet
+/// give `None`.
+pub(crate) fn decode_record(cur: &mut Cursor<&[u8]>) -> Result<Option<MTRecords>, DecodeError> {
+    // the profile feature counts bytes and time of each decoded record
+    #[cfg(feature = "profile")]
+    let read_record = |cur: &mut Cursor<&[u8]>| super::profile::measure(cur, read_record);
+    read_record(cur)
+}
+
+fn read_record(cur: &mut Cursor<&[u8]>) -> Result<Option<MTRecords>, DecodeError> {
+    let tag = cur.read_u8()?;
+    let record = match RecordType::try_from(tag) {
+        Ok(RecordType::End) => MTRecords::END,
+        Ok(RecordType::Line) => {
+            let options = LineOptions::from_bits(cur.read_u8()?);
+            let mut line = MTLine {
+                nudge: (0, 0),
+                line_spacing: 0,
+                null: false,
+                ruler: None,
+            };
+            if options.contains(LineOptions::NUDGE) {
+                line.nudge = read_nudge_values(cur)?
+            }
+            if options.contains(LineOptions::LSPACE) {
+                line.line_spacing = cur.read_u8()?
+            }
+            if options.contains(LineOptions::RULER) {
+                if cur.read_u8()? != RULER {
+                    return Err(super::error::Error::MalformedMTEF("RULER record expected").into());
+                }
+                line.ruler = Some(read_ruler(cur)?)
+            }
+            if options.contains(LineOptions::NULL) {
+                line.null = true
+            }
+            MTRecords::LINE(line)
+        }
+        Ok(RecordType::Char) => {
+            let options = CharOptions::from_bits(cur.read_u8()?);
+            let mut ch = MTChar { options, nudge: (0, 0), typeface: Typeface::Style(0),
+                mtcode: None, fp8: None, fp16: None };
+            if options.contains(CharOptions::NUDGE) {
+                ch.nudge = read_nudge_values(cur)?
+            }
+            ch.typeface = Typeface::from_biased(cur.read_u8()?);
+
+            if !options.contains(CharOptions::ENC_NO_MTCODE) {
+                ch.mtcode = Some(cur.read_u16::<LittleEndian>()?)
+            }
+            if options.contains(CharOptions::ENC_CHAR_8) {
+                ch.fp8 = Some(cur.read_u8()?);
+            }
+            if options.contains(CharOptions::ENC_CHAR_16) {
+                ch.fp16 = Some(cur.read_u16::<LittleEndian>()?);
+            }
+            MTRecords::CHAR(ch)
+        }
+        Ok(RecordType::Tmpl) => {
+            let mut tmpl = MTTmpl { nudge: (0, 0), selector: 0, variation: 0, options: TemplateOptions::Other(0) };
+            let options = cur.read_u8()?;
+            if MTEF_OPT_NUDGE == MTEF_OPT_NUDGE & options {
+                tmpl.nudge = read_nudge_values(cur)?
+            }
+            tmpl.selector = cur.read_u8()?;
+
+            // variation, 1 or 2 bytes
+            let byte1 = cur.read_u8()? as u16;
+            tmpl.variation = match 0x80 == byte1 & 0x80 {
+                true => {
+                    let byte2 = cur.read_u8()? as u16;
+                    (byte1 & 0x7F) | (byte2 << 8)
+                },
+                false => { byte1 }
+            };
+            tmpl.options = TemplateOptions::decode(tmpl.selector, cur.read_u8()?);
+            MTRecords::TMPL(tmpl)
+        }
+        Ok(RecordType::Pile) => {
+            let options = PileOptions::from_bits(cur.read_u8()?);
+            let mut pile = MTPile { nudge: (0, 0), halign: 0, valign: 0, ruler: None };
+            if options.contains(PileOptions::NUDGE) {
+                pile.nudge = read_nudge_values(cur)?
+            }
+            pile.halign = cur.read_u8()?;
+            pile.valign = cur.read_u8()?;
+            if options.contains(PileOptions::RULER) {
+                if cur.read_u8()? != RULER {
+                    return Err(super::error::Error::MalformedMTEF("RULER record expected").into());
+                }
+                pile.ruler = Some(read_ruler(cur)?)
+            }
+            MTRecords::PILE(pile)
+        }
+        Ok(RecordType::Embell) => {
+            let options = cur.read_u8()?;
+            let mut embell = MTEmbell { nudge: (0, 0), embell: 0 };
+            if MTEF_OPT_NUDGE == MTEF_OPT_NUDGE & options {
+                embell.nudge = read_nudge_values(cur)?
+            }
+            embell.embell = cur.read_u8()?;
+            MTRecords::EMBELL(embell)
+        }
+        // matrices are not decoded yet, only read past
+        Ok(RecordType::Matrix) => { skip_matrix(cur)?; return Ok(None) }
+        // a ruler away from its line or pile, nothing to attach it to
+        Ok(RecordType::Ruler) => { read_ruler(cur)?; return Ok(None) }
+        Ok(RecordType::FontStyleDef) => MTRecords::FONT_STYLE_DEF {
+            font_def_index: cur.read_u8()?,
+            char_style: cur.read_u8()?
+        },
+        Ok(RecordType::Size) => MTRecords::SIZE(read_size(cur)?),
+        Ok(RecordType::Full) => MTRecords::FULL,
+        Ok(RecordType::Sub) => MTRecords::SUB,
+        Ok(RecordType::Sub2) => MTRecords::SUB2,
+        Ok(RecordType::Sym) => MTRecords::SYM,
+        Ok(RecordType::SubSym) => MTRecords::SUBSYM,
+        Ok(RecordType::Color) => MTRecords::COLOR(cur.read_u8()?),
+        Ok(RecordType::ColorDef) => MTRecords::COLOR_DEF(read_color_def(cur)?),
+        Ok(RecordType::FontDef) => MTRecords::FONT_DEF {
+            enc_def_index: cur.read_u8()?,
+            name: read_null_terminated_string(cur)?,
+        },
+        Ok(RecordType::EqnPrefs) => {
+            let _options = cur.read_u8()?;
+
+            // sizes
+            let size = cur.read_u8()?;
+            let sizes = read_dimension_arrays(cur, size)?;
+
+            // spaces
+            let size = cur.read_u8()?;
+            let spaces = read_dimension_arrays(cur, size)?;
+
+            // styles, font def index and character style
+            let size = cur.read_u8()?;
+            let mut styles = vec![];
+            for _i in 0..size {
+                let c = cur.read_u8()?;
+                match c == 0 {
+                    true => { styles.push(None) },
+                    false => { styles.push(Some((c, cur.read_u8()?))) }
+                }
+            }
+            MTRecords::EQN_PREFS { sizes, spaces, styles }
+        }
+        Ok(RecordType::EncodingDef) => MTRecords::ENCODING_DEF(read_null_terminated_string(cur)?),
+        Ok(RecordType::Future) => {
+            let len = cur.read_u16::<LittleEndian>()?;
+            let mut bytes = vec![0; len as usize];
+            cur.read_exact(&mut bytes)?;
+            MTRecords::FUTURE(UnknownRecord { tag, bytes })
+        }
+        Err(_) => MTRecords::FUTURE(UnknownRecord { tag, bytes: vec![] }),
+    };
+    Ok(Some(record))
+}
+
+/// Moves the cursor past a MATRIX record whose tag was read: its payload, then the
+/// lines of its elements up to the END closing it.
+fn skip_matrix(cur: &mut Cursor<&[u8]>) -> Result<(), DecodeError> {
+    let options = cur.read_u8()?;
+    if MTEF_OPT_NUDGE == MTEF_OPT_NUDGE & options {
+        read_nudge_values(cur)?;
+    }
+    // valign, h_just, v_just
+    for _ in 0..3 {
+        cur.read_u8()?;
+    }
+    let rows = cur.read_u8()?;
+    let cols = cur.read_u8()?;
+    // row then column partition line types, 2 bits for each of the rows + 1 and
+    // cols + 1 lines, padded to a byte
+    for lines in [usize::from(rows) + 1, usize::from(cols) + 1] {
+        for _ in 0..(lines * 2).div_ceil(8) {
+            cur.read_u8()?;
+        }
+    }
+    let mut open = 1usize;
+    while open > 0 {
+        match read_record(cur)? {
+            Some(MTRecords::END) => open -= 1,
+            Some(record) if opens_scope(&record) => open += 1,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Whether `record` opens a scope closed by an END record of its own.
+pub(crate) fn opens_scope(record: &MTRecords) -> bool {
+    match record {
+        MTRecords::LINE(line) => !line.null,
+        MTRecords::CHAR(ch) => ch.options.contains(CharOptions::EMBELL),
+        MTRecords::TMPL(_) | MTRecords::PILE(_) => true,
+        _ => false,
+    }
+}
+
+/// Moves the cursor past the record at it, as `decode_record` would, without
+/// decoding its payload. Returns its tag.
+#[cfg(feature = "std")]
+pub(crate) fn skip_record(cur: &mut Cursor<&[u8]>) -> Result<u8, DecodeError> {
+    let tag = cur.read_u8()?;
+    let skip_nudge = |cur: &mut Cursor<&[u8]>, options: u8| match MTEF_OPT_NUDGE == MTEF_OPT_NUDGE & options {
+        true => read_nudge_values(cur).map(|_| ()),
+        false => Ok(()),
+    };
+    let skip_ruler = |cur: &mut Cursor<&[u8]>| -> Result<(), DecodeError> {
+        if cur.read_u8()? != RULER {
+            return Err(super::error::Error::MalformedMTEF("RULER record expected").into());
+        }
+        let n_stops = cur.read_u8()?;
+        skip_bytes(cur, 3 * u64::from(n_stops))
+    };
+    match RecordType::try_from(tag) {
+        Ok(RecordType::Line) => {
+            let options = cur.read_u8()?;
+            skip_nudge(cur, options)?;
+            let options = LineOptions::from_bits(options);
+            if options.contains(LineOptions::LSPACE) {
+                cur.read_u8()?;
+            }
+            if options.contains(LineOptions::RULER) {
+                skip_ruler(cur)?;
+            }
+        }
+        Ok(RecordType::Char) => {
+            let options = cur.read_u8()?;
+            skip_nudge(cur, options)?;
+            let options = CharOptions::from_bits(options);
+            let mut len = 1;
+            if !options.contains(CharOptions::ENC_NO_MTCODE) {
+                len += 2;
+            }
+            if options.contains(CharOptions::ENC_CHAR_8) {
+                len += 1;
+            }
+            if options.contains(CharOptions::ENC_CHAR_16) {
+                len += 2;
+            }
+            skip_bytes(cur, len)?;
+        }
+        Ok(RecordType::Tmpl) => {
+            let options = cur.read_u8()?;
+            skip_nudge(cur, options)?;
+            cur.read_u8()?;
+            if 0x80 == cur.read_u8()? & 0x80 {
+                cur.read_u8()?;
+            }
+            cur.read_u8()?;
+        }
+        Ok(RecordType::Pile) => {
+            let options = cur.read_u8()?;
+            skip_nudge(cur, options)?;
+            skip_bytes(cur, 2)?;
+            if PileOptions::from_bits(options).contains(PileOptions::RULER) {
+                skip_ruler(cur)?;
+            }
+        }
+        Ok(RecordType::Embell) => {
+            let options = cur.read_u8()?;
+            skip_nudge(cur, options)?;
+            cur.read_u8()?;
+        }
+        Ok(RecordType::Ruler) => {
+            let n_stops = cur.read_u8()?;
+            skip_bytes(cur, 3 * u64::from(n_stops))?;
+        }
+        Ok(RecordType::FontStyleDef) => skip_bytes(cur, 2)?,
+        Ok(RecordType::Size) => { read_size(cur)?; }
+        Ok(RecordType::Color) => { cur.read_u8()?; }
+        Ok(RecordType::ColorDef) => {
+            let options = ColorDefOptions::from_bits(cur.read_u8()?);
+            skip_bytes(cur, match options.contains(ColorDefOptions::CMYK) {
+                true => 8,
+                false => 6,
+            })?;
+            if options.contains(ColorDefOptions::NAME) {
+                skip_string(cur)?;
+            }
+        }
+        Ok(RecordType::FontDef) => {
+            cur.read_u8()?;
+            skip_string(cur)?;
+        }
+        Ok(RecordType::EqnPrefs) => {
+            cur.read_u8()?;
+            for _ in 0..2 {
+                let size = cur.read_u8()?;
+                skip_dimension_arrays(cur, size)?;
+            }
+            for _ in 0..cur.read_u8()? {
+                if cur.read_u8()? != 0 {
+                    cur.read_u8()?;
+                }
+            }
+        }
+        Ok(RecordType::EncodingDef) => skip_string(cur)?,
+        Ok(RecordType::Future) => {
+            let len = cur.read_u16::<LittleEndian>()?;
+            skip_bytes(cur, u64::from(len))?;
+        }
+        Ok(RecordType::Matrix) => skip_matrix(cur)?,
+        // END and the typesize records have no payload; undefined records are not
+        // decoded, only their tag is consumed
+        Ok(RecordType::End) | Ok(RecordType::Full) | Ok(RecordType::Sub) | Ok(RecordType::Sub2)
+        | Ok(RecordType::Sym) | Ok(RecordType::SubSym) | Err(_) => {}
+    }
+    Ok(tag)
+}
+
+#[cfg(feature = "std")]
+fn skip_bytes(cur: &mut Cursor<&[u8]>, n: u64) -> Result<(), DecodeError> {
+    let end = cur.position() + n;
+    if end > cur.get_ref().len() as u64 {
+        return Err(DecodeError::Incomplete);
+    }
+    cur.set_position(end);
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+fn skip_string(cur: &mut Cursor<&[u8]>) -> Result<(), DecodeError> {
+    while cur.read_u8()? != 0 {}
+    Ok(())
+}
+
+/// Skips `size` values in the nibble encoding of `read_dimension_arrays`, each ended
+/// by a 0xF nibble.
+#[cfg(feature = "std")]
+fn skip_dimension_arrays(cur: &mut Cursor<&[u8]>, size: u8) -> Result<(), DecodeError> {
+    let mut count = 0;
+    while count < size {
+        let byte = cur.read_u8()?;
+        for nibble in [byte >> 4, byte & 0x0F] {
+            if count < size && nibble == 0x0F {
+                count += 1;
+            }
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn read_null_terminated_string(cur: &mut Cursor<&[u8]>) -> Result<String, DecodeError> {
+    let mut buf = vec![];
+    cur.read_until(b'\0', &mut buf)?;
+    if buf.pop() != Some(0) {
+        return Err(DecodeError::Incomplete);
+    }
+    decode_string(&buf)
+}
+
+#[cfg(feature = "std")]
+fn decode_string(buf: &[u8]) -> Result<String, DecodeError> {
     // TODO: or UTF_8 encase of Windows English version.
-    GBK.decode(buf.as_slice(), DecoderTrap::Strict)
+    GBK.decode(buf, DecoderTrap::Strict)
+        .map_err(|_| DecodeError::Invalid(super::error::Error::MalformedMTEF("string is not GBK encoded")))
+}
+
+/// Strings of GBK's ASCII subset, the GBK tables coming with `std`.
+#[cfg(not(feature = "std"))]
+fn decode_string(buf: &[u8]) -> Result<String, DecodeError> {
+    match buf.is_ascii() {
+        true => Ok(buf.iter().map(|b| char::from(*b)).collect()),
+        false => Err(DecodeError::Invalid(super::error::Error::MalformedMTEF("non-ASCII string, GBK needs the std feature"))),
+    }
 }
 
-fn read_dimension_arrays(cur: &mut Cursor<Vec<u8>>, size: u8) -> Result<Vec<String>, super::error::Error> {
+pub(crate) fn read_dimension_arrays(cur: &mut Cursor<&[u8]>, size: u8) -> Result<Vec<String>, DecodeError> {
     let mut count = 0;
     let mut new_str = true;
     let mut tmp_str = String::new();
@@ -386,9 +1707,9 @@ fn read_dimension_arrays(cur: &mut Cursor<Vec<u8>>, size: u8) -> Result<Vec<Stri
                 0x01 => s.push_str("cm"),
                 0x02 => s.push_str("pt"),
                 0x03 => s.push_str("pc"),
-                0x04 => s.push_str("%"),
+                0x04 => s.push('%'),
                 _ => {
-                    return Err(super::error::Error::InvalidOLEFile);
+                    return Err(super::error::Error::MalformedMTEF("unknown dimension unit"));
                 }
             },
             false => match x {
@@ -409,7 +1730,7 @@ fn read_dimension_arrays(cur: &mut Cursor<Vec<u8>>, size: u8) -> Result<Vec<Stri
                     s.clear();
                 }
                 _ => {
-                    return Err(super::error::Error::InvalidOLEFile);
+                    return Err(super::error::Error::MalformedMTEF("invalid nibble in dimension value"));
                 }
             }
         }
@@ -417,17 +1738,21 @@ fn read_dimension_arrays(cur: &mut Cursor<Vec<u8>>, size: u8) -> Result<Vec<Stri
     };
 
     while count < size {
-        let ch = cur.read_u8().unwrap();
+        let ch = cur.read_u8()?;
         let hi = (ch & 0xF0)/16;
         let lo = ch & 0x0F;
-        fx(hi, &mut tmp_str, &new_str).unwrap();
+        fx(hi, &mut tmp_str, &new_str)?;
         new_str = false;
         if hi == 0x0f {
             new_str = true;
             count += 1;
         }
+        // the low nibble after the last value is padding
+        if count == size {
+            break;
+        }
 
-        fx(lo, &mut tmp_str, &new_str).unwrap();
+        fx(lo, &mut tmp_str, &new_str)?;
         new_str = false;
         if lo == 0x0f {
             new_str = true;
@@ -438,11 +1763,135 @@ fn read_dimension_arrays(cur: &mut Cursor<Vec<u8>>, size: u8) -> Result<Vec<Stri
 }
 
 
-fn read_nudge_values(cur: &mut Cursor<Vec<u8>>) -> (u16, u16){
-    let b1 = cur.read_u8().unwrap();
-    let b2 = cur.read_u8().unwrap();
-    match b1 == 128 || b2 == 128 {
-        true => (cur.read_u16::<LittleEndian>().unwrap(), cur.read_u16::<LittleEndian>().unwrap()),
-        false => (b1 as u16, b2 as u16)
+/// SIZE record payload: an explicit point size after 101, a typesize and a 16-bit
+/// delta after 100, otherwise a typesize and a delta biased by 128.
+pub(crate) fn read_size(cur: &mut Cursor<&[u8]>) -> Result<MTSize, DecodeError> {
+    Ok(match cur.read_u8()? {
+        101 => MTSize::Points(cur.read_u16::<LittleEndian>()?),
+        100 => MTSize::Delta { lsize: cur.read_u8()?, delta: cur.read_i16::<LittleEndian>()? },
+        lsize => MTSize::Delta { lsize, delta: cur.read_u8()? as i16 - 128 },
+    })
+}
+
+/// Tab stops after a RULER tag: their count, then type and offset of each.
+pub(crate) fn read_ruler(cur: &mut Cursor<&[u8]>) -> Result<MTRuler, DecodeError> {
+    let n_stops = cur.read_u8()?;
+    let mut tab_stops = vec![];
+    for _i in 0..n_stops {
+        tab_stops.push(MTTabStop {
+            kind: cur.read_u8()?,
+            offset: cur.read_i16::<LittleEndian>()?,
+        });
+    }
+    Ok(MTRuler { tab_stops })
+}
+
+fn read_color_def(cur: &mut Cursor<&[u8]>) -> Result<MTColorDef, DecodeError> {
+    let options = ColorDefOptions::from_bits(cur.read_u8()?);
+    let value = match options.contains(ColorDefOptions::CMYK) {
+        true => ColorValue::Cmyk([cur.read_u16::<LittleEndian>()?, cur.read_u16::<LittleEndian>()?,
+                                  cur.read_u16::<LittleEndian>()?, cur.read_u16::<LittleEndian>()?]),
+        false => ColorValue::Rgb([cur.read_u16::<LittleEndian>()?, cur.read_u16::<LittleEndian>()?,
+                                  cur.read_u16::<LittleEndian>()?]),
+    };
+    let name = match options.contains(ColorDefOptions::NAME) {
+        true => Some(read_null_terminated_string(cur)?),
+        false => None,
+    };
+    Ok(MTColorDef { value, spot: options.contains(ColorDefOptions::SPOT), name })
+}
+
+/// Nudge offsets, horizontal then vertical: a byte each biased by 128, or after two
+/// 128 bytes 16-bit integers for offsets a byte cannot hold.
+pub(crate) fn read_nudge_values(cur: &mut Cursor<&[u8]>) -> Result<(i16, i16), DecodeError> {
+    let b1 = cur.read_u8()?;
+    let b2 = cur.read_u8()?;
+    Ok(match b1 == 128 || b2 == 128 {
+        true => (cur.read_i16::<LittleEndian>()?, cur.read_i16::<LittleEndian>()?),
+        false => (b1 as i16 - 128, b2 as i16 - 128)
+    })
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use super::super::constants::selectors::TM_ROOT;
+    use super::super::error::Error;
+
+    /// Header and MTEF of the "Equation Native" stream of the sample object.
+    fn sample() -> (EqnOleFileHdr, Vec<u8>) {
+        let reader = ole::Reader::from_path("assets/oleObject1.bin").unwrap();
+        let native = native_data(&reader).unwrap();
+        let hdr = EqnOleFileHdr::parse_ole_hdr(&native).unwrap();
+        let mtef = native[EQN_OLE_FILE_HDR_LEN..][..hdr.size as usize].to_vec();
+        (hdr, mtef)
+    }
+
+    #[test]
+    fn builder_matches_mathtype_header() {
+        let (hdr, mtef) = sample();
+        let built = EqnOleFileHdr::builder().cf(hdr.cf()).build(&mtef).unwrap();
+        // the reserved fields MathType leaves uninitialized, they are written as 0
+        assert_eq!(built.to_bytes()[..12], hdr.to_bytes()[..12]);
+        assert_eq!(built.to_bytes()[12..], [0; 16]);
+        assert_eq!(hdr.cf(), 0xC39F);
+    }
+
+    #[test]
+    fn builder_sizes_rewritten_mtef() {
+        let (hdr, mtef) = sample();
+        let rewritten = MTEquation::from_bytes(&mtef).unwrap().to_bytes();
+        let built = EqnOleFileHdr::builder().cf(hdr.cf()).build(&rewritten).unwrap();
+        assert_eq!(built.size() as usize, rewritten.len());
+        assert_eq!(EqnOleFileHdr::parse_ole_hdr(&built.to_bytes()).unwrap().size(), built.size());
+    }
+
+    #[test]
+    fn builder_needs_a_registered_format() {
+        let (_, mtef) = sample();
+        assert!(EqnOleFileHdr::builder().build(&mtef).is_err());
+        // CF_METAFILEPICT, a predefined format
+        assert!(EqnOleFileHdr::builder().cf(3).build(&mtef).is_err());
+    }
+
+    #[test]
+    fn builder_needs_mtef() {
+        let (hdr, _) = sample();
+        let builder = EqnOleFileHdr::builder().cf(hdr.cf());
+        assert!(builder.build(b"not MTEF").is_err());
+        assert!(builder.build(&[]).is_err());
+    }
+
+    /// x under `levels` nested square roots.
+    fn nested_roots(levels: usize) -> Vec<u8> {
+        let mut buf = vec![5, 1, 0, 6, 9, b'D', b'S', b'M', b'T', b'6', 0, 0, 1, 0];
+        for _ in 0..levels {
+            buf.extend_from_slice(&[3, 0, TM_ROOT, 0, 0, 1, 0]);
+        }
+        buf.extend_from_slice(&[2, 0, 131, b'x', 0]);
+        buf.resize(buf.len() + 2 * levels + 2, 0);
+        buf
+    }
+
+    #[test]
+    fn nesting_over_max_depth() {
+        // the equation line, then a template and its slot per root
+        let deepest = (ParseOptions::DEFAULT_MAX_DEPTH - 1) / 2;
+        let eqn = MTEquation::from_bytes(&nested_roots(deepest)).unwrap();
+        assert!(eqn.to_plain_text().contains('x'));
+        #[cfg(feature = "latex")]
+        assert!(eqn.translate().unwrap().contains("x"));
+        #[cfg(feature = "mathml")]
+        assert!(eqn.to_mathml().unwrap().contains("x"));
+        for levels in [deepest + 1, 30_000] {
+            match MTEquation::from_bytes(&nested_roots(levels)) {
+                Err(Error::LimitExceeded(_)) => {}
+                other => panic!("{} roots gave {:?}", levels, other.map(|eqn| eqn.records().count())),
+            }
+        }
+        let options = ParseOptions { mode: ParseMode::Lenient, ..Default::default() };
+        assert!(MTEquation::parse_with(nested_roots(deepest + 1), &options).is_err());
+        let options = ParseOptions { max_depth: None, ..Default::default() };
+        assert!(MTEquation::parse_with(nested_roots(deepest + 1), &options).is_ok());
     }
 }
@@ -14,7 +14,7 @@
 //
 // Author: zadig <thomas chr(0x40) bailleux.me>
 
-use std;
+use alloc::string::String;
 
 /// Errors related to the process of parsing.
 #[derive(Debug)]
@@ -23,6 +23,7 @@ pub enum Error {
     BadFileSize,
 
     /// Classic std::io::Error.
+    #[cfg(feature = "std")]
     IOError(std::io::Error),
 
     /// Something is not implemented yet ?
@@ -48,35 +49,112 @@ pub enum Error {
 
     /// User query an empty entry
     EmptyEntry,
+
+    /// MTEF data MathType would not accept.
+    MalformedMTEF(&'static str),
+
+    /// Not a readable OpenDocument (zip) package.
+    InvalidPackage(&'static str),
+
+    /// Input over a limit of the `ParseOptions`, named by the string.
+    LimitExceeded(&'static str),
+
+    /// The object links to the file holding its equation, named by the string,
+    /// instead of embedding it.
+    LinkedObjectNotEmbedded(String),
 }
 
-impl std::error::Error for Error {
-    fn description(&self) -> &str {
-        match *self {
+impl Error {
+    /// Message of the error, without the name of a linked file or the cause.
+    fn message(&self) -> &str {
+        match self {
             Error::BadFileSize => "Filesize is null or too big.",
-            Error::IOError(ref e) => e.description(),
+            #[cfg(feature = "std")]
+            Error::IOError(_) => "I/O error",
             Error::NotImplementedYet => "Method not implemented yet",
             Error::InvalidOLEFile => "Invalid OLE File",
-            Error::BadSizeValue(ref e) => e,
+            Error::BadSizeValue(e) => e,
             Error::EmptyMasterSectorAllocationTable => "MSAT is empty",
             Error::NotSectorUsedBySAT => "Sector is not a sector used by the SAT.",
             Error::NodeTypeUnknown => "Unknown node type",
             Error::BadRootStorageSize => "Bad RootStorage size",
-            Error::EmptyEntry => "Empty entry"
+            Error::EmptyEntry => "Empty entry",
+            Error::MalformedMTEF(e) => e,
+            Error::InvalidPackage(e) => e,
+            Error::LimitExceeded(e) => e,
+            Error::LinkedObjectNotEmbedded(_) => "Linked object, the equation is not embedded",
         }
     }
+}
+
+impl core::error::Error for Error {
+    fn description(&self) -> &str {
+        self.message()
+    }
 
-    fn cause(&self) -> Option<&std::error::Error> {
+    fn cause(&self) -> Option<&dyn core::error::Error> {
         match *self {
+            #[cfg(feature = "std")]
             Error::IOError(ref e) => Some(e),
             _ => None
         }
     }
 }
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        use std::error::Error;
-        write!(f, "{}", self.description())
+impl Error {
+    /// What the user can do about the error, when there is something.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            Error::InvalidOLEFile => Some("not an OLE compound file; MTEquation::from_any also reads RTF, WMF, \
+                                           HTML and bare MTEF, `mtef extract-raw` takes .doc, .docx and RTF documents"),
+            Error::BadSizeValue(_) => Some("the object header is damaged; MTEquation::from_ole_repaired keeps what decodes"),
+            Error::EmptyEntry => Some("the file has no Equation Native stream, it is probably not an equation object"),
+            Error::MalformedMTEF(message) => match *message {
+                "truncated MTEF header" | "truncated MTEF" | "truncated record" | "unbalanced END record"
+                | "unbalanced END records" | "unknown record type" | "undefined record type"
+                | "string is not GBK encoded" => {
+                    Some("the equation is damaged; ParseMode::Lenient or MTEquation::from_ole_repaired keep what decodes")
+                }
+                "unknown MTEF version" => Some("MTEF before version 3 (MathType 1.x and 2.x) is not read"),
+                "MATRIX records are not supported" => {
+                    Some("matrices are not decoded yet; ParseMode::Standard reads the equation without them")
+                }
+                "MTEF older than version 5" | "only MTEF version 5 is supported" => {
+                    Some("MTEF 3 and 4 (Equation Editor 3.0, MathType 4) is read through MTEquation::from_bytes, \
+                          which upgrades it to MTEF 5")
+                }
+                _ => None,
+            },
+            Error::LimitExceeded(_) => Some("raise max_bytes, time_budget or max_depth in the ParseOptions, or leave them unset"),
+            Error::LinkedObjectNotEmbedded(_) => {
+                Some("open the document with the linked file at hand and break the link to embed the equation, \
+                      or read the linked file itself")
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The alternate form, `{:#}`, follows the message with the `hint`, as the command
+/// line tool prints it.
+impl core::fmt::Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            #[cfg(feature = "std")]
+            Self::IOError(e) => write!(f, "{}", e)?,
+            Self::LinkedObjectNotEmbedded(source) => write!(f, "{}: {}", self.message(), source)?,
+            _ => write!(f, "{}", self.message())?,
+        }
+        match self.hint() {
+            Some(hint) if f.alternate() => write!(f, "\nhint: {}", hint),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::IOError(e)
     }
 }
@@ -0,0 +1,112 @@
+//! MTEF exactly as stored, without parsing it: the payload of every "Equation Native"
+//! stream of a container, its EQNOLEFILEHDR removed.
+//!
+//! Containers are told apart by their first bytes:
+//!
+//! | container | equations |
+//! | --------- | --------- |
+//! |OLE compound file (.doc, object files)|"Equation Native" streams, in directory order|
+//! |zip package (.docx, .odt)|OLE objects stored as package entries, needs the `odt` feature|
+//! |RTF|`\objdata` groups, hex-encoded OLE1 objects wrapping an OLE compound file|
+//!
+//! Compressed MTEF is kept compressed, older MTEF versions are not upgraded.
+
+use std::io::Cursor;
+use std::path::Path;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use super::eqn::{read_entry, EqnOleFileHdr, CFB_MAGIC, EQUATION_NATIVE};
+use super::error::Error;
+
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+pub(crate) const RTF_MAGIC: &[u8] = b"{\\rtf";
+const OBJDATA: &str = "\\objdata";
+
+/// MTEF payloads of the container at `path`, in document order.
+pub fn raw_mtef<P: AsRef<Path>>(path: P) -> Result<Vec<Vec<u8>>, Error> {
+    let container = std::fs::read(path)?;
+    if container.starts_with(&CFB_MAGIC) {
+        compound_file(&container)
+    } else if container.starts_with(ZIP_MAGIC) {
+        package(&container)
+    } else if container.starts_with(RTF_MAGIC) {
+        rtf(&String::from_utf8_lossy(&container))
+    } else {
+        Err(Error::InvalidOLEFile)
+    }
+}
+
+fn compound_file(buf: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let reader = ole::Reader::new(buf).map_err(|_| Error::InvalidOLEFile)?;
+    let mut payloads = vec![];
+    for entry in reader.iterate().filter(|entry| entry.name() == EQUATION_NATIVE) {
+        payloads.push(payload(&read_entry(&reader, entry)?)?);
+    }
+    Ok(payloads)
+}
+
+/// The MTEF following the EQNOLEFILEHDR of an "Equation Native" stream.
+fn payload(stream: &[u8]) -> Result<Vec<u8>, Error> {
+    let hdr = EqnOleFileHdr::parse_ole_hdr(stream)?;
+    let start = hdr.cb_hdr as usize;
+    stream.get(start..start + hdr.size as usize)
+        .map(<[u8]>::to_vec)
+        .ok_or(Error::BadSizeValue("EQNOLEFILEHDR size"))
+}
+
+#[cfg(feature = "odt")]
+fn package(buf: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let mut payloads = vec![];
+    for entry in super::odt::entries(buf)? {
+        let data = entry.data()?;
+        if data.starts_with(&CFB_MAGIC) {
+            payloads.extend(compound_file(&data)?);
+        }
+    }
+    Ok(payloads)
+}
+
+#[cfg(not(feature = "odt"))]
+fn package(_: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    Err(Error::InvalidPackage("zip packages need the odt feature"))
+}
+
+pub(crate) fn rtf(text: &str) -> Result<Vec<Vec<u8>>, Error> {
+    let mut payloads = vec![];
+    let mut pos = 0;
+    while let Some(start) = text[pos..].find(OBJDATA).map(|i| pos + i + OBJDATA.len()) {
+        let end = text[start..].find('}').map_or(text.len(), |i| start + i);
+        pos = end;
+        let object = hex(&text[start..end]).ok_or(Error::InvalidPackage("invalid \\objdata hex"))?;
+        if let Some(native) = ole1_native_data(&object) {
+            if native.starts_with(&CFB_MAGIC) {
+                payloads.extend(compound_file(native)?);
+            }
+        }
+    }
+    Ok(payloads)
+}
+
+/// Bytes of hex digits, whitespace between them ignored.
+pub(crate) fn hex(text: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = text.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    digits.chunks(2)
+        .map(|pair| std::str::from_utf8(pair).ok().and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
+
+/// Native data of an OLE1 embedded object: version and format, then the class,
+/// topic and item names, each prefixed by its length, then the data and its size.
+pub(crate) fn ole1_native_data(object: &[u8]) -> Option<&[u8]> {
+    let mut cur = Cursor::new(object);
+    cur.read_u32::<LittleEndian>().ok()?;
+    cur.read_u32::<LittleEndian>().ok()?;
+    for _ in 0..3 {
+        let len = cur.read_u32::<LittleEndian>().ok()?;
+        cur.set_position(cur.position() + u64::from(len));
+    }
+    let size = cur.read_u32::<LittleEndian>().ok()? as usize;
+    let start = cur.position() as usize;
+    object.get(start..start.checked_add(size)?)
+}
@@ -0,0 +1,164 @@
+//! Which program wrote an equation, from the platform, product and version bytes of
+//! the MTEF header and its application key.
+//!
+//! | field | values |
+//! | ----- | ------ |
+//! |MTEF version |1 to 5; equations read from MTEF 3 and 4 are upgraded and report 5|
+//! |platform |0 for Macintosh, 1 for Windows|
+//! |product |0 for MathType, 1 for Equation Editor|
+//! |product version |major version of the product|
+//! |product subversion |minor version of the product|
+//! |application key |"DSMT4", "DSMT6"... for MathType, may be empty for other writers|
+
+use std::fmt;
+
+use super::eqn::MTEquation;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MtefVersion {
+    V1,
+    V2,
+    V3,
+    V4,
+    V5,
+    Other(u8),
+}
+
+impl From<u8> for MtefVersion {
+    fn from(b: u8) -> MtefVersion {
+        match b {
+            1 => MtefVersion::V1,
+            2 => MtefVersion::V2,
+            3 => MtefVersion::V3,
+            4 => MtefVersion::V4,
+            5 => MtefVersion::V5,
+            b => MtefVersion::Other(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Mac,
+    Windows,
+    Other(u8),
+}
+
+impl From<u8> for Platform {
+    fn from(b: u8) -> Platform {
+        match b {
+            0 => Platform::Mac,
+            1 => Platform::Windows,
+            b => Platform::Other(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Product {
+    MathType,
+    /// Equation Editor 3.x, and Microsoft Equation 3.0 shipped with Office
+    EquationEditor,
+    Other(u8),
+}
+
+impl From<u8> for Product {
+    fn from(b: u8) -> Product {
+        match b {
+            0 => Product::MathType,
+            1 => Product::EquationEditor,
+            b => Product::Other(b),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Platform::Mac => f.write_str("Mac"),
+            Platform::Windows => f.write_str("Windows"),
+            Platform::Other(b) => write!(f, "platform {}", b),
+        }
+    }
+}
+
+impl fmt::Display for Product {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Product::MathType => f.write_str("MathType"),
+            Product::EquationEditor => f.write_str("Equation Editor"),
+            Product::Other(b) => write!(f, "product {}", b),
+        }
+    }
+}
+
+/// Best guess at the program that wrote an equation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Generator {
+    EquationEditor { version: u8, subversion: u8 },
+    /// `version` comes from the application key when the header leaves it at 0.
+    MathType { version: u8, subversion: u8 },
+    /// Third-party writers filling the header with something MathType would not write.
+    Unknown,
+}
+
+/// "MathType 6.9", "Equation Editor 3.0", or "unknown generator".
+impl fmt::Display for Generator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Generator::EquationEditor { version, subversion } => write!(f, "Equation Editor {}.{}", version, subversion),
+            Generator::MathType { version, subversion } => write!(f, "MathType {}.{}", version, subversion),
+            Generator::Unknown => f.write_str("unknown generator"),
+        }
+    }
+}
+
+/// Application keys MathType writes, followed by a version digit.
+const DSMT: &str = "DSMT";
+
+impl MTEquation {
+    pub fn mtef_version(&self) -> MtefVersion {
+        MtefVersion::from(self.m_mtef_ver)
+    }
+
+    pub fn platform(&self) -> Platform {
+        Platform::from(self.m_platform)
+    }
+
+    pub fn product(&self) -> Product {
+        Product::from(self.m_product)
+    }
+
+    /// Version and subversion bytes of the header, as written; see `generator` for
+    /// the version of the program.
+    pub fn product_version(&self) -> (u8, u8) {
+        (self.m_version, self.m_version_sub)
+    }
+
+    /// Application key, "DSMT4" and up for MathType.
+    pub fn application(&self) -> &str {
+        &self.m_application
+    }
+
+    /// Whether the equation was inserted inline with text rather than displayed.
+    pub fn is_inline(&self) -> bool {
+        self.m_inline != 0
+    }
+
+    pub fn generator(&self) -> Generator {
+        let (version, subversion) = (self.m_version, self.m_version_sub);
+        match self.product() {
+            Product::EquationEditor => Generator::EquationEditor { version, subversion },
+            Product::MathType => {
+                let key_version = self.m_application.strip_prefix(DSMT)
+                    .and_then(|v| v.parse::<u8>().ok());
+                match (version, key_version) {
+                    (0, Some(version)) => Generator::MathType { version, subversion },
+                    (0, None) => Generator::Unknown,
+                    (version, _) => Generator::MathType { version, subversion },
+                }
+            }
+            Product::Other(_) => Generator::Unknown,
+        }
+    }
+}
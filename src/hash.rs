@@ -0,0 +1,84 @@
+//! Content hashes of equations, enabled by the `hash` feature, for rendering services
+//! caching their output across documents.
+//!
+//! The hash covers the tree of lines, templates, piles and characters: characters by
+//! code, font name and style, templates by selector and variation. Header fields,
+//! the order and numbering of definition records, nudges, size records and markers
+//! are left out, so equations MathType saved differently but showing the same hash
+//! the same.
+
+use xxhash_rust::xxh3::Xxh3;
+
+use super::ast::{Line, Node};
+use super::eqn::{MTChar, MTEquation};
+
+/// Tags separating the parts of the hashed tree.
+const LINE: u8 = 1;
+const CHAR: u8 = 2;
+const TMPL: u8 = 3;
+const PILE: u8 = 4;
+const EMBELL: u8 = 6;
+const END: u8 = 0;
+
+impl MTEquation {
+    /// 128-bit XXH3 hash of the equation's content, stable across releases for
+    /// equations decoding to the same tree.
+    pub fn content_hash(&self) -> u128 {
+        let mut hasher = Xxh3::new();
+        for line in self.lines() {
+            hash_line(&mut hasher, self, &line.line);
+        }
+        hasher.digest128()
+    }
+}
+
+fn hash_line(hasher: &mut Xxh3, eqn: &MTEquation, line: &Line) {
+    hasher.update(&[LINE]);
+    for node in &line.nodes {
+        match node {
+            Node::Char(c) => {
+                hash_char(hasher, eqn, c.ch);
+                for embell in &c.embells {
+                    hasher.update(&[EMBELL, embell.embell]);
+                }
+                hasher.update(&[END]);
+            }
+            Node::Tmpl(t) => {
+                hasher.update(&[TMPL, t.tmpl.selector]);
+                hasher.update(&t.tmpl.variation.to_le_bytes());
+                for ch in &t.chars {
+                    hash_char(hasher, eqn, ch);
+                }
+                for slot in &t.slots {
+                    hash_line(hasher, eqn, slot);
+                }
+                hasher.update(&[END]);
+            }
+            Node::Pile(p) => {
+                hasher.update(&[PILE, p.pile.halign, p.pile.valign]);
+                for line in &p.lines {
+                    hash_line(hasher, eqn, line);
+                }
+                hasher.update(&[END]);
+            }
+            // placeholders are not in the records
+            Node::Marker(_) | Node::Placeholder => {}
+        }
+    }
+    hasher.update(&[END]);
+}
+
+fn hash_char(hasher: &mut Xxh3, eqn: &MTEquation, ch: &MTChar) {
+    let code = ch.mtcode.or(ch.fp16).or_else(|| ch.fp8.map(u16::from)).unwrap_or(0);
+    hasher.update(&[CHAR]);
+    hasher.update(&code.to_le_bytes());
+    // typefaces without a definition are the predefined ones, numbered the same everywhere
+    match eqn.font_name(ch.typeface) {
+        Some(name) => {
+            hasher.update(&[1]);
+            hasher.update(name.as_bytes());
+            hasher.update(&[0, eqn.char_style(ch.typeface).unwrap_or(0)]);
+        }
+        None => hasher.update(&[0, ch.typeface.biased()]),
+    }
+}
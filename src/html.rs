@@ -0,0 +1,124 @@
+//! Equations in HTML pages exported by MathType, which keep the MTEF of each equation
+//! image in a comment next to its `<img>` tag:
+//!
+//! ```text
+//! <img src="Image1.gif"><!-- MathType@MTEF@5@5@+=feaafiart1ev1aaat...@3785@ -->
+//! ```
+//!
+//! The fields are the MTEF version, the product version, the MTEF, then a checksum.
+//! MTEF is written four characters per three bytes, least significant bits first,
+//! each character giving six bits: `a`-`z` for 0 to 25, `A`-`Z` for 26 to 51 and
+//! `0`-`9` for 52 to 61. The first two characters of the field name the characters
+//! standing for 62 and 63, usually `+` and `=`.
+
+use super::eqn::MTEquation;
+use super::error::Error;
+
+const COMMENT_START: &str = "<!--";
+const COMMENT_END: &str = "-->";
+const MTEF_KEY: &str = "MathType@MTEF@";
+
+/// Equations of the MathType comments of `html`, in document order, each with the
+/// `src` of the image tag closest to its comment.
+pub fn equations(html: &str) -> Result<Vec<(Option<String>, MTEquation)>, Error> {
+    let comments = mtef_comments(html);
+    let mut equations = vec![];
+    for (i, &(start, end, data)) in comments.iter().enumerate() {
+        // images between the neighbouring comments are candidates, the nearest wins
+        let before = match i {
+            0 => 0,
+            _ => comments[i - 1].1,
+        };
+        let after = comments.get(i + 1).map_or(html.len(), |c| c.0);
+        let src = nearest_img_src(html, before, start, end, after);
+        let mtef = decode_text(data).ok_or(Error::MalformedMTEF("invalid MathType text encoding"))?;
+        equations.push((src, super::upgrade::upgrade(&mtef)?));
+    }
+    Ok(equations)
+}
+
+/// Byte range of each MathType comment with its MTEF data, still encoded.
+fn mtef_comments(html: &str) -> Vec<(usize, usize, &str)> {
+    let mut comments = vec![];
+    let mut pos = 0;
+    while let Some(start) = html[pos..].find(COMMENT_START).map(|i| pos + i) {
+        let body_start = start + COMMENT_START.len();
+        let end = match html[body_start..].find(COMMENT_END) {
+            Some(i) => body_start + i + COMMENT_END.len(),
+            None => break,
+        };
+        let body = &html[body_start..end - COMMENT_END.len()];
+        if let Some(data) = body.trim().strip_prefix(MTEF_KEY).and_then(mtef_field) {
+            comments.push((start, end, data));
+        }
+        pos = end;
+    }
+    comments
+}
+
+/// The data field following the two version fields.
+fn mtef_field(fields: &str) -> Option<&str> {
+    fields.split('@').nth(2)
+}
+
+fn nearest_img_src(html: &str, before: usize, start: usize, end: usize, after: usize) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let preceding = lower[before..start].rfind("<img").map(|i| (start - (before + i), before + i));
+    let following = lower[end..after].find("<img").map(|i| (i, end + i));
+    let (_, img) = match (preceding, following) {
+        (Some(p), Some(f)) => p.min(f),
+        (p, f) => p.or(f)?,
+    };
+    let tag = &html[img..html[img..].find('>').map_or(html.len(), |i| img + i)];
+    attribute(tag, "src")
+}
+
+/// Value of attribute `name` in `tag`, quoted or not.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut pos = 0;
+    while let Some(i) = lower[pos..].find(name).map(|i| pos + i) {
+        pos = i + name.len();
+        let preceded = lower[..i].ends_with(|c: char| c.is_ascii_whitespace());
+        let rest = lower[pos..].trim_start();
+        if !preceded || !rest.starts_with('=') {
+            continue;
+        }
+        let value_start = tag.len() - rest[1..].trim_start().len();
+        let value = &tag[value_start..];
+        return Some(match value.chars().next() {
+            Some(quote @ '"') | Some(quote @ '\'') => value[1..].split(quote).next().unwrap_or(""),
+            _ => value.split(|c: char| c.is_ascii_whitespace()).next().unwrap_or(""),
+        }.to_string());
+    }
+    None
+}
+
+/// Bytes of MTEF in MathType's text encoding, the two characters for 62 and 63 first.
+fn decode_text(field: &str) -> Option<Vec<u8>> {
+    let mut chars = field.bytes();
+    let (c62, c63) = (chars.next()?, chars.next()?);
+    let mut bytes = Vec::with_capacity(field.len() * 3 / 4);
+    let mut bits = 0u32;
+    let mut count = 0;
+    for c in chars {
+        let value = match c {
+            b'a'..=b'z' => c - b'a',
+            b'A'..=b'Z' => c - b'A' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            c if c == c62 => 62,
+            c if c == c63 => 63,
+            // exports may wrap long comments
+            b' ' | b'\t' | b'\r' | b'\n' => continue,
+            _ => return None,
+        };
+        bits |= u32::from(value) << count;
+        count += 6;
+        if count >= 8 {
+            bytes.push(bits as u8);
+            bits >>= 8;
+            count -= 8;
+        }
+    }
+    Some(bytes)
+}
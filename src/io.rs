@@ -0,0 +1,98 @@
+//! What the record decoder reads MTEF with. With the `std` feature these are the
+//! `std::io` and `byteorder` items themselves; without it, a cursor over bytes in
+//! memory and the few reading methods the decoder calls, under the same names.
+
+#[cfg(feature = "std")]
+pub(crate) use byteorder::ReadBytesExt;
+#[cfg(feature = "std")]
+pub(crate) use std::io::{BufRead, Cursor, Read};
+
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use byteorder::ByteOrder;
+
+#[cfg(not(feature = "std"))]
+use super::eqn::DecodeError;
+
+/// Bytes and a position in them, as `std::io::Cursor`. Reads past the end fail with
+/// `DecodeError::Incomplete`.
+#[cfg(not(feature = "std"))]
+#[derive(Debug)]
+pub(crate) struct Cursor<T> {
+    inner: T,
+    pos: u64,
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: AsRef<[u8]>> Cursor<T> {
+    pub(crate) fn new(inner: T) -> Cursor<T> {
+        Cursor { inner, pos: 0 }
+    }
+
+    pub(crate) fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Bytes from the position on, none once it is past the end.
+    fn remaining(&self) -> &[u8] {
+        let bytes = self.inner.as_ref();
+        &bytes[(self.pos as usize).min(bytes.len())..]
+    }
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) trait Read {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError>;
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: AsRef<[u8]>> Read for Cursor<T> {
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), DecodeError> {
+        let bytes = self.remaining().get(..buf.len()).ok_or(DecodeError::Incomplete)?;
+        buf.copy_from_slice(bytes);
+        self.pos += buf.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) trait BufRead {
+    /// Bytes up to `byte` included, or to the end when it does not come.
+    fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> Result<usize, DecodeError>;
+}
+
+#[cfg(not(feature = "std"))]
+impl<T: AsRef<[u8]>> BufRead for Cursor<T> {
+    fn read_until(&mut self, byte: u8, buf: &mut Vec<u8>) -> Result<usize, DecodeError> {
+        let remaining = self.remaining();
+        let len = remaining.iter().position(|b| *b == byte).map_or(remaining.len(), |i| i + 1);
+        buf.extend_from_slice(&remaining[..len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+pub(crate) trait ReadBytesExt: Read {
+    fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        let mut buf = [0; 1];
+        self.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn read_u16<B: ByteOrder>(&mut self) -> Result<u16, DecodeError> {
+        let mut buf = [0; 2];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_u16(&buf))
+    }
+
+    fn read_i16<B: ByteOrder>(&mut self) -> Result<i16, DecodeError> {
+        let mut buf = [0; 2];
+        self.read_exact(&mut buf)?;
+        Ok(B::read_i16(&buf))
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<R: Read> ReadBytesExt for R {}
@@ -0,0 +1,173 @@
+//! JSON dump of the parsed records, for viewers and other tools outside Rust.
+
+use std::fmt::Write;
+
+use super::eqn::{ColorValue, MTEquation, MTRecords, MTRuler, MTSize};
+
+/// Version of the format written by `MTEquation::to_json`.
+pub const JSON_SCHEMA_VERSION: u32 = 1;
+
+impl MTEquation {
+    /// The equation as JSON, for viewers and other tools outside Rust.
+    ///
+    /// The format is documented here and versioned on its own. Within a version, fields
+    /// are never removed, renamed or given another meaning; new fields and record types
+    /// may appear, so consumers should ignore what they do not know. Incompatible changes
+    /// bump `JSON_SCHEMA_VERSION`.
+    ///
+    /// Version 1:
+    ///
+    /// ```text
+    /// {
+    ///   "schema": "mtef-rs/equation",
+    ///   "version": 1,
+    ///   "header": {
+    ///     "mtef_version": 5, "platform": 1, "product": 0,
+    ///     "product_version": 6, "product_subversion": 9,
+    ///     "application": "DSMT6", "inline": false
+    ///   },
+    ///   "records": [ record, ... ]
+    /// }
+    /// ```
+    ///
+    /// Records keep stream order; END records close lines, templates, piles and
+    /// embellishment lists as in MTEF. Every record has a `"type"`, the MTEF record name:
+    ///
+    /// | type | fields |
+    /// | ---- | ------ |
+    /// |END, FULL, SUB, SUB2, SYM, SUBSYM | none|
+    /// |LINE  |`nudge`, `line_spacing`, `null`, `ruler`|
+    /// |CHAR  |`nudge`, `options`, `typeface` (biased by 128), `mtcode`, `fp8`, `fp16` (null when absent)|
+    /// |TMPL  |`nudge`, `selector`, `variation`, `options`|
+    /// |PILE  |`nudge`, `halign`, `valign`, `ruler`|
+    /// |EMBELL |`nudge`, `embell`|
+    /// |ENCODING_DEF |`name`|
+    /// |FONT_DEF |`encoding` (1-based ENCODING_DEF index), `name`|
+    /// |FONT_STYLE_DEF |`font` (1-based FONT_DEF index), `style`|
+    /// |COLOR |`color` (COLOR_DEF index)|
+    /// |COLOR_DEF |`model` (`"rgb"` or `"cmyk"`), `values` (0 to 65535), `spot`, `name` (null when absent)|
+    /// |SIZE  |`points` (32nds of a point), or `lsize` and `delta` (32nds of a point)|
+    /// |FUTURE |`tag`, `bytes` (payload in hexadecimal)|
+    /// |EQN_PREFS |`sizes`, `spaces` (dimension strings such as `"pt12"`), `styles` (`[font, style]` or null)|
+    ///
+    /// `nudge` is `[dx, dy]`, how far the object is moved from where it would sit: signed
+    /// offsets in pixels (1/72 inch), right and down positive, `[0, 0]` for objects not
+    /// nudged. `ruler` is null or `{"tab_stops": [{"type": 0, "offset": 96}, ...]}` with
+    /// offsets in 32nds of a point.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        write!(out, "{{\"schema\": \"mtef-rs/equation\", \"version\": {}, \"header\": {{", JSON_SCHEMA_VERSION).unwrap();
+        write!(out, "\"mtef_version\": {}, \"platform\": {}, \"product\": {}, \"product_version\": {}, \"product_subversion\": {}, ",
+               self.m_mtef_ver, self.m_platform, self.m_product, self.m_version, self.m_version_sub).unwrap();
+        write!(out, "\"application\": {}, \"inline\": {}}}, \"records\": [",
+               json_string(&self.m_application), self.m_inline != 0).unwrap();
+        for (i, record) in self.records.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            write!(out, "{{\"type\": \"{}\"", record.record_type()).unwrap();
+            fields(&mut out, record);
+            out.push('}');
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+fn fields(out: &mut String, record: &MTRecords) {
+    match record {
+        MTRecords::LINE(line) => {
+            write!(out, ", \"nudge\": {}, \"line_spacing\": {}, \"null\": {}, \"ruler\": {}",
+                   nudge(line.nudge), line.line_spacing, line.null, ruler(line.ruler.as_ref())).unwrap();
+        }
+        MTRecords::CHAR(ch) => {
+            write!(out, ", \"nudge\": {}, \"options\": {}, \"typeface\": {}, \"mtcode\": {}, \"fp8\": {}, \"fp16\": {}",
+                   nudge(ch.nudge), ch.options.bits(), ch.typeface.biased(), number(ch.mtcode), number(ch.fp8), number(ch.fp16)).unwrap();
+        }
+        MTRecords::TMPL(tmpl) => {
+            write!(out, ", \"nudge\": {}, \"selector\": {}, \"variation\": {}, \"options\": {}",
+                   nudge(tmpl.nudge), tmpl.selector, tmpl.variation, tmpl.options.bits()).unwrap();
+        }
+        MTRecords::PILE(pile) => {
+            write!(out, ", \"nudge\": {}, \"halign\": {}, \"valign\": {}, \"ruler\": {}",
+                   nudge(pile.nudge), pile.halign, pile.valign, ruler(pile.ruler.as_ref())).unwrap();
+        }
+        MTRecords::EMBELL(embell) => {
+            write!(out, ", \"nudge\": {}, \"embell\": {}", nudge(embell.nudge), embell.embell).unwrap();
+        }
+        MTRecords::ENCODING_DEF(name) => write!(out, ", \"name\": {}", json_string(name)).unwrap(),
+        MTRecords::FONT_DEF { enc_def_index, name } => {
+            write!(out, ", \"encoding\": {}, \"name\": {}", enc_def_index, json_string(name)).unwrap();
+        }
+        MTRecords::FONT_STYLE_DEF { font_def_index, char_style } => {
+            write!(out, ", \"font\": {}, \"style\": {}", font_def_index, char_style).unwrap();
+        }
+        MTRecords::EQN_PREFS { sizes, spaces, styles } => {
+            let strings = |dims: &[String]| dims.iter().map(|d| json_string(d)).collect::<Vec<_>>().join(", ");
+            let styles: Vec<String> = styles.iter()
+                .map(|style| match style {
+                    Some((font, style)) => format!("[{}, {}]", font, style),
+                    None => "null".to_string(),
+                })
+                .collect();
+            write!(out, ", \"sizes\": [{}], \"spaces\": [{}], \"styles\": [{}]",
+                   strings(sizes), strings(spaces), styles.join(", ")).unwrap();
+        }
+        MTRecords::SIZE(MTSize::Points(points)) => write!(out, ", \"points\": {}", points).unwrap(),
+        MTRecords::SIZE(MTSize::Delta { lsize, delta }) => {
+            write!(out, ", \"lsize\": {}, \"delta\": {}", lsize, delta).unwrap();
+        }
+        MTRecords::COLOR(color_def_index) => write!(out, ", \"color\": {}", color_def_index).unwrap(),
+        MTRecords::COLOR_DEF(def) => {
+            let (model, values): (&str, &[u16]) = match &def.value {
+                ColorValue::Rgb(rgb) => ("rgb", rgb),
+                ColorValue::Cmyk(cmyk) => ("cmyk", cmyk),
+            };
+            let values: Vec<String> = values.iter().map(u16::to_string).collect();
+            let name = def.name.as_deref().map_or("null".to_string(), json_string);
+            write!(out, ", \"model\": \"{}\", \"values\": [{}], \"spot\": {}, \"name\": {}",
+                   model, values.join(", "), def.spot, name).unwrap();
+        }
+        MTRecords::FUTURE(record) => {
+            let hex: String = record.bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            write!(out, ", \"tag\": {}, \"bytes\": \"{}\"", record.tag, hex).unwrap();
+        }
+        MTRecords::END | MTRecords::FULL | MTRecords::SUB | MTRecords::SUB2 | MTRecords::SYM
+        | MTRecords::SUBSYM => {}
+    }
+}
+
+fn nudge((dx, dy): (i16, i16)) -> String {
+    format!("[{}, {}]", dx, dy)
+}
+
+fn number<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "null".to_string(), |v| v.to_string())
+}
+
+fn ruler(ruler: Option<&MTRuler>) -> String {
+    match ruler {
+        Some(ruler) => {
+            let stops: Vec<String> = ruler.tab_stops.iter()
+                .map(|stop| format!("{{\"type\": {}, \"offset\": {}}}", stop.kind, stop.offset))
+                .collect();
+            format!("{{\"tab_stops\": [{}]}}", stops.join(", "))
+        }
+        None => "null".to_string(),
+    }
+}
+
+pub(crate) fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,1711 @@
+//! LaTeX output backend, enabled by the `latex` feature.
+
+use super::ast::{Arrow, ArrowKind, BigOp, BigOpKind, Boxed, Char, Fence, FenceKind, HBrace, Line, Node, Pile, Scripts, Strike, Tmpl};
+use super::bounds;
+use super::constants::embellishments::*;
+use super::constants::pile_align::*;
+use super::constants::selectors::*;
+use super::constants::typeface::*;
+use super::constants::variations::*;
+use super::eqn::{CharOptions, EquationLine, MTChar, MTEquation, MTRecords, DEFAULT_LINE_SPACING};
+use super::error::Error;
+use super::charmap::{self, CharMapper, MappedChar};
+use super::warning::{Warning, WarningFilter};
+use std::fmt;
+use std::sync::Arc;
+
+/// Options of the LaTeX backend.
+#[derive(Clone)]
+pub struct TranslateOptions {
+    /// Drop cancellation strokes (\cancel, \bcancel, \xcancel, \hcancel) and keep the
+    /// content, for output that must compile without the cancel and hcancel packages.
+    pub strip_cancel: bool,
+    /// Write a number followed by a unit text run as `\SI{number}{unit}` (siunitx),
+    /// with °, °C, µ and Ω as `\degree`, `\degreeCelsius`, `\micro` and `\ohm`.
+    pub siunitx: bool,
+    /// Which of the two forms to write for Greek letters with a variant
+    /// (\epsilon/\varepsilon, \theta/\vartheta, \kappa/\varkappa, \rho/\varrho, \phi/\varphi).
+    pub greek_variants: GreekVariants,
+    /// Write lines that read as chemical equations (element symbols, reaction arrows,
+    /// subscript counts) as `\ce{...}` (mhchem).
+    pub mhchem: bool,
+    /// Mapping consulted before `DefaultCharMapper`, for private font encodings.
+    pub char_mapper: Option<Arc<dyn CharMapper + Send + Sync>>,
+    /// Fences as `\left(..\right)`, sized by TeX (the default). When off, fences get
+    /// a fixed size picked from the estimated height of their content, `(..)` up to
+    /// `\Biggl(..\Biggr)`, as some journals require.
+    pub auto_delimiters: bool,
+    /// Alphabet for characters in the vector style, bold (`\mathbf`) by default.
+    pub vector_alphabet: MathAlphabet,
+    /// Alphabets for the two user-defined styles, which templates use for different
+    /// things; characters are written as they are by default.
+    pub user1_alphabet: MathAlphabet,
+    pub user2_alphabet: MathAlphabet,
+    /// How a pile opened by a left brace alone (cases) is written.
+    pub brace_pile_style: BracePileStyle,
+    /// Whether commas between digits are decimal separators, written `3{,}14` so
+    /// that TeX does not space them as punctuation.
+    pub decimal_comma: DecimalComma,
+    /// How Chinese, Japanese and Korean characters are written.
+    pub cjk_policy: CjkPolicy,
+    /// Math mode delimiters around the output of a whole equation; none by default,
+    /// for callers placing it in an environment of their own.
+    pub delimiters: MathDelimiters,
+    /// How characters without a LaTeX command, outside ASCII, are written.
+    pub unknown_chars: UnknownChars,
+    /// Warnings `MTEquation::translation_warnings` leaves out.
+    pub warning_filter: WarningFilter,
+    /// What the slots added by `ParseOptions::fill_missing_slots` are written as,
+    /// `\square` (amssymb) by default.
+    pub placeholder: String,
+}
+
+impl Default for TranslateOptions {
+    fn default() -> TranslateOptions {
+        TranslateOptions {
+            strip_cancel: false,
+            siunitx: false,
+            greek_variants: GreekVariants::default(),
+            mhchem: false,
+            char_mapper: None,
+            auto_delimiters: true,
+            vector_alphabet: MathAlphabet::Bold,
+            user1_alphabet: MathAlphabet::Plain,
+            user2_alphabet: MathAlphabet::Plain,
+            brace_pile_style: BracePileStyle::default(),
+            decimal_comma: DecimalComma::default(),
+            cjk_policy: CjkPolicy::default(),
+            delimiters: MathDelimiters::default(),
+            unknown_chars: UnknownChars::default(),
+            warning_filter: WarningFilter::default(),
+            placeholder: "\\square".to_string(),
+        }
+    }
+}
+
+impl fmt::Debug for TranslateOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TranslateOptions")
+            .field("strip_cancel", &self.strip_cancel)
+            .field("siunitx", &self.siunitx)
+            .field("greek_variants", &self.greek_variants)
+            .field("mhchem", &self.mhchem)
+            .field("char_mapper", &self.char_mapper.is_some())
+            .field("auto_delimiters", &self.auto_delimiters)
+            .field("vector_alphabet", &self.vector_alphabet)
+            .field("user1_alphabet", &self.user1_alphabet)
+            .field("user2_alphabet", &self.user2_alphabet)
+            .field("brace_pile_style", &self.brace_pile_style)
+            .field("decimal_comma", &self.decimal_comma)
+            .field("cjk_policy", &self.cjk_policy)
+            .field("delimiters", &self.delimiters)
+            .field("unknown_chars", &self.unknown_chars)
+            .field("warning_filter", &self.warning_filter)
+            .field("placeholder", &self.placeholder)
+            .finish()
+    }
+}
+
+/// Selection between the plain and the variant form of a Greek letter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GreekVariants {
+    /// Keep the form the equation was written with.
+    #[default]
+    AsWritten,
+    /// Always the plain form, \phi rather than \varphi.
+    Plain,
+    /// Always the variant form, \varphi rather than \phi.
+    Variant,
+}
+
+/// Rendering of a pile behind a left brace without a right one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BracePileStyle {
+    /// `\begin{cases}..\end{cases}` (amsmath).
+    #[default]
+    Cases,
+    /// The pile as any other, an array behind a brace: stretched by TeX,
+    /// `\left\{\begin{array}{l}..\end{array}\right.`, or with `auto_delimiters` off
+    /// sized after the pile, e.g. `\Bigl\{\begin{array}{l}..\end{array}`.
+    Array,
+}
+
+/// Reading of commas between digits, as in European documents' `3,14`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecimalComma {
+    /// Commas are punctuation, `3,14` is a list of two numbers.
+    #[default]
+    Off,
+    /// Commas between digits are decimal separators.
+    On,
+    /// Decimal separators when the equation reads so: it has commas between digits,
+    /// none followed by exactly three digits as a thousands separator would be, and
+    /// no decimal points.
+    Auto,
+}
+
+/// Writing of CJK characters, common in equations of Chinese documents.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum CjkPolicy {
+    /// As they are, for XeLaTeX or LuaLaTeX with a CJK font (xeCJK, luatexja).
+    #[default]
+    Unicode,
+    /// Runs of them in text, `\text{\begin{CJK}{UTF8}{gbsn}..\end{CJK}}` (CJKutf8) for
+    /// pdfLaTeX, with the font family given, e.g. "gbsn" or "min".
+    Environment(String),
+}
+
+/// Math mode delimiters written around an equation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MathDelimiters {
+    #[default]
+    None,
+    /// `\(..\)`
+    Inline,
+    /// `\[..\]`
+    Display,
+    /// Inline or display as the equation was inserted in its document.
+    Auto,
+}
+
+impl MathDelimiters {
+    fn pair(self, eqn: &MTEquation) -> Option<(&'static str, &'static str)> {
+        match (self, eqn.m_inline) {
+            (MathDelimiters::None, _) => None,
+            (MathDelimiters::Inline, _) | (MathDelimiters::Auto, 1..) => Some(("\\(", "\\)")),
+            (MathDelimiters::Display, _) | (MathDelimiters::Auto, 0) => Some(("\\[", "\\]")),
+        }
+    }
+}
+
+/// Writing of characters LaTeX has no command for, e.g. from symbol fonts.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum UnknownChars {
+    /// As they are, for XeLaTeX or LuaLaTeX with a font covering them.
+    #[default]
+    Unicode,
+    /// By code point, `\symbol{"2A01}`, which pdfLaTeX takes without input encoding errors.
+    CodePoint,
+    /// The given text in place of each, e.g. `"\\square"` to spot them in proofs.
+    Replace(String),
+}
+
+/// Math alphabet command wrapping runs of characters in a style.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum MathAlphabet {
+    /// No command.
+    #[default]
+    Plain,
+    /// `\mathbf{..}`, upright bold; Greek letters stay as they are.
+    Bold,
+    /// `\boldsymbol{..}` (amsmath), bold italic letters and bold Greek.
+    BoldSymbol,
+    /// `\mathsf{..}`
+    SansSerif,
+    /// A one-argument macro of the user's template, e.g. `"\\vect"`.
+    Custom(String),
+}
+
+impl MathAlphabet {
+    fn command(&self) -> Option<&str> {
+        match self {
+            MathAlphabet::Plain => None,
+            MathAlphabet::Bold => Some("\\mathbf"),
+            MathAlphabet::BoldSymbol => Some("\\boldsymbol"),
+            MathAlphabet::SansSerif => Some("\\mathsf"),
+            MathAlphabet::Custom(command) => Some(command),
+        }
+    }
+}
+
+impl MTEquation {
+    pub fn translate(&self) -> Result<String, Error> {
+        self.translate_with(&TranslateOptions::default())
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self, options)))]
+    pub fn translate_with(&self, options: &TranslateOptions) -> Result<String, Error> {
+        let mut tx = Tx::new(options, self);
+        let delimiters = options.delimiters.pair(self);
+        if let Some((open, _)) = delimiters {
+            tx.push(open);
+        }
+        for (i, line) in self.lines().enumerate() {
+            if i > 0 {
+                tx.push(" \\\\ ");
+            }
+            tx.top_line(&line.line);
+        }
+        if let Some((_, close)) = delimiters {
+            tx.push(close);
+        }
+        Ok(tx.out)
+    }
+
+    /// Warnings about translating the equation with `options`, less those of its
+    /// `warning_filter`; characters its `char_mapper` knows are not reported.
+    pub fn translation_warnings(&self, options: &TranslateOptions) -> Vec<Warning> {
+        let mapper = options.char_mapper.as_ref().map(|m| m.as_ref() as &dyn CharMapper);
+        self.filtered_warnings(&options.warning_filter, mapper)
+    }
+}
+
+/// Marker ending truncated previews.
+const ELLIPSIS: &str = "\\ldots";
+
+impl MTEquation {
+    /// LaTeX of at most `max_len` bytes for listings, cut between top-level characters,
+    /// templates and piles and ended by `\ldots` when the equation does not fit (the
+    /// marker alone if nothing does).
+    pub fn translate_preview(&self, max_len: usize) -> Result<String, Error> {
+        self.translate_preview_with(max_len, &TranslateOptions::default())
+    }
+
+    /// `translate_preview` with `options`. The delimiters of `options.delimiters` are
+    /// written around the preview however short it is, and count towards `max_len`.
+    pub fn translate_preview_with(&self, max_len: usize, options: &TranslateOptions) -> Result<String, Error> {
+        let (open, close) = options.delimiters.pair(self).unwrap_or(("", ""));
+        let max_len = max_len.saturating_sub(open.len() + close.len());
+        let (mut out, truncated) = self.preview(max_len, options);
+        if truncated {
+            out = self.preview(max_len.saturating_sub(ELLIPSIS.len() + 1), options).0;
+            out.truncate(out.trim_end().len());
+            if !out.is_empty() {
+                out.push(' ');
+            }
+            out.push_str(ELLIPSIS);
+        }
+        Ok(format!("{}{}{}", open, out, close))
+    }
+
+    /// Output up to `limit` bytes, and whether some had to be left out.
+    fn preview(&self, limit: usize, options: &TranslateOptions) -> (String, bool) {
+        let mut tx = Tx::new(options, self);
+        tx.limit = Some(limit);
+        for (i, line) in self.lines().enumerate() {
+            let mark = tx.out.len();
+            if i > 0 {
+                tx.push(" \\\\ ");
+            }
+            tx.top_line(&line.line);
+            // a \ce{..} line is written whole, or not at all
+            if tx.out.len() > limit {
+                tx.out.truncate(mark);
+                tx.truncated = true;
+            }
+            if tx.truncated {
+                break;
+            }
+        }
+        (tx.out, tx.truncated)
+    }
+}
+
+impl EquationLine<'_> {
+    pub fn translate(&self) -> Result<String, Error> {
+        self.translate_with(&TranslateOptions::default())
+    }
+
+    pub fn translate_with(&self, options: &TranslateOptions) -> Result<String, Error> {
+        let mut tx = Tx::new(options, self.eqn);
+        tx.top_line(&self.line);
+        Ok(tx.out)
+    }
+}
+
+/// Template selectors `Tx::tmpl` translates specifically, keep in sync with it.
+pub(crate) const TRANSLATED_SELECTORS: &[u8] = &[
+    TM_ANGLE, TM_PAREN, TM_BRACE, TM_BRACK, TM_BAR, TM_DBAR, TM_FLOOR, TM_CEILING,
+    TM_ROOT, TM_FRACT, TM_HBRACE, TM_HBRACK, TM_ARROW, TM_STRIKE, TM_BOX,
+    TM_INTEG, TM_SUM, TM_PROD, TM_COPROD, TM_UNION, TM_INTER, TM_INTOP, TM_SUMOP, TM_LIM,
+    TM_SUB, TM_SUP, TM_SUBSUP,
+];
+
+/// Output buffer keeping control words apart from the letters that follow them.
+struct Tx<'o> {
+    out: String,
+    options: &'o TranslateOptions,
+    /// Font and encoding definitions of the equation.
+    eqn: &'o MTEquation,
+    /// `decimal_comma` as it applies to the equation.
+    decimal_comma: bool,
+    /// Length of output previews stop before exceeding.
+    limit: Option<usize>,
+    /// Nesting of node lists, 1 for the top-level lines.
+    depth: usize,
+    /// Output was cut short of `limit`, nothing more is written.
+    truncated: bool,
+}
+
+impl<'o> Tx<'o> {
+    fn new(options: &'o TranslateOptions, eqn: &'o MTEquation) -> Tx<'o> {
+        let decimal_comma = match options.decimal_comma {
+            DecimalComma::Off => false,
+            DecimalComma::On => true,
+            DecimalComma::Auto => uses_decimal_comma(eqn),
+        };
+        Tx { out: String::new(), options, eqn, decimal_comma, limit: None, depth: 0, truncated: false }
+    }
+
+    fn push(&mut self, s: &str) {
+        let starts_with_letter = s.chars().next().is_some_and(|c| c.is_alphabetic());
+        if starts_with_letter && ends_with_control_word(&self.out) {
+            self.out.push(' ');
+        }
+        self.out.push_str(s);
+    }
+
+    /// `{...}` around a slot; missing slots give an empty group.
+    fn group(&mut self, slot: Option<&Line>) {
+        self.push("{");
+        if let Some(line) = slot {
+            self.line(line);
+        }
+        self.push("}");
+    }
+
+    fn top_line(&mut self, line: &Line) {
+        if self.options.mhchem {
+            let mut ce = Ce::default();
+            if ce.line(self, line).is_some() && ce.is_chemistry() {
+                self.push(&format!("\\ce{{{}}}", ce.out.trim()));
+                return;
+            }
+        }
+        self.line(line);
+    }
+
+    fn line(&mut self, line: &Line) {
+        self.nodes(&line.nodes);
+    }
+
+    fn nodes(&mut self, nodes: &[Node]) {
+        self.depth += 1;
+        let mut i = 0;
+        while i < nodes.len() && !self.truncated {
+            let mark = self.out.len();
+            i += self.unit(nodes, i);
+            // previews end between top-level units, never inside one
+            if self.depth == 1 && self.limit.is_some_and(|limit| self.out.len() > limit) {
+                self.out.truncate(mark);
+                self.truncated = true;
+            }
+        }
+        self.depth -= 1;
+    }
+
+    /// Translates what starts at `nodes[i]`: a node, or a run of them translating as a
+    /// whole. Returns the number of nodes consumed.
+    fn unit(&mut self, nodes: &[Node], i: usize) -> usize {
+        if self.options.siunitx {
+            if let Some(n) = self.quantity(&nodes[i..]) {
+                return n;
+            }
+        }
+        if self.decimal_comma && is_decimal_comma(nodes, i) {
+            self.push("{,}");
+            return 1;
+        }
+        match &nodes[i] {
+            Node::Char(c) => match typeface(c.ch) {
+                // runs of function or text characters translate as a whole
+                face @ Some(FN_FUNCTION) | face @ Some(FN_TEXT) => {
+                    let run = char_run(&nodes[i..], face);
+                    match face {
+                        Some(FN_FUNCTION) => self.function(&run),
+                        _ => self.text(&run),
+                    }
+                    return run.len();
+                }
+                face @ Some(FN_VECTOR) | face @ Some(FN_USER1) | face @ Some(FN_USER2) => {
+                    let n = nodes[i..].iter()
+                        .take_while(|n| matches!(n, Node::Char(c) if typeface(c.ch) == face))
+                        .count();
+                    self.styled(face, &nodes[i..i + n]);
+                    return n;
+                }
+                _ => match flagged_function(&nodes[i..]) {
+                    Some(run) => {
+                        self.function(&run);
+                        return run.len();
+                    }
+                    None => self.embellished(c),
+                },
+            },
+            Node::Tmpl(t) => self.tmpl(t),
+            Node::Pile(p) => self.pile(p),
+            // no glyph, see MTEquation::markers
+            Node::Marker(_) => {}
+            Node::Placeholder => {
+                let options = self.options;
+                self.push(&options.placeholder);
+            }
+        }
+        1
+    }
+
+    /// Piles aligned on their relation operators are `aligned` environments (amsmath),
+    /// the others arrays. A pile hanging from its top or bottom line is positioned
+    /// `[t]` or `[b]`; line spacing other than MathType's default stretches arrays
+    /// through `\arraystretch`, and goes in the row separators of `aligned` and of
+    /// lines spaced on their own.
+    fn pile(&mut self, pile: &Pile) {
+        let relop = PHA_RELOP == pile.pile.halign;
+        let rows = cells(pile, relop);
+        let size = self.eqn.full_size().unwrap_or(12.0);
+        let spacing = self.eqn.line_spacing();
+        let mut extra = pile.extra_spacing(size * spacing);
+        let position = match pile.pile.valign {
+            PVA_TOP => "[t]",
+            PVA_BOTTOM => "[b]",
+            _ => "",
+        };
+        if relop {
+            for e in extra.iter_mut().skip(1) {
+                *e += size * (spacing - DEFAULT_LINE_SPACING);
+            }
+            self.push(&format!("\\begin{{aligned}}{}", position));
+            self.rows(&rows, &extra);
+            self.push("\\end{aligned}");
+            return;
+        }
+        let align = match pile.pile.halign {
+            PHA_LEFT => "l",
+            PHA_RIGHT => "r",
+            _ => "c",
+        };
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(1);
+        let stretch = round(spacing / DEFAULT_LINE_SPACING);
+        let stretched = rows.len() > 1 && stretch != 1.0;
+        if stretched {
+            self.push(&format!("{{\\renewcommand{{\\arraystretch}}{{{}}}", stretch));
+        }
+        self.push(&format!("\\begin{{array}}{}{{{}}}", position, align.repeat(columns)));
+        self.rows(&rows, &extra);
+        self.push("\\end{array}");
+        if stretched {
+            self.push("}");
+        }
+    }
+
+    /// Cells separated by `&`, rows by `\\`, followed by the space `extra` gives
+    /// above the next row, in points.
+    fn rows(&mut self, rows: &[Vec<&[Node]>], extra: &[f32]) {
+        for (i, row) in rows.iter().enumerate() {
+            if i > 0 {
+                match extra.get(i).map(|&e| round(e)).filter(|&e| e != 0.0) {
+                    Some(e) => self.push(&format!(" \\\\[{}pt] ", e)),
+                    None => self.push(" \\\\ "),
+                }
+            }
+            for (j, cell) in row.iter().enumerate() {
+                if j > 0 {
+                    self.push(" & ");
+                }
+                self.nodes(cell);
+            }
+        }
+    }
+
+    /// siunitx quantities: a number, optional spaces, then a text run made of units
+    /// (and a numeric superscript), `\SI{9.81}{m/s^{2}}`. Units without a number are
+    /// left to the text they are written in. Returns the number of nodes consumed.
+    fn quantity(&mut self, nodes: &[Node]) -> Option<usize> {
+        let number: String = nodes.iter().enumerate()
+            .map_while(|(i, n)| match n {
+                Node::Char(c) if c.embells.is_empty() => unicode(c.ch)
+                    .filter(|c| c.is_ascii_digit() || '.' == *c || (self.decimal_comma && is_decimal_comma(nodes, i))),
+                _ => None,
+            })
+            .collect();
+        if number.is_empty() || number.starts_with('.') {
+            return None;
+        }
+        let mut i = number.len();
+        while let Some(Node::Char(c)) = nodes.get(i) {
+            if typeface(c.ch) != Some(FN_SPACE) {
+                break;
+            }
+            i += 1;
+        }
+        let run = char_run(&nodes[i..], Some(FN_TEXT));
+        let unit: String = run.iter().filter_map(|ch| unicode(ch)).collect();
+        if run.is_empty() || !is_unit(&unit) {
+            return None;
+        }
+        let mut unit = SI_MACROS.iter().fold(unit, |unit, (symbol, name)| unit.replace(symbol, name));
+        i += run.len();
+        if let Some(Node::Tmpl(t)) = nodes.get(i) {
+            if let Some(Scripts { sub: None, sup: Some(sup), precedes: false }) = t.scripts() {
+                let exponent: Option<String> = sup.nodes.iter()
+                    .map(|n| match n {
+                        Node::Char(c) => unicode(c.ch).filter(|c| c.is_ascii_digit() || '-' == *c || '\u{2212}' == *c),
+                        _ => None,
+                    })
+                    .collect();
+                if let Some(exponent) = exponent {
+                    unit.push_str(&format!("^{{{}}}", exponent.replace('\u{2212}', "-")));
+                    i += 1;
+                }
+            }
+        }
+        self.push(&format!("\\SI{{{}}}{{{}}}", number, unit));
+        Some(i)
+    }
+
+    /// A run of characters in the vector or a user style, in the alphabet the
+    /// options give for it.
+    fn styled(&mut self, face: Option<u8>, run: &[Node]) {
+        let alphabet = match face {
+            Some(FN_VECTOR) => &self.options.vector_alphabet,
+            Some(FN_USER1) => &self.options.user1_alphabet,
+            _ => &self.options.user2_alphabet,
+        };
+        let command = alphabet.command();
+        if let Some(command) = command {
+            self.push(&format!("{}{{", command));
+        }
+        for node in run {
+            if let Node::Char(c) = node {
+                self.embellished(c);
+            }
+        }
+        if command.is_some() {
+            self.push("}");
+        }
+    }
+
+    /// Embellishments wrap the character in list order, the first one innermost.
+    fn embellished(&mut self, c: &Char) {
+        let marks: Vec<(&str, &str)> = c.embells.iter()
+            .filter(|e| !(self.options.strip_cancel && is_cancel(e.embell)))
+            .filter_map(|e| embellishment(e.embell))
+            .collect();
+        for (before, _) in marks.iter().rev() {
+            self.push(before);
+        }
+        self.char(c.ch);
+        for (_, after) in marks.iter() {
+            self.push(after);
+        }
+    }
+
+    fn char(&mut self, ch: &MTChar) {
+        if typeface(ch) == Some(FN_SPACE) {
+            self.push("\\,");
+            return;
+        }
+        let c = match self.map(ch) {
+            Some(MappedChar::Char(c)) => c,
+            Some(MappedChar::Latex(s)) => return self.push(&s),
+            None => return,
+        };
+        if let Some(g) = greek(c, typeface(ch)).map(|c| prefer_variant(c, self.options.greek_variants)).and_then(greek_command) {
+            self.push(g);
+            return;
+        }
+        match (symbol(c), &self.options.cjk_policy) {
+            (Some(s), _) => self.push(s),
+            (None, CjkPolicy::Environment(family)) if is_cjk(c) => {
+                self.push(&format!("\\text{{{}}}", cjk_environments(&c.to_string(), family)))
+            }
+            (None, _) if c.is_ascii() => self.push(c.encode_utf8(&mut [0; 4])),
+            (None, _) => match &self.options.unknown_chars {
+                UnknownChars::Unicode => self.push(c.encode_utf8(&mut [0; 4])),
+                UnknownChars::CodePoint => self.push(&format!("\\symbol{{\"{:X}}}", c as u32)),
+                UnknownChars::Replace(text) => self.push(text),
+            },
+        }
+    }
+
+    /// A character through the user's mapper, then the default one.
+    fn map(&self, ch: &MTChar) -> Option<MappedChar> {
+        let mapper = self.options.char_mapper.as_ref().map(|m| m.as_ref() as &dyn CharMapper);
+        charmap::resolve(self.eqn, ch, mapper)
+    }
+
+    fn function(&mut self, run: &[&MTChar]) {
+        let name: String = run.iter().filter_map(|ch| unicode(ch)).collect();
+        match FUNCTIONS.contains(&name.as_str()) {
+            true => self.push(&format!("\\{}", name)),
+            false => self.push(&format!("\\operatorname{{{}}}", escape_text(&name))),
+        }
+    }
+
+    fn text(&mut self, run: &[&MTChar]) {
+        let text: String = run.iter().filter_map(|ch| unicode(ch)).collect();
+        let text = match &self.options.cjk_policy {
+            CjkPolicy::Unicode => escape_text(&text),
+            CjkPolicy::Environment(family) => cjk_environments(&text, family),
+        };
+        self.push(&format!("\\text{{{}}}", text));
+    }
+
+    fn tmpl(&mut self, t: &Tmpl) {
+        match t.tmpl.selector {
+            TM_ANGLE | TM_PAREN | TM_BRACE | TM_BRACK | TM_BAR | TM_DBAR | TM_FLOOR | TM_CEILING => {
+                if let Some(fence) = t.fence() {
+                    self.fence(&fence);
+                }
+            }
+            TM_ROOT => self.root(t),
+            TM_FRACT => {
+                self.push("\\frac");
+                self.group(t.slot(0));
+                self.group(t.slot(1));
+            }
+            TM_HBRACE | TM_HBRACK => {
+                if let Some(hbrace) = t.hbrace() {
+                    self.hbrace(&hbrace);
+                }
+            }
+            TM_ARROW => {
+                if let Some(arrow) = t.arrow() {
+                    self.arrow(&arrow);
+                }
+            }
+            TM_STRIKE => {
+                if let Some(strike) = t.strike() {
+                    self.strike(&strike);
+                }
+            }
+            TM_BOX => {
+                if let Some(boxed) = t.boxed() {
+                    self.boxed(&boxed);
+                }
+            }
+            TM_INTEG | TM_SUM | TM_PROD | TM_COPROD | TM_UNION | TM_INTER | TM_INTOP | TM_SUMOP => {
+                if let Some(big_op) = t.big_op() {
+                    self.big_op(&big_op);
+                }
+            }
+            TM_LIM => {
+                if let Some(limit) = t.limit() {
+                    self.line_opt(limit.name);
+                    self.limits(limit.lower, limit.upper);
+                }
+            }
+            TM_SUB | TM_SUP | TM_SUBSUP => {
+                if let Some(scripts) = t.scripts() {
+                    self.scripts(&scripts);
+                }
+            }
+            // not translated specifically yet, keep the content
+            _ => {
+                for slot in &t.slots {
+                    self.group(Some(slot));
+                }
+            }
+        }
+    }
+
+    /// Radicals: the first slot is the radicand, nth roots carry their degree in the second one.
+    fn root(&mut self, t: &Tmpl) {
+        self.push("\\sqrt");
+        if TV_ROOT_NTH == t.tmpl.variation & TV_ROOT_NTH {
+            if let Some(degree) = t.slot(1) {
+                self.push("[");
+                self.line(degree);
+                self.push("]");
+            }
+        }
+        self.group(t.slot(0));
+    }
+
+    /// `\overbrace{body}^{label}` / `\underbrace{body}_{label}`,
+    /// brackets use the mathtools `\overbracket` and `\underbracket`.
+    fn hbrace(&mut self, hbrace: &HBrace) {
+        let (cmd, script) = match (hbrace.top, hbrace.bracket) {
+            (true, false) => ("\\overbrace", "^"),
+            (false, false) => ("\\underbrace", "_"),
+            (true, true) => ("\\overbracket", "^"),
+            (false, true) => ("\\underbracket", "_"),
+        };
+        self.push(cmd);
+        self.group(hbrace.body);
+        if let Some(label) = hbrace.label {
+            self.push(script);
+            self.group(Some(label));
+        }
+    }
+
+    /// `\sum_{i=1}^{n} body`. LaTeX sets integral limits beside the sign and other
+    /// operators' limits above and below it in display style, `\limits` and `\nolimits`
+    /// keep the placement MathType uses when it differs.
+    fn big_op(&mut self, big_op: &BigOp) {
+        let cmd = match big_op.kind {
+            BigOpKind::Integral { count, contour: false } => Some(["\\int", "\\iint", "\\iiint"][count.min(3) as usize - 1]),
+            BigOpKind::Integral { count, contour: true } => Some(["\\oint", "\\oiint", "\\oiiint"][count.min(3) as usize - 1]),
+            BigOpKind::Sum => Some("\\sum"),
+            BigOpKind::Prod => Some("\\prod"),
+            BigOpKind::Coprod => Some("\\coprod"),
+            BigOpKind::Union => Some("\\bigcup"),
+            BigOpKind::Inter => Some("\\bigcap"),
+            BigOpKind::Other => None,
+        };
+        match cmd {
+            Some(cmd) => self.push(cmd),
+            // limit controls need an operator, whatever the character is
+            None => {
+                self.push("\\mathop{");
+                if let Some(op) = big_op.op {
+                    self.char(op);
+                }
+                self.push("}");
+            }
+        }
+        if big_op.lower.is_some() || big_op.upper.is_some() {
+            let integral = matches!(big_op.kind, BigOpKind::Integral { .. });
+            if big_op.over_under && integral {
+                self.push("\\limits");
+            } else if !big_op.over_under && !integral {
+                self.push("\\nolimits");
+            }
+        }
+        self.limits(big_op.lower, big_op.upper);
+        self.line_opt(big_op.body);
+    }
+
+    fn limits(&mut self, lower: Option<&Line>, upper: Option<&Line>) {
+        if let Some(lower) = lower {
+            self.push("_");
+            self.group(Some(lower));
+        }
+        if let Some(upper) = upper {
+            self.push("^");
+            self.group(Some(upper));
+        }
+    }
+
+    /// Scripts attach to the previous object; left scripts are written
+    /// on an empty group in front of the object that follows, `{}_{b}^{a}X`.
+    fn scripts(&mut self, scripts: &Scripts) {
+        if scripts.precedes {
+            self.push("{}");
+        }
+        if let Some(sub) = scripts.sub {
+            self.push("_");
+            self.group(Some(sub));
+        }
+        if let Some(sup) = scripts.sup {
+            self.push("^");
+            self.group(Some(sup));
+        }
+    }
+
+    /// Diagonal strokes map to the cancel package, a horizontal stroke to `\hcancel`
+    /// (hcancel package) around them. All strokes are dropped with `strip_cancel`.
+    fn strike(&mut self, strike: &Strike) {
+        if self.options.strip_cancel {
+            self.line_opt(strike.body);
+            return;
+        }
+        let diagonal = match (strike.up, strike.down) {
+            (true, true) => Some("\\xcancel"),
+            (true, false) => Some("\\cancel"),
+            (false, true) => Some("\\bcancel"),
+            (false, false) => None,
+        };
+        if strike.horizontal {
+            self.push("\\hcancel{");
+        }
+        match diagonal {
+            Some(cmd) => {
+                self.push(cmd);
+                self.group(strike.body);
+            }
+            None => self.line_opt(strike.body),
+        }
+        if strike.horizontal {
+            self.push("}");
+        }
+    }
+
+    /// Full boxes become `\boxed` (amsmath), rounded ones `\ovalbox` (fancybox) around
+    /// the content in inline math. Partial boxes are approximated: over/underlines for
+    /// the horizontal edges, bars for the vertical ones.
+    fn boxed(&mut self, boxed: &Boxed) {
+        if boxed.is_full() && boxed.round {
+            self.push("\\ovalbox{$");
+            self.line_opt(boxed.body);
+            self.push("$}");
+            return;
+        }
+        if boxed.is_full() {
+            self.push("\\boxed");
+            self.group(boxed.body);
+            return;
+        }
+        if boxed.left || boxed.right {
+            self.push(if boxed.left { "\\left|" } else { "\\left." });
+        }
+        if boxed.top {
+            self.push("\\overline{");
+        }
+        if boxed.bottom {
+            self.push("\\underline{");
+        }
+        self.line_opt(boxed.body);
+        if boxed.bottom {
+            self.push("}");
+        }
+        if boxed.top {
+            self.push("}");
+        }
+        if boxed.left || boxed.right {
+            self.push(if boxed.right { "\\right|" } else { "\\right." });
+        }
+    }
+
+    /// Extensible arrows, `\xrightarrow[below]{above}` (amsmath).
+    /// Other directions and arrow pairs need mathtools.
+    fn arrow(&mut self, arrow: &Arrow) {
+        self.push(match arrow.kind {
+            ArrowKind::Right => "\\xrightarrow",
+            ArrowKind::Left => "\\xleftarrow",
+            ArrowKind::LeftRight => "\\xleftrightarrow",
+            ArrowKind::Double | ArrowKind::Harpoons => "\\xrightleftharpoons",
+        });
+        if let Some(bottom) = arrow.bottom {
+            self.push("[");
+            self.line(bottom);
+            self.push("]");
+        }
+        self.group(arrow.top);
+    }
+
+    fn fence(&mut self, fence: &Fence) {
+        let (open, close) = match fence.kind {
+            FenceKind::Angle => ("\\langle", "\\rangle"),
+            FenceKind::Paren => ("(", ")"),
+            FenceKind::Brace => ("\\{", "\\}"),
+            FenceKind::Brack => ("[", "]"),
+            FenceKind::Bar => ("\\lvert", "\\rvert"),
+            FenceKind::DBar => ("\\lVert", "\\rVert"),
+            FenceKind::Floor => ("\\lfloor", "\\rfloor"),
+            FenceKind::Ceiling => ("\\lceil", "\\rceil"),
+        };
+        if let (FenceKind::Brace, true, false) = (fence.kind, fence.left, fence.right) {
+            let pile = fence.body.and_then(Line::as_pile);
+            if let (Some(pile), BracePileStyle::Cases) = (pile, self.options.brace_pile_style) {
+                self.push("\\begin{cases}");
+                self.rows(&cells(pile, false), &[]);
+                self.push("\\end{cases}");
+                return;
+            }
+        }
+        if !self.options.auto_delimiters {
+            let height = fence.body.map(bounds::line).unwrap_or_default().height();
+            let size = fixed_size(height);
+            if fence.left {
+                self.push(&format!("{}{}", size.map_or("", |(l, _)| l), open));
+            }
+            self.line_opt(fence.body);
+            if fence.right {
+                self.push(&format!("{}{}", size.map_or("", |(_, r)| r), close));
+            }
+            return;
+        }
+        self.push("\\left");
+        self.push(if fence.left { open } else { "." });
+        self.line_opt(fence.body);
+        self.push("\\right");
+        self.push(if fence.right { close } else { "." });
+    }
+
+    fn line_opt(&mut self, line: Option<&Line>) {
+        if let Some(line) = line {
+            self.line(line);
+        }
+    }
+}
+
+/// Sizing commands for delimiters around content `height` ems tall, none up to
+/// about the height of a letter with scripts.
+fn fixed_size(height: f32) -> Option<(&'static str, &'static str)> {
+    match height {
+        h if h <= 1.3 => None,
+        h if h <= 1.6 => Some(("\\bigl", "\\bigr")),
+        h if h <= 2.2 => Some(("\\Bigl", "\\Bigr")),
+        h if h <= 2.8 => Some(("\\biggl", "\\biggr")),
+        _ => Some(("\\Biggl", "\\Biggr")),
+    }
+}
+
+fn is_cancel(embell: u8) -> bool {
+    matches!(embell, EMB_X_BARS | EMB_UP_BAR | EMB_DOWN_BAR)
+}
+
+/// What goes before and after an embellished character.
+fn embellishment(embell: u8) -> Option<(&'static str, &'static str)> {
+    let marks = match embell {
+        EMB_1DOT => ("\\dot{", "}"),
+        EMB_2DOT => ("\\ddot{", "}"),
+        EMB_3DOT => ("\\dddot{", "}"),
+        EMB_4DOT => ("\\ddddot{", "}"),
+        EMB_1PRIME => ("", "'"),
+        EMB_2PRIME => ("", "''"),
+        EMB_3PRIME => ("", "'''"),
+        EMB_BPRIME => ("\\backprime ", ""),
+        EMB_TILDE => ("\\tilde{", "}"),
+        EMB_HAT => ("\\hat{", "}"),
+        EMB_NOT => ("\\not", ""),
+        EMB_RARROW => ("\\overrightarrow{", "}"),
+        EMB_LARROW => ("\\overleftarrow{", "}"),
+        EMB_BARROW => ("\\overleftrightarrow{", "}"),
+        EMB_R1ARROW => ("\\overset{\\rightharpoonup}{", "}"),
+        EMB_L1ARROW => ("\\overset{\\leftharpoonup}{", "}"),
+        EMB_OBAR => ("\\bar{", "}"),
+        EMB_FROWN => ("\\overset{\\frown}{", "}"),
+        EMB_SMILE => ("\\overset{\\smile}{", "}"),
+        EMB_X_BARS => ("\\xcancel{", "}"),
+        EMB_UP_BAR => ("\\cancel{", "}"),
+        EMB_DOWN_BAR => ("\\bcancel{", "}"),
+        EMB_U_1DOT => ("\\underset{\\cdot}{", "}"),
+        EMB_U_2DOT => ("\\underset{\\cdot\\cdot}{", "}"),
+        EMB_U_3DOT => ("\\underset{\\cdot\\cdot\\cdot}{", "}"),
+        EMB_U_4DOT => ("\\underset{\\cdot\\cdot\\cdot\\cdot}{", "}"),
+        EMB_U_BAR => ("\\underline{", "}"),
+        EMB_U_TILDE => ("\\underset{\\sim}{", "}"),
+        EMB_U_FROWN => ("\\underset{\\frown}{", "}"),
+        EMB_U_SMILE => ("\\underset{\\smile}{", "}"),
+        EMB_U_RARROW => ("\\underrightarrow{", "}"),
+        EMB_U_LARROW => ("\\underleftarrow{", "}"),
+        EMB_U_BARROW => ("\\underleftrightarrow{", "}"),
+        EMB_U_R1ARROW => ("\\underset{\\rightharpoonup}{", "}"),
+        EMB_U_L1ARROW => ("\\underset{\\leftharpoonup}{", "}"),
+        // EMB_MBAR, a horizontal strike, has no math-mode equivalent
+        _ => return None,
+    };
+    Some(marks)
+}
+
+/// mhchem input built from a line, `None` from its methods when the line holds
+/// something that is not chemistry.
+#[derive(Default)]
+struct Ce {
+    out: String,
+    /// letter runs outside arrow labels, to be checked against element symbols
+    runs: Vec<String>,
+    arrow: bool,
+    counts: bool,
+}
+
+impl Ce {
+    fn line(&mut self, tx: &Tx, line: &Line) -> Option<()> {
+        for node in &line.nodes {
+            match node {
+                Node::Char(c) if c.embells.is_empty() => self.char(tx, c.ch)?,
+                Node::Tmpl(t) => self.tmpl(tx, t)?,
+                _ => return None,
+            }
+        }
+        Some(())
+    }
+
+    fn char(&mut self, tx: &Tx, ch: &MTChar) -> Option<()> {
+        if typeface(ch) == Some(FN_SPACE) {
+            return self.op(" ");
+        }
+        let c = match tx.map(ch)? {
+            MappedChar::Char(c) => c,
+            MappedChar::Latex(_) => return None,
+        };
+        match c {
+            'A'..='Z' | 'a'..='z' => {
+                match self.out.chars().last().is_some_and(|l| l.is_ascii_alphabetic()) {
+                    true => self.runs.last_mut()?.push(c),
+                    false => self.runs.push(c.to_string()),
+                }
+                self.out.push(c);
+                Some(())
+            }
+            '0'..='9' | '(' | ')' | '[' | ']' | '.' => {
+                self.out.push(c);
+                Some(())
+            }
+            '+' | '=' => self.op(&c.to_string()),
+            '\u{00B7}' | '\u{22C5}' | '\u{2219}' => self.op("*"),
+            '\u{2192}' => self.arrow("->"),
+            '\u{2190}' => self.arrow("<-"),
+            '\u{2194}' => self.arrow("<->"),
+            '\u{21CC}' | '\u{21C4}' => self.arrow("<=>"),
+            '\u{2191}' => self.op("^"),
+            '\u{2193}' => self.op("v"),
+            _ => None,
+        }
+    }
+
+    fn tmpl(&mut self, tx: &Tx, t: &Tmpl) -> Option<()> {
+        match t.tmpl.selector {
+            TM_SUB | TM_SUP | TM_SUBSUP => {
+                let scripts = t.scripts()?;
+                if let Some(sub) = scripts.sub {
+                    let mut count = Ce::default();
+                    count.line(tx, sub)?;
+                    match count.out.chars().all(|c| c.is_ascii_digit()) {
+                        true => {
+                            self.counts = true;
+                            self.out.push_str(&count.out);
+                        }
+                        false => self.out.push_str(&format!("_{{{}}}", count.out)),
+                    }
+                }
+                if let Some(sup) = scripts.sup {
+                    let mut charge = Ce::default();
+                    charge.line(tx, sup)?;
+                    self.out.push_str(&format!("^{{{}}}", charge.out.replace(' ', "")));
+                }
+                Some(())
+            }
+            TM_ARROW => {
+                let arrow = t.arrow()?;
+                self.arrow(match arrow.kind {
+                    ArrowKind::Right => "->",
+                    ArrowKind::Left => "<-",
+                    ArrowKind::LeftRight => "<->",
+                    ArrowKind::Double | ArrowKind::Harpoons => "<=>",
+                })?;
+                // labels are free text, not checked for element symbols
+                for label in &[arrow.top, arrow.bottom] {
+                    let mut text = Ce::default();
+                    if let Some(label) = label {
+                        text.line(tx, label)?;
+                    }
+                    self.out.truncate(self.out.trim_end().len());
+                    self.out.push_str(&format!("[{}] ", text.out.trim()));
+                }
+                Some(())
+            }
+            TM_PAREN | TM_BRACK => {
+                let fence = t.fence()?;
+                let (open, close) = match fence.kind {
+                    FenceKind::Paren => ('(', ')'),
+                    _ => ('[', ']'),
+                };
+                self.out.push(open);
+                if let Some(body) = fence.body {
+                    self.line(tx, body)?;
+                }
+                self.out.push(close);
+                Some(())
+            }
+            _ => None,
+        }
+    }
+
+    fn op(&mut self, op: &str) -> Option<()> {
+        self.out.truncate(self.out.trim_end().len());
+        self.out.push_str(&format!(" {} ", op.trim()));
+        Some(())
+    }
+
+    fn arrow(&mut self, arrow: &str) -> Option<()> {
+        self.arrow = true;
+        self.op(arrow)
+    }
+
+    /// A reaction arrow or subscript counts, and element symbols only (states such
+    /// as "aq" and the electron "e" aside).
+    fn is_chemistry(&self) -> bool {
+        (self.arrow || self.counts) && !self.runs.is_empty()
+            && self.runs.iter().all(|run| ["aq", "g", "s", "l", "e"].contains(&run.as_str()) || is_formula(run))
+    }
+}
+
+/// Element symbols, by atomic number.
+const ELEMENTS: &[&str] = &[
+    "H", "He", "Li", "Be", "B", "C", "N", "O", "F", "Ne", "Na", "Mg", "Al", "Si", "P", "S", "Cl", "Ar",
+    "K", "Ca", "Sc", "Ti", "V", "Cr", "Mn", "Fe", "Co", "Ni", "Cu", "Zn", "Ga", "Ge", "As", "Se", "Br", "Kr",
+    "Rb", "Sr", "Y", "Zr", "Nb", "Mo", "Tc", "Ru", "Rh", "Pd", "Ag", "Cd", "In", "Sn", "Sb", "Te", "I", "Xe",
+    "Cs", "Ba", "La", "Ce", "Pr", "Nd", "Pm", "Sm", "Eu", "Gd", "Tb", "Dy", "Ho", "Er", "Tm", "Yb", "Lu",
+    "Hf", "Ta", "W", "Re", "Os", "Ir", "Pt", "Au", "Hg", "Tl", "Pb", "Bi", "Po", "At", "Rn",
+    "Fr", "Ra", "Ac", "Th", "Pa", "U", "Np", "Pu", "Am", "Cm", "Bk", "Cf", "Es", "Fm", "Md", "No", "Lr",
+    "Rf", "Db", "Sg", "Bh", "Hs", "Mt", "Ds", "Rg", "Cn", "Nh", "Fl", "Mc", "Lv", "Ts", "Og",
+];
+
+/// Whether `run` splits into element symbols, e.g. "NaCl".
+fn is_formula(run: &str) -> bool {
+    let mut rest = run;
+    while !rest.is_empty() {
+        let two = rest.get(..2).filter(|s| ELEMENTS.contains(s));
+        let one = rest.get(..1).filter(|s| ELEMENTS.contains(s));
+        match two.or(one) {
+            Some(symbol) => rest = &rest[symbol.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Cells of the pile lines, the parts between tab characters. Lines without tabs
+/// are split before their first relation operator when `relop` is set.
+fn cells<'p, 'a>(pile: &'p Pile<'a>, relop: bool) -> Vec<Vec<&'p [Node<'a>]>> {
+    pile.lines.iter()
+        .map(|line| {
+            let nodes = &line.nodes[..];
+            if nodes.iter().any(is_tab) {
+                return nodes.split(is_tab).collect();
+            }
+            match nodes.iter().position(is_relation).filter(|_| relop) {
+                Some(i) => vec![&nodes[..i], &nodes[i..]],
+                None => vec![nodes],
+            }
+        })
+        .collect()
+}
+
+fn is_tab(node: &Node) -> bool {
+    matches!(node, Node::Char(c) if c.ch.mtcode == Some(0x09))
+}
+
+fn is_relation(node: &Node) -> bool {
+    match node {
+        Node::Char(c) => matches!(unicode(c.ch), Some('=' | '<' | '>' | '\u{2248}' | '\u{2260}' | '\u{2261}'
+            | '\u{2264}' | '\u{2265}' | '\u{223C}' | '\u{2243}' | '\u{2245}' | '\u{221D}'
+            | '\u{2192}' | '\u{21D2}' | '\u{21D4}')),
+        _ => false,
+    }
+}
+
+/// Characters at the start of `nodes` sharing the typeface `face`.
+fn char_run<'a>(nodes: &[Node<'a>], face: Option<u8>) -> Vec<&'a MTChar> {
+    nodes.iter()
+        .map_while(|n| match n {
+            Node::Char(c) if typeface(c.ch) == face => Some(c.ch),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Known function name starting at `nodes[0]` in a style other than the function
+/// one, flagged by MathType as starting a function: the letters of a `log_2` typed
+/// before the template, which its script must follow as a whole, `\log_{2}`.
+fn flagged_function<'a>(nodes: &[Node<'a>]) -> Option<Vec<&'a MTChar>> {
+    let face = match nodes.first()? {
+        Node::Char(c) if c.ch.options.contains(CharOptions::FUNC_START) => typeface(c.ch),
+        _ => return None,
+    };
+    let run: Vec<&MTChar> = nodes.iter().enumerate()
+        .map_while(|(i, n)| match n {
+            Node::Char(c) if c.embells.is_empty() && typeface(c.ch) == face
+                && (i == 0 || !c.ch.options.contains(CharOptions::FUNC_START)) => Some(c.ch),
+            _ => None,
+        })
+        .collect();
+    let name: Vec<char> = run.iter().map_while(|ch| unicode(ch)).collect();
+    // the longest known name, letters after it are variables
+    (1..=name.len()).rev()
+        .find(|&n| FUNCTIONS.contains(&name[..n].iter().collect::<String>().as_str()))
+        .map(|n| run[..n].to_vec())
+}
+
+/// SI prefixes accepted in front of a unit symbol.
+const SI_PREFIXES: &[&str] = &["G", "M", "k", "h", "d", "c", "m", "\u{00B5}", "\u{03BC}", "n", "p"];
+
+/// Unit symbols recognised in text runs.
+const SI_UNITS: &[&str] = &[
+    "m", "g", "s", "A", "K", "mol", "cd", "Hz", "N", "Pa", "J", "W", "C", "V", "F",
+    "\u{03A9}", "S", "Wb", "T", "H", "L", "l", "min", "h", "eV", "bar", "atm", "rad", "sr",
+    "Bq", "Gy", "Sv", "lm", "lx", "\u{00B0}C", "\u{00B0}",
+];
+
+/// Unit symbols and prefixes siunitx takes as macros only, °C before °.
+const SI_MACROS: &[(&str, &str)] = &[
+    ("\u{00B0}C", "\\degreeCelsius"), ("\u{00B0}", "\\degree"), ("\u{00B5}", "\\micro "), ("\u{03BC}", "\\micro "),
+    ("\u{03A9}", "\\ohm"),
+];
+
+/// A text run is a unit when every part between `.`, `/` or `\u{00B7}` is a unit symbol.
+fn is_unit(text: &str) -> bool {
+    let is_symbol = |part: &str| {
+        SI_UNITS.contains(&part) || SI_PREFIXES.iter().any(|p| {
+            part.starts_with(p) && SI_UNITS.contains(&&part[p.len()..])
+        })
+    };
+    let mut parts = text.split(['.', '/', '\u{00B7}', ' ']).peekable();
+    parts.peek().is_some() && parts.all(|part| !part.is_empty() && is_symbol(part))
+}
+
+/// Typeface without its 128 bias, `None` for explicit font indices.
+fn typeface(ch: &MTChar) -> Option<u8> {
+    ch.typeface.style()
+}
+
+fn unicode(ch: &MTChar) -> Option<char> {
+    ch.mtcode.and_then(|c| std::char::from_u32(c as u32))
+}
+
+/// Whether `nodes[i]` is a comma between two digits.
+fn is_decimal_comma(nodes: &[Node], i: usize) -> bool {
+    let char_at = |j: Option<usize>| match j.and_then(|j| nodes.get(j)) {
+        Some(Node::Char(c)) => unicode(c.ch),
+        _ => None,
+    };
+    let digit = |j| char_at(j).is_some_and(|c| c.is_ascii_digit());
+    char_at(Some(i)) == Some(',') && digit(i.checked_sub(1)) && digit(Some(i + 1))
+}
+
+/// `DecimalComma::Auto` detection, over the characters of the whole equation.
+fn uses_decimal_comma(eqn: &MTEquation) -> bool {
+    let chars: Vec<Option<char>> = eqn.records.iter()
+        .map(|r| match r {
+            MTRecords::CHAR(ch) => unicode(ch),
+            _ => None,
+        })
+        .collect();
+    let digit = |j: usize| chars.get(j).copied().flatten().is_some_and(|c| c.is_ascii_digit());
+    let mut found = false;
+    for (i, c) in chars.iter().enumerate().skip(1) {
+        if !digit(i - 1) || !digit(i + 1) {
+            continue;
+        }
+        match c {
+            Some('.') => return false,
+            Some(',') => {
+                // a thousands separator, or a list of numbers
+                if (i + 1..).take_while(|&j| digit(j)).count() == 3 {
+                    return false;
+                }
+                found = true;
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// Greek letter of a character. Characters in the Greek typefaces may carry
+/// Symbol font positions (`q` for theta) rather than Unicode.
+fn greek(c: char, face: Option<u8>) -> Option<char> {
+    match c {
+        '\u{0391}'..='\u{03D6}' | '\u{03F0}' | '\u{03F1}' | '\u{03F5}' => Some(c),
+        'A'..='Z' | 'a'..='z' if matches!(face, Some(FN_LCGREEK) | Some(FN_UCGREEK)) => charmap::symbol_font(c as u8),
+        _ => None,
+    }
+}
+
+/// Plain and variant forms of the Greek letters that have both.
+const GREEK_VARIANTS: &[(char, char)] = &[
+    ('\u{03F5}', '\u{03B5}'),
+    ('\u{03B8}', '\u{03D1}'),
+    ('\u{03BA}', '\u{03F0}'),
+    ('\u{03C1}', '\u{03F1}'),
+    ('\u{03D5}', '\u{03C6}'),
+];
+
+fn prefer_variant(c: char, preference: GreekVariants) -> char {
+    let pair = GREEK_VARIANTS.iter().find(|(plain, variant)| c == *plain || c == *variant);
+    match (pair, preference) {
+        (Some((plain, _)), GreekVariants::Plain) => *plain,
+        (Some((_, variant)), GreekVariants::Variant) => *variant,
+        _ => c,
+    }
+}
+
+/// LaTeX for a Greek letter. Uppercase letters looking like Latin ones have no
+/// command of their own. U+03C6 is the loopy phi (\varphi) and U+03D5 the stroked
+/// one (\phi), likewise for epsilon.
+fn greek_command(c: char) -> Option<&'static str> {
+    let s = match c {
+        '\u{03B1}' => "\\alpha",
+        '\u{03B2}' => "\\beta",
+        '\u{03B3}' => "\\gamma",
+        '\u{03B4}' => "\\delta",
+        '\u{03F5}' => "\\epsilon",
+        '\u{03B5}' => "\\varepsilon",
+        '\u{03B6}' => "\\zeta",
+        '\u{03B7}' => "\\eta",
+        '\u{03B8}' => "\\theta",
+        '\u{03D1}' => "\\vartheta",
+        '\u{03B9}' => "\\iota",
+        '\u{03BA}' => "\\kappa",
+        '\u{03F0}' => "\\varkappa",
+        '\u{03BB}' => "\\lambda",
+        '\u{03BC}' => "\\mu",
+        '\u{03BD}' => "\\nu",
+        '\u{03BE}' => "\\xi",
+        '\u{03BF}' => "o",
+        '\u{03C0}' => "\\pi",
+        '\u{03D6}' => "\\varpi",
+        '\u{03C1}' => "\\rho",
+        '\u{03F1}' => "\\varrho",
+        '\u{03C3}' => "\\sigma",
+        '\u{03C2}' => "\\varsigma",
+        '\u{03C4}' => "\\tau",
+        '\u{03C5}' => "\\upsilon",
+        '\u{03D5}' => "\\phi",
+        '\u{03C6}' => "\\varphi",
+        '\u{03C7}' => "\\chi",
+        '\u{03C8}' => "\\psi",
+        '\u{03C9}' => "\\omega",
+        '\u{0391}' => "A",
+        '\u{0392}' => "B",
+        '\u{0393}' => "\\Gamma",
+        '\u{0394}' => "\\Delta",
+        '\u{0395}' => "E",
+        '\u{0396}' => "Z",
+        '\u{0397}' => "H",
+        '\u{0398}' => "\\Theta",
+        '\u{0399}' => "I",
+        '\u{039A}' => "K",
+        '\u{039B}' => "\\Lambda",
+        '\u{039C}' => "M",
+        '\u{039D}' => "N",
+        '\u{039E}' => "\\Xi",
+        '\u{039F}' => "O",
+        '\u{03A0}' => "\\Pi",
+        '\u{03A1}' => "P",
+        '\u{03A3}' => "\\Sigma",
+        '\u{03A4}' => "T",
+        '\u{03A5}' | '\u{03D2}' => "\\Upsilon",
+        '\u{03A6}' => "\\Phi",
+        '\u{03A7}' => "X",
+        '\u{03A8}' => "\\Psi",
+        '\u{03A9}' => "\\Omega",
+        _ => return None,
+    };
+    Some(s)
+}
+
+/// Lengths and factors to two decimals, as written out.
+fn round(x: f32) -> f32 {
+    (x * 100.0).round() / 100.0
+}
+
+fn ends_with_control_word(s: &str) -> bool {
+    let letters = s.len() - s.trim_end_matches(|c: char| c.is_ascii_alphabetic()).len();
+    letters > 0 && s[..s.len() - letters].ends_with('\\')
+}
+
+fn escape_text(s: &str) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '{' | '}' | '#' | '$' | '%' | '&' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Text escaped for `\text`, its runs of CJK characters in CJK environments.
+fn cjk_environments(text: &str, family: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(first) = rest.chars().next() {
+        let cjk = is_cjk(first);
+        let (run, tail) = rest.split_at(rest.find(|c| is_cjk(c) != cjk).unwrap_or(rest.len()));
+        match cjk {
+            true => out.push_str(&format!("\\begin{{CJK}}{{UTF8}}{{{}}}{}\\end{{CJK}}", family, run)),
+            false => out.push_str(&escape_text(run)),
+        }
+        rest = tail;
+    }
+    out
+}
+
+/// Han, kana, hangul and their punctuation and full-width forms.
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{30FF}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}'
+        | '\u{AC00}'..='\u{D7AF}' | '\u{F900}'..='\u{FAFF}' | '\u{FF00}'..='\u{FFEF}'
+        | '\u{20000}'..='\u{2FA1F}')
+}
+
+/// Function names LaTeX knows as operators.
+const FUNCTIONS: &[&str] = &[
+    "arccos", "arcsin", "arctan", "arg", "cos", "cosh", "cot", "coth", "csc", "deg", "det",
+    "dim", "exp", "gcd", "hom", "inf", "ker", "lg", "lim", "liminf", "limsup", "ln", "log",
+    "max", "min", "Pr", "sec", "sin", "sinh", "sup", "tan", "tanh",
+];
+
+fn symbol(c: char) -> Option<&'static str> {
+    let s = match c {
+        '{' => "\\{",
+        '}' => "\\}",
+        '#' => "\\#",
+        '$' => "\\$",
+        '%' => "\\%",
+        '&' => "\\&",
+        '_' => "\\_",
+        '\\' => "\\backslash",
+        '~' => "\\sim",
+        '^' => "\\hat{}",
+        '\u{2212}' => "-",
+        '\u{00B1}' => "\\pm",
+        '\u{2213}' => "\\mp",
+        '\u{00D7}' => "\\times",
+        '\u{00F7}' => "\\div",
+        '\u{00B7}' | '\u{22C5}' => "\\cdot",
+        '\u{2217}' => "*",
+        '\u{2218}' => "\\circ",
+        '\u{2219}' | '\u{2022}' => "\\bullet",
+        '\u{2264}' => "\\le",
+        '\u{2265}' => "\\ge",
+        '\u{2260}' => "\\ne",
+        '\u{2248}' => "\\approx",
+        '\u{2261}' => "\\equiv",
+        '\u{223C}' => "\\sim",
+        '\u{2245}' => "\\cong",
+        '\u{221D}' => "\\propto",
+        '\u{226A}' => "\\ll",
+        '\u{226B}' => "\\gg",
+        '\u{221E}' => "\\infty",
+        '\u{2202}' => "\\partial",
+        '\u{2207}' => "\\nabla",
+        '\u{2208}' => "\\in",
+        '\u{2209}' => "\\notin",
+        '\u{220B}' => "\\ni",
+        '\u{2282}' => "\\subset",
+        '\u{2283}' => "\\supset",
+        '\u{2286}' => "\\subseteq",
+        '\u{2287}' => "\\supseteq",
+        '\u{222A}' => "\\cup",
+        '\u{2229}' => "\\cap",
+        '\u{2205}' => "\\emptyset",
+        '\u{2200}' => "\\forall",
+        '\u{2203}' => "\\exists",
+        '\u{00AC}' => "\\neg",
+        '\u{2227}' => "\\wedge",
+        '\u{2228}' => "\\vee",
+        '\u{2192}' => "\\to",
+        '\u{2190}' => "\\leftarrow",
+        '\u{2194}' => "\\leftrightarrow",
+        '\u{21D2}' => "\\Rightarrow",
+        '\u{21D0}' => "\\Leftarrow",
+        '\u{21D4}' => "\\Leftrightarrow",
+        '\u{2191}' => "\\uparrow",
+        '\u{2193}' => "\\downarrow",
+        '\u{2032}' => "'",
+        '\u{2033}' => "''",
+        '\u{00B0}' => "^{\\circ}",
+        '\u{2026}' => "\\ldots",
+        '\u{22EF}' => "\\cdots",
+        '\u{22EE}' => "\\vdots",
+        '\u{22F1}' => "\\ddots",
+        '\u{2220}' => "\\angle",
+        '\u{22A5}' => "\\perp",
+        '\u{2225}' => "\\parallel",
+        '\u{25B3}' => "\\triangle",
+        '\u{2211}' => "\\sum",
+        '\u{220F}' => "\\prod",
+        '\u{222B}' => "\\int",
+        '\u{222E}' => "\\oint",
+        '\u{221A}' => "\\surd",
+        '\u{2295}' => "\\oplus",
+        '\u{2297}' => "\\otimes",
+        '\u{210F}' => "\\hbar",
+        '\u{2113}' => "\\ell",
+        '\u{211C}' => "\\Re",
+        '\u{2111}' => "\\Im",
+        '\u{2118}' => "\\wp",
+        '\u{2135}' => "\\aleph",
+        _ => return None,
+    };
+    Some(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::constants::variations::{TV_BX_BOTTOM, TV_BX_LEFT, TV_BX_RIGHT, TV_BX_ROUND, TV_BX_TOP, TV_FENCE_L,
+                                              TV_ROOT_NTH, TV_ROOT_SQ, TV_ST_DOWN, TV_ST_HORIZ, TV_ST_UP};
+    use super::super::eqn::{MTEquation, ParseOptions};
+    use super::{BracePileStyle, CjkPolicy, MathDelimiters, TranslateOptions};
+
+    /// MTEF of a header, a font, the equation preferences and a line of the records
+    /// in `body`, closed with the equation.
+    fn mtef(body: &[u8]) -> Vec<u8> {
+        let mut buf = vec![5, 1, 0, 6, 9, b'D', b'S', b'M', b'T', b'6', 0, 0, 17, 1];
+        buf.extend_from_slice(b"Times New Roman\0");
+        buf.extend_from_slice(&[18, 0, 1, 0x22, 0xf0, 0, 3, 1, 0, 0, 1, 1, 1, 0]);
+        buf.extend_from_slice(body);
+        buf.extend_from_slice(&[0, 0]);
+        buf
+    }
+
+    /// LaTeX of the equation of `mtef(body)`.
+    fn equation(body: &[u8]) -> String {
+        MTEquation::from_bytes(&mtef(body)).unwrap().translate().unwrap()
+    }
+
+    /// LaTeX of a root of x, with `degree` (a variable) in the index slot when given.
+    fn root(variation: u16, degree: Option<u8>) -> String {
+        // the root template and its radicand
+        let mut body = vec![3, 0, 10, variation as u8, 0, 1, 0, 2, 0, 131, b'x', 0, 0];
+        match degree {
+            Some(degree) => body.extend_from_slice(&[1, 0, 2, 0, 131, degree, 0, 0]),
+            None => body.extend_from_slice(&[1, 1]),
+        }
+        body.push(0);
+        equation(&body)
+    }
+
+    #[test]
+    fn square_root() {
+        assert_eq!(root(TV_ROOT_SQ, None), "\\sqrt{x}");
+    }
+
+    #[test]
+    fn square_root_ignores_its_index_slot() {
+        assert_eq!(root(TV_ROOT_SQ, Some(b'3')), "\\sqrt{x}");
+    }
+
+    #[test]
+    fn cube_root() {
+        assert_eq!(root(TV_ROOT_NTH, Some(b'3')), "\\sqrt[3]{x}");
+    }
+
+    #[test]
+    fn nth_root() {
+        assert_eq!(root(TV_ROOT_NTH, Some(b'n')), "\\sqrt[n]{x}");
+    }
+
+    /// "中文x", the Chinese in text style and a font named in GBK.
+    fn cjk_text(cjk_policy: CjkPolicy) -> String {
+        let eqn = MTEquation::from_bytes(include_bytes!("../tests/fixtures/cjk_text.mtef")).unwrap();
+        eqn.translate_with(&TranslateOptions { cjk_policy, ..Default::default() }).unwrap()
+    }
+
+    #[test]
+    fn cjk_as_unicode() {
+        assert_eq!(cjk_text(CjkPolicy::Unicode), "\\text{中文}x");
+    }
+
+    #[test]
+    fn cjk_in_environments() {
+        assert_eq!(cjk_text(CjkPolicy::Environment("gbsn".to_string())),
+                   "\\text{\\begin{CJK}{UTF8}{gbsn}中文\\end{CJK}}x");
+    }
+
+    /// LaTeX of x struck through by the strokes of `variation`.
+    fn strike(variation: u8) -> String {
+        equation(&[3, 0, 36, variation, 0, 1, 0, 2, 0, 131, b'x', 0, 0, 0])
+    }
+
+    #[test]
+    fn strikes() {
+        assert_eq!(strike(TV_ST_HORIZ as u8), "\\hcancel{x}");
+        assert_eq!(strike(TV_ST_UP as u8), "\\cancel{x}");
+        assert_eq!(strike((TV_ST_HORIZ | TV_ST_UP | TV_ST_DOWN) as u8), "\\hcancel{\\xcancel{x}}");
+    }
+
+    /// LaTeX of x in a box with the edges of `variation`.
+    fn boxed(variation: u16) -> String {
+        equation(&[3, 0, 37, variation as u8, 0, 1, 0, 2, 0, 131, b'x', 0, 0, 0])
+    }
+
+    #[test]
+    fn boxes() {
+        let full = TV_BX_LEFT | TV_BX_RIGHT | TV_BX_TOP | TV_BX_BOTTOM;
+        assert_eq!(boxed(full), "\\boxed{x}");
+        assert_eq!(boxed(full | TV_BX_ROUND), "\\ovalbox{$x$}");
+        assert_eq!(boxed(TV_BX_TOP | TV_BX_ROUND), "\\overline{x}");
+    }
+
+    /// x over a denominator the fraction lost, parsed with `fill_missing_slots`.
+    fn fraction_without_denominator(fill_missing_slots: bool) -> MTEquation {
+        let buf = mtef(&[3, 0, 11, 0, 0, 1, 0, 2, 0, 131, b'x', 0, 0, 0]);
+        MTEquation::parse_with(buf, &ParseOptions { fill_missing_slots, ..Default::default() }).unwrap()
+    }
+
+    #[test]
+    fn missing_slots() {
+        let eqn = fraction_without_denominator(false);
+        assert_eq!(eqn.translate().unwrap(), "\\frac{x}{}");
+        assert_eq!(eqn.to_plain_text(), "x/");
+        #[cfg(feature = "mathml")]
+        assert!(eqn.to_mathml().unwrap().contains("<mfrac><mi>x</mi><mrow/></mfrac>"));
+    }
+
+    #[test]
+    fn filled_slots() {
+        let eqn = fraction_without_denominator(true);
+        assert_eq!(eqn.translate().unwrap(), "\\frac{x}{\\square}");
+        let options = TranslateOptions { placeholder: "\\Box".to_string(), ..Default::default() };
+        assert_eq!(eqn.translate_with(&options).unwrap(), "\\frac{x}{\\Box}");
+        assert_eq!(eqn.to_plain_text(), "x/\u{25A1}");
+        #[cfg(feature = "mathml")]
+        assert!(eqn.to_mathml().unwrap().contains("<mfrac><mi>x</mi><mi>&#x25A1;</mi></mfrac>"));
+    }
+
+    /// LaTeX of a pile of x and y behind a left brace alone.
+    fn brace_pile(brace_pile_style: BracePileStyle, auto_delimiters: bool) -> String {
+        let pile = [4, 0, 1, 1, 1, 0, 2, 0, 131, b'x', 0, 0, 1, 0, 2, 0, 131, b'y', 0, 0, 0];
+        let body = [&[3, 0, 2, TV_FENCE_L as u8, 0, 1, 0][..], &pile, &[0, 0]].concat();
+        let options = TranslateOptions { brace_pile_style, auto_delimiters, ..Default::default() };
+        MTEquation::from_bytes(&mtef(&body)).unwrap().translate_with(&options).unwrap()
+    }
+
+    #[test]
+    fn brace_piles_as_cases() {
+        assert_eq!(brace_pile(BracePileStyle::Cases, true), "\\begin{cases}x \\\\ y\\end{cases}");
+        // cases size their brace whatever auto_delimiters
+        assert_eq!(brace_pile(BracePileStyle::Cases, false), "\\begin{cases}x \\\\ y\\end{cases}");
+    }
+
+    #[test]
+    fn brace_piles_as_arrays() {
+        assert_eq!(brace_pile(BracePileStyle::Array, true), "\\left\\{\\begin{array}{l}x \\\\ y\\end{array}\\right.");
+        assert_eq!(brace_pile(BracePileStyle::Array, false), "\\Bigl\\{\\begin{array}{l}x \\\\ y\\end{array}");
+    }
+
+    /// x + a/b with `delimiters`, previewed in `max_len` bytes.
+    fn preview(max_len: usize, delimiters: MathDelimiters) -> String {
+        let eqn = MTEquation::from_bytes(&mtef(&[
+            2, 0, 131, b'x', 0, 2, 0, 134, b'+', 0,
+            3, 0, 11, 0, 0, 1, 0, 2, 0, 131, b'a', 0, 0, 1, 0, 2, 0, 131, b'b', 0, 0, 0,
+        ])).unwrap();
+        let options = TranslateOptions { delimiters, ..Default::default() };
+        eqn.translate_preview_with(max_len, &options).unwrap()
+    }
+
+    #[test]
+    fn previews() {
+        assert_eq!(preview(100, MathDelimiters::None), "x+\\frac{a}{b}");
+        // the fraction does not fit whole and is left out
+        assert_eq!(preview(12, MathDelimiters::None), "x+ \\ldots");
+        assert_eq!(preview(5, MathDelimiters::None), "\\ldots");
+    }
+
+    #[test]
+    fn previews_in_delimiters() {
+        assert_eq!(preview(100, MathDelimiters::Inline), "\\(x+\\frac{a}{b}\\)");
+        assert_eq!(preview(16, MathDelimiters::Inline), "\\(x+ \\ldots\\)");
+        assert_eq!(preview(5, MathDelimiters::Display), "\\[\\ldots\\]");
+    }
+
+    /// LaTeX with siunitx of `number` in the number style followed by `unit` in text.
+    fn quantity(number: &str, unit: &str) -> String {
+        let mut body = vec![];
+        for (text, typeface) in [(number, 136), (unit, 129)] {
+            for ch in text.chars() {
+                body.extend_from_slice(&[2, 0, typeface]);
+                body.extend_from_slice(&(ch as u16).to_le_bytes());
+            }
+        }
+        let options = TranslateOptions { siunitx: true, ..Default::default() };
+        MTEquation::from_bytes(&mtef(&body)).unwrap().translate_with(&options).unwrap()
+    }
+
+    #[test]
+    fn quantities() {
+        assert_eq!(quantity("9.81", "m/s"), "\\SI{9.81}{m/s}");
+        assert_eq!(quantity("5", "\u{00B5}m"), "\\SI{5}{\\micro m}");
+        assert_eq!(quantity("5", "\u{03BC}A"), "\\SI{5}{\\micro A}");
+        assert_eq!(quantity("10", "k\u{03A9}"), "\\SI{10}{k\\ohm}");
+        assert_eq!(quantity("90", "\u{00B0}"), "\\SI{90}{\\degree}");
+        assert_eq!(quantity("20", "\u{00B0}C"), "\\SI{20}{\\degreeCelsius}");
+    }
+
+    #[test]
+    fn units_without_numbers_stay_text() {
+        assert_eq!(quantity("", "m"), "\\text{m}");
+        assert_eq!(quantity("", "s"), "\\text{s}");
+    }
+}
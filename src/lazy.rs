@@ -0,0 +1,107 @@
+//! Equations indexed instead of parsed.
+
+use std::convert::TryFrom;
+use std::io::Cursor;
+use std::ops::Range;
+
+use super::constants::RecordType;
+use super::eqn::{decode_header, decode_record, skip_record, DecodeError, MTEquation, MTHeader, MTRecords};
+use super::error::Error;
+
+/// Tag of a record and the bytes it takes in the MTEF, its tag included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedRecord {
+    pub tag: u8,
+    pub span: Range<usize>,
+}
+
+impl IndexedRecord {
+    /// Type of the record, `None` for tags MTEF does not define.
+    pub fn record_type(&self) -> Option<RecordType> {
+        RecordType::try_from(self.tag).ok()
+    }
+}
+
+/// MTEF with its records indexed instead of parsed, for jobs that count or filter
+/// records across large corpora.
+///
+/// `LazyEquation::index` goes through the MTEF once, noting the tag and the byte
+/// range of each record without decoding its payload. Records are decoded one at a
+/// time when asked for, or all at once into an `MTEquation`.
+///
+/// ```ignore
+/// let lazy = LazyEquation::index(&mtef)?;
+/// if lazy.count(RecordType::Tmpl) > 0 {
+///     let eqn = lazy.materialize()?;
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LazyEquation<'a> {
+    mtef: &'a [u8],
+    header: MTHeader,
+    records: Vec<IndexedRecord>,
+}
+
+impl<'a> LazyEquation<'a> {
+    /// Indexes the records of `mtef`, uncompressed MTEF 5 as `MTEquation::parse` takes
+    /// it. Fails when a record is cut short; payloads are only checked when decoded.
+    pub fn index(mtef: &'a [u8]) -> Result<LazyEquation<'a>, Error> {
+        if super::upgrade::needs_upgrade(mtef) {
+            return Err(Error::MalformedMTEF("MTEF older than version 5"));
+        }
+        let mut cur = Cursor::new(mtef);
+        let header = decode_header(&mut cur).map_err(invalid)?;
+        let mut records = vec![];
+        while (cur.position() as usize) < mtef.len() {
+            let start = cur.position() as usize;
+            let tag = skip_record(&mut cur).map_err(invalid)?;
+            records.push(IndexedRecord { tag, span: start..cur.position() as usize });
+        }
+        Ok(LazyEquation { mtef, header, records })
+    }
+
+    pub fn header(&self) -> &MTHeader {
+        &self.header
+    }
+
+    pub fn records(&self) -> &[IndexedRecord] {
+        &self.records
+    }
+
+    /// Records of type `record_type`.
+    pub fn count(&self, record_type: RecordType) -> usize {
+        self.records.iter().filter(|record| record.tag == record_type as u8).count()
+    }
+
+    /// Decodes record `index`. `None` past the last record, and for records the parser
+    /// skips: MATRIX, and RULER records away from their line or pile.
+    pub fn decode(&self, index: usize) -> Result<Option<MTRecords>, Error> {
+        let span = match self.records.get(index) {
+            Some(record) => record.span.clone(),
+            None => return Ok(None),
+        };
+        decode_record(&mut Cursor::new(&self.mtef[span])).map_err(invalid)
+    }
+
+    /// Decodes every record into an equation, as `MTEquation::parse` would.
+    pub fn materialize(&self) -> Result<MTEquation, Error> {
+        let mut records = Vec::with_capacity(self.records.len());
+        let mut spans = Vec::with_capacity(self.records.len());
+        for (index, indexed) in self.records.iter().enumerate() {
+            if let Some(record) = self.decode(index)? {
+                records.push(record);
+                spans.push(indexed.span.clone());
+            }
+        }
+        let mut eqn = MTEquation::from_parts(self.header.clone(), records);
+        eqn.spans = spans;
+        Ok(eqn)
+    }
+}
+
+fn invalid(e: DecodeError) -> Error {
+    match e {
+        DecodeError::Incomplete => Error::MalformedMTEF("truncated MTEF"),
+        DecodeError::Invalid(e) => e,
+    }
+}
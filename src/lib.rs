@@ -0,0 +1,167 @@
+//! mtef-rs reads MathType equations (MTEF) out of OLE objects and translates them.
+//!
+//! Output backends are compiled in through cargo features:
+//!
+//! | feature | default | provides |
+//! | ------- | ------- | -------- |
+//! |std      |yes      |`MTEquation` and everything reading files and objects, needed by every other feature but serde|
+//! |latex    |yes      |`MTEquation::translate`, LaTeX output|
+//! |mathml   |no       |`MTEquation::to_mathml`, presentation MathML output|
+//! |tracing  |no       |spans around extraction, parsing and translation|
+//! |compression|no     |zlib-compressed MTEF in "Equation Native" streams|
+//! |async    |no       |`MTEquation::from_ole_async`, `from_ole_reader_async` over tokio's `AsyncRead`|
+//! |odt      |no       |`container::odt_equations`, equations in OpenDocument files converted from Word|
+//! |embed    |no       |`container::replace_equation_native`, writing equations back into .doc files|
+//! |profile  |no       |`profile::take`, bytes and time spent decoding each record type|
+//! |clipboard|no       |`put_equation_on_clipboard`, equations for pasting into MathType or Word (Windows only)|
+//! |hash     |no       |`MTEquation::content_hash`, cache keys for rendered equations|
+//! |service  |no       |`output::ConversionCache`, a bounded LRU cache of outputs for long-running services|
+//! |serde    |no       |`Serialize` and `Deserialize` for `MTEquation` and its records|
+//! |base64   |no       |`MTEquation::from_base64`, equations embedded in HTML and XML attributes|
+//! |conformance-tests|no|test only, `tests/mathml_conformance.rs` checks the MathML of every fixture for structural validity|
+//!
+//! `ole_to_latex`, `ole_to_mathml` and `bytes_to_latex` convert an equation object in one call.
+//! `MTEquation::from_ole_repaired` recovers what it can of damaged objects.
+//! `MTEquation::filtered_warnings` lists its repairs and unmapped characters, less those an `output::WarningFilter` silences.
+//! `container::html_equations` reads the equations of pages exported by MathType as HTML.
+//! `MTEquation::from_any` reads an equation however it was packaged, `from_hex` (also `FromStr`) from RTF `\objdata` hex.
+//! `container::raw_mtef` pulls the MTEF out of .doc, .docx and RTF files without parsing it.
+//! `parser::LazyEquation` indexes records and decodes them on demand, for counting and filtering.
+//! `parser::minimize_failing_input` cuts an equation breaking the parser down to the records it takes, for bug reports.
+//! `MTEquation::pretty`, also its `Display`, lists the records as an indented tree.
+//! `MTEquation::to_plain_text` writes the equation as a line of Unicode text, for search and previews.
+//! `MTEquation::walk` drives an `output::MtefVisitor` through the equation, for custom exporters;
+//! `walk_mut` and `records_mut` change it before writing it out again.
+//!
+//! `prelude` gathers the names most programs use; `parser`, `output` and `container`
+//! hold everything else public, by what it is for.
+//!
+//! `eqn`, `error` and `constants` are public as they are, for code that works with the
+//! records directly, `ast` for code that works with the tree they make, and `raw` for
+//! code decoding MTEF its own way from the value encodings up.
+//!
+//! Building with `--no-default-features --features std` leaves the parser and the MTEF
+//! writer only. Without `std` the crate is `no_std` and needs `alloc`: `eqn` records,
+//! `constants`, `error` and `parser::StreamParser`, decoding MTEF from memory, are all
+//! there is, and strings outside ASCII are rejected for lack of the GBK tables.
+//! `capabilities::capabilities()` reports what a build supports at runtime.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg_attr(not(feature = "std"), macro_use)]
+extern crate alloc;
+extern crate byteorder;
+#[cfg(feature = "std")]
+extern crate ole;
+#[cfg(feature = "std")]
+extern crate encoding;
+#[cfg(feature = "embed")]
+extern crate cfb;
+#[cfg(any(feature = "compression", feature = "odt"))]
+extern crate flate2;
+#[cfg(feature = "async")]
+extern crate tokio;
+#[cfg(all(feature = "clipboard", windows))]
+extern crate windows_sys;
+#[cfg(feature = "hash")]
+extern crate xxhash_rust;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "base64")]
+extern crate base64;
+
+pub mod eqn;
+mod io;
+mod stream;
+#[cfg(feature = "std")]
+mod lazy;
+#[cfg(feature = "std")]
+mod upgrade;
+#[cfg(feature = "async")]
+mod async_io;
+pub mod error;
+pub mod constants;
+#[cfg(feature = "std")]
+mod compobj;
+#[cfg(feature = "std")]
+mod charmap;
+#[cfg(feature = "std")]
+pub mod ast;
+#[cfg(feature = "std")]
+pub mod capabilities;
+#[cfg(feature = "std")]
+mod writer;
+#[cfg(feature = "std")]
+mod cst;
+#[cfg(feature = "std")]
+pub mod report;
+#[cfg(feature = "std")]
+mod json;
+#[cfg(feature = "std")]
+mod minimize;
+#[cfg(feature = "std")]
+pub mod raw;
+#[cfg(feature = "std")]
+mod bounds;
+#[cfg(feature = "std")]
+mod repair;
+#[cfg(feature = "std")]
+mod warning;
+#[cfg(feature = "std")]
+mod runs;
+#[cfg(feature = "std")]
+mod presentation;
+#[cfg(feature = "std")]
+mod link;
+#[cfg(feature = "std")]
+mod generator;
+#[cfg(feature = "std")]
+mod color;
+#[cfg(feature = "std")]
+mod class;
+#[cfg(feature = "std")]
+mod pretty;
+#[cfg(feature = "std")]
+mod plain;
+#[cfg(feature = "std")]
+mod visit;
+#[cfg(feature = "std")]
+mod html;
+#[cfg(feature = "std")]
+mod extract;
+#[cfg(feature = "std")]
+mod detect;
+#[cfg(feature = "profile")]
+pub mod profile;
+#[cfg(feature = "embed")]
+mod embed;
+#[cfg(feature = "odt")]
+mod odt;
+#[cfg(all(feature = "clipboard", windows))]
+mod clipboard;
+#[cfg(feature = "hash")]
+mod hash;
+#[cfg(feature = "service")]
+mod service;
+#[cfg(feature = "latex")]
+mod latex;
+#[cfg(feature = "mathml")]
+mod mathml;
+#[cfg(any(feature = "latex", feature = "mathml"))]
+mod convert;
+#[cfg(feature = "latex")]
+pub mod parity;
+
+
+/// Public paths of the crate, see each module.
+pub mod prelude;
+pub mod parser;
+#[cfg(feature = "std")]
+pub mod output;
+#[cfg(feature = "std")]
+pub mod container;
+
+#[cfg(any(feature = "latex", feature = "mathml"))]
+pub use convert::*;
+#[cfg(all(feature = "clipboard", windows))]
+pub use clipboard::put_equation_on_clipboard;
@@ -0,0 +1,95 @@
+//! Linked objects
+//!
+//! An object inserted as a link keeps no native data, only monikers naming the file
+//! its content comes from, in its `\x01Ole` stream (MS-OLEDS 2.3.3):
+//!
+//! | field | size | description |
+//! | ----- | ---- | ----------- |
+//! |Version          |4 |0x02000001|
+//! |Flags            |4 |0x00000001 for linked objects|
+//! |LinkUpdateOption |4 |automatic or manual updates|
+//! |Reserved1        |4 |0|
+//! |ReservedMonikerStreamSize |4 |size of the following moniker, 0 for none|
+//! |ReservedMonikerStream |variable| |
+//! |RelativeSourceMonikerStreamSize |4 |linked objects only|
+//! |RelativeSourceMonikerStream |variable|source relative to the container|
+//! |AbsoluteSourceMonikerStreamSize |4 | |
+//! |AbsoluteSourceMonikerStream |variable|absolute source|
+//!
+//! Moniker streams are a CLSID followed by the moniker's data. Sources are file
+//! monikers, or composite monikers starting with one (the file, then the item in it).
+
+use std::io::{Cursor, Read};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use super::eqn::read_entry;
+
+/// Name of the stream holding the object's link information.
+const OLE: &str = "\u{1}Ole";
+const LINKED: u32 = 0x0000_0001;
+/// {00000303-0000-0000-C000-000000000046}
+const CLSID_FILE_MONIKER: [u8; 16] = [0x03, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46];
+/// {00000309-0000-0000-C000-000000000046}
+const CLSID_COMPOSITE_MONIKER: [u8; 16] = [0x09, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46];
+
+/// Path of the file a linked object takes its content from, absolute when known.
+/// `None` for embedded objects.
+pub(crate) fn link_source(reader: &ole::Reader) -> Option<String> {
+    let entry = reader.iterate().find(|entry| entry.name() == OLE)?;
+    parse_ole_stream(&read_entry(reader, entry).ok()?)
+}
+
+fn parse_ole_stream(buf: &[u8]) -> Option<String> {
+    let mut cur = Cursor::new(buf);
+    cur.read_u32::<LittleEndian>().ok()?;
+    if LINKED != LINKED & cur.read_u32::<LittleEndian>().ok()? {
+        return None;
+    }
+    cur.set_position(cur.position() + 8);
+    let mut moniker = || -> Option<Vec<u8>> {
+        let size = cur.read_u32::<LittleEndian>().ok()?;
+        let mut stream = vec![0; size as usize];
+        cur.read_exact(&mut stream).ok()?;
+        Some(stream)
+    };
+    moniker()?;
+    let relative = moniker();
+    let absolute = moniker();
+    absolute.as_deref().and_then(moniker_path)
+        .or_else(|| relative.as_deref().and_then(moniker_path))
+}
+
+/// Path named by a file moniker, or by the file moniker a composite starts with.
+fn moniker_path(stream: &[u8]) -> Option<String> {
+    let mut cur = Cursor::new(stream);
+    let mut clsid = [0u8; 16];
+    cur.read_exact(&mut clsid).ok()?;
+    if clsid == CLSID_COMPOSITE_MONIKER {
+        // number of monikers, the first one follows
+        cur.read_u32::<LittleEndian>().ok()?;
+        cur.read_exact(&mut clsid).ok()?;
+    }
+    if clsid != CLSID_FILE_MONIKER {
+        return None;
+    }
+    // anti-moniker count, then the ANSI path, null-terminated
+    cur.read_u16::<LittleEndian>().ok()?;
+    let len = cur.read_u32::<LittleEndian>().ok()?;
+    let mut ansi = vec![0; len as usize];
+    cur.read_exact(&mut ansi).ok()?;
+    let ansi: String = ansi.iter().take_while(|&&b| b != 0).map(|&b| b as char).collect();
+    // end server, version and reserved fields, then the Unicode path if any
+    cur.set_position(cur.position() + 24);
+    let unicode = (|| {
+        let size = cur.read_u32::<LittleEndian>().ok().filter(|&size| size > 0)?;
+        let bytes = cur.read_u32::<LittleEndian>().ok()?;
+        cur.read_u16::<LittleEndian>().ok()?;
+        let mut units = vec![0u16; bytes.min(size) as usize / 2];
+        cur.read_u16_into::<LittleEndian>(&mut units).ok()?;
+        String::from_utf16(&units).ok()
+    })();
+    unicode.or(Some(ansi)).filter(|path| !path.is_empty())
+}
@@ -1,15 +1,200 @@
-extern crate byteorder;
-extern crate ole;
-extern crate encoding;
+//! The `mtef` command: shows, converts, repairs and reports on equation objects
+//! through the library.
 
-mod eqn;
-mod error;
-mod constants;
+use mtef::{container, eqn, report};
+#[cfg(any(feature = "latex", feature = "mathml"))]
+use mtef::output;
+#[cfg(any(feature = "latex", feature = "mathml"))]
+use mtef::capabilities;
+#[cfg(feature = "latex")]
+use mtef::parity;
 
+const USAGE: &str = "usage: mtef [<file>]
+       mtef convert <file>... [--to latex,mathml] [--lenient] [--ignore <warning>,...]
+       mtef report <dir> [--json | --html] [-o <file>]
+       mtef repair <file>
+       mtef extract-raw <container> -o <dir>
+       mtef parity <dir> [--min <similarity>]";
 
 fn main() {
-    let eqn = eqn::MTEquation::from_ole("assets/oleObject1.bin").unwrap();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        #[cfg(any(feature = "latex", feature = "mathml"))]
+        Some("convert") => convert(&args[1..]),
+        Some("report") => report(&args[1..]),
+        Some("extract-raw") => extract_raw(&args[1..]),
+        Some("repair") => args.get(1).map_or(Err(USAGE.to_string()), |path| repair(path)),
+        #[cfg(feature = "latex")]
+        Some("parity") => parity(&args[1..]),
+        Some("-h") | Some("--help") => Err(USAGE.to_string()),
+        Some(path) => show(path),
+        None => show("assets/oleObject1.bin"),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(2);
+    }
+}
+
+fn show(path: &str) -> Result<(), String> {
+    let eqn = eqn::MTEquation::from_ole(path).map_err(|e| format!("{:#}", e))?;
+    print!("{}", eqn);
+    #[cfg(feature = "latex")]
+    println!("{}", eqn.translate().map_err(|e| format!("{:#}", e))?);
+    #[cfg(feature = "mathml")]
+    println!("{}", eqn.to_mathml().map_err(|e| format!("{:#}", e))?);
+    Ok(())
+}
+
+/// `mtef convert <file>... --to latex,mathml`: every input parsed once and written
+/// next to itself in each target, `.tex` and `.mml`; all compiled targets by default.
+/// Failed inputs are reported and skipped. `--lenient` converts what decodes of
+/// damaged inputs; warnings go to stderr, less the categories, character codes and
+/// record types given to `--ignore` (see `output::WarningFilter`).
+#[cfg(any(feature = "latex", feature = "mathml"))]
+fn convert(args: &[String]) -> Result<(), String> {
+    let capabilities = capabilities::capabilities();
+    let mut files = vec![];
+    let mut targets: Vec<&str> = capabilities.output_targets.clone();
+    let mut parse_options = eqn::ParseOptions::default();
+    let mut filter = output::WarningFilter::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--to" => targets = args.next().ok_or(USAGE)?.split(',').collect(),
+            "--lenient" => parse_options.mode = eqn::ParseMode::Lenient,
+            "--ignore" => {
+                let ignored: output::WarningFilter = args.next().ok_or(USAGE)?.parse()?;
+                filter.categories.extend(ignored.categories);
+                filter.codes.extend(ignored.codes);
+                filter.record_types.extend(ignored.record_types);
+            }
+            _ if !arg.starts_with('-') => files.push(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    if files.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    if let Some(target) = targets.iter().find(|t| !capabilities.supports_target(t)) {
+        return Err(format!("output target {} is not compiled in", target));
+    }
+    let mut failed = 0;
+    for file in &files {
+        let converted = eqn::MTEquation::from_ole_with(file, &parse_options).and_then(|eqn| {
+            for warning in eqn.filtered_warnings(&filter, None) {
+                eprintln!("{}: warning: {}", file, warning);
+            }
+            for target in &targets {
+                let (out, extension) = match *target {
+                    #[cfg(feature = "latex")]
+                    "latex" => (eqn.translate()?, "tex"),
+                    #[cfg(feature = "mathml")]
+                    "mathml" => (eqn.to_mathml()?, "mml"),
+                    _ => continue,
+                };
+                std::fs::write(std::path::Path::new(file).with_extension(extension), out)?;
+            }
+            Ok(())
+        });
+        if let Err(e) = converted {
+            eprintln!("{}: {:#}", file, e);
+            failed += 1;
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        _ => Err(format!("{} of {} files failed", failed, files.len())),
+    }
+}
+
+/// `mtef repair <file>`: the repairs a damaged object needs, on stderr, then the
+/// equation recovered from it.
+fn repair(path: &str) -> Result<(), String> {
+    let (eqn, report) = eqn::MTEquation::from_ole_repaired(path).map_err(|e| format!("{:#}", e))?;
+    for repair in &report.repairs {
+        eprintln!("repaired: {}", repair);
+    }
     println!("{:?}", eqn);
-    let latex = eqn.translate();
-    println!("{:?}", latex);
+    #[cfg(feature = "latex")]
+    println!("{}", eqn.translate().map_err(|e| format!("{:#}", e))?);
+    #[cfg(feature = "mathml")]
+    println!("{}", eqn.to_mathml().map_err(|e| format!("{:#}", e))?);
+    Ok(())
+}
+
+/// `mtef extract-raw <container> -o <dir>`: the MTEF of every equation of a .doc,
+/// .docx or RTF file, unparsed, written to `<dir>/equation1.mtef`...
+fn extract_raw(args: &[String]) -> Result<(), String> {
+    let mut container = None;
+    let mut dir = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => dir = Some(args.next().ok_or(USAGE)?),
+            _ if container.is_none() && !arg.starts_with('-') => container = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let (container, dir) = (container.ok_or(USAGE)?, std::path::Path::new(dir.ok_or(USAGE)?));
+    let payloads = container::raw_mtef(container).map_err(|e| format!("{:#}", e))?;
+    std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    for (i, mtef) in payloads.iter().enumerate() {
+        std::fs::write(dir.join(format!("equation{}.mtef", i + 1)), mtef).map_err(|e| e.to_string())?;
+    }
+    eprintln!("{} equations extracted", payloads.len());
+    Ok(())
+}
+
+/// `mtef report <dir>`: aggregate report over a corpus, JSON by default.
+fn report(args: &[String]) -> Result<(), String> {
+    let mut dir = None;
+    let mut html = false;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => html = false,
+            "--html" => html = true,
+            "-o" => output = Some(args.next().ok_or(USAGE)?),
+            _ if dir.is_none() && !arg.starts_with('-') => dir = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let report = report::Report::collect(std::path::Path::new(dir.ok_or(USAGE)?));
+    let out = match html {
+        true => report.to_html(),
+        false => report.to_json(),
+    };
+    match output {
+        Some(path) => std::fs::write(path, out).map_err(|e| e.to_string()),
+        None => {
+            print!("{}", out);
+            Ok(())
+        }
+    }
+}
+
+/// `mtef parity <dir>`: our LaTeX against the reference fixtures of `dir`, failing
+/// when the mean similarity is under `--min`.
+#[cfg(feature = "latex")]
+fn parity(args: &[String]) -> Result<(), String> {
+    let mut dir = None;
+    let mut min = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--min" => min = Some(args.next().and_then(|m| m.parse::<f64>().ok()).ok_or(USAGE)?),
+            _ if dir.is_none() && !arg.starts_with('-') => dir = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let report = parity::ParityReport::collect(std::path::Path::new(dir.ok_or(USAGE)?));
+    print!("{}", report.to_json());
+    match min {
+        Some(min) if report.similarity() < min => {
+            Err(format!("similarity {:.3} under {:.3}", report.similarity(), min))
+        }
+        _ => Ok(()),
+    }
 }
@@ -0,0 +1,610 @@
+//! MathML output backend, enabled by the `mathml` feature.
+//!
+//! Presentation MathML 3. Explicit spaces keep their width as `<mspace>`, lines split
+//! by tab characters become the cells of an `<mtable>` aligned after the line's ruler,
+//! and so do the lines of piles.
+
+use super::ast::{Arrow, ArrowKind, BigOp, BigOpKind, Boxed, Char, Fence, FenceKind, HBrace, Line, Node, Pile, Scripts, Strike, Tmpl};
+use super::charmap::{self, MappedChar};
+use super::constants::embellishments::*;
+use super::constants::pile_align::*;
+use super::constants::selectors::*;
+use super::constants::tab_stops::*;
+use super::constants::typeface::*;
+use super::constants::variations::*;
+use super::eqn::{EquationLine, MTChar, MTEquation, MTRuler, DEFAULT_LINE_SPACING};
+use super::error::Error;
+use std::collections::BTreeMap;
+
+/// Options of the MathML backend.
+#[derive(Debug, Clone, Default)]
+pub struct MathmlOptions {
+    /// Attributes of characters in a MathType style, by style (`FN_VECTOR`...). They
+    /// replace the `mathvariant` the backend would pick, e.g. bold for vectors.
+    pub styles: BTreeMap<u8, FontAttributes>,
+    /// Attributes of characters by the name of their font, e.g. "Euclid Math One",
+    /// for characters in a style without an entry in `styles`.
+    pub fonts: BTreeMap<String, FontAttributes>,
+}
+
+/// Font attributes of token elements (`<mi>`, `<mn>`, `<mo>`, `<mtext>`).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FontAttributes {
+    pub mathvariant: Option<String>,
+    /// a CSS font-family list, e.g. "STIX Two Math"
+    pub fontfamily: Option<String>,
+}
+
+impl MTEquation {
+    /// The equation as a `<math>` element.
+    pub fn to_mathml(&self) -> Result<String, Error> {
+        self.to_mathml_with(&MathmlOptions::default())
+    }
+
+    pub fn to_mathml_with(&self, options: &MathmlOptions) -> Result<String, Error> {
+        let mx = Mx::new(self, options);
+        let lines: Vec<EquationLine> = self.lines().collect();
+        let lines: Vec<&Line> = lines.iter().map(|line| &line.line).collect();
+        Ok(mx.math(&mx.lines(&lines)))
+    }
+}
+
+impl EquationLine<'_> {
+    pub fn to_mathml(&self) -> Result<String, Error> {
+        self.to_mathml_with(&MathmlOptions::default())
+    }
+
+    pub fn to_mathml_with(&self, options: &MathmlOptions) -> Result<String, Error> {
+        let mx = Mx::new(self.eqn, options);
+        Ok(mx.math(&mx.lines(&[&self.line])))
+    }
+}
+
+/// Template selectors `Mx::tmpl` translates specifically, keep in sync with it.
+pub(crate) const TRANSLATED_SELECTORS: &[u8] = &[
+    TM_ANGLE, TM_PAREN, TM_BRACE, TM_BRACK, TM_BAR, TM_DBAR, TM_FLOOR, TM_CEILING,
+    TM_ROOT, TM_FRACT, TM_HBRACE, TM_HBRACK, TM_ARROW, TM_STRIKE, TM_BOX,
+    TM_INTEG, TM_SUM, TM_PROD, TM_COPROD, TM_UNION, TM_INTER, TM_INTOP, TM_SUMOP, TM_LIM,
+    TM_SUB, TM_SUP, TM_SUBSUP,
+];
+
+/// Translation of an equation. Every method returns a single element, so that
+/// scripts can take the previous one as their base.
+struct Mx<'o> {
+    eqn: &'o MTEquation,
+    options: &'o MathmlOptions,
+    /// full size of the equation in points, for spaces given in points
+    full_size: f32,
+}
+
+impl<'o> Mx<'o> {
+    fn new(eqn: &'o MTEquation, options: &'o MathmlOptions) -> Mx<'o> {
+        Mx { eqn, options, full_size: eqn.full_size().unwrap_or(12.0) }
+    }
+
+    fn math(&self, body: &str) -> String {
+        let display = match self.eqn.m_inline {
+            0 => "block",
+            _ => "inline",
+        };
+        format!("<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"{}\">{}</math>", display, body)
+    }
+
+    /// A single line without tabs is a row. Several lines, or lines with tabs, are
+    /// the rows of a table, its columns aligned after the first ruler found.
+    fn lines(&self, lines: &[&Line]) -> String {
+        let ruler = lines.iter().find_map(|line| line.line.ruler.as_ref());
+        if let ([line], None) = (lines, ruler) {
+            if !line.nodes.iter().any(is_tab) {
+                return group(self.row(&line.nodes));
+            }
+        }
+        self.table(lines, ruler, None, "")
+    }
+
+    /// Pile lines are aligned as the pile says, unless it has a ruler. Piles hanging
+    /// from their top or bottom line are aligned on its baseline, line spacing other
+    /// than MathType's default is kept as `rowspacing`.
+    fn pile(&self, pile: &Pile) -> String {
+        let align = match pile.pile.halign {
+            PHA_LEFT => "left",
+            PHA_RIGHT => "right",
+            _ => "center",
+        };
+        let mut attributes = match pile.pile.valign {
+            PVA_TOP => " align=\"baseline 1\"".to_string(),
+            PVA_BOTTOM => " align=\"baseline -1\"".to_string(),
+            _ => String::new(),
+        };
+        // the space between lines is their distance less about a line's height
+        let spacing = self.eqn.line_spacing();
+        let extra = pile.extra_spacing(self.full_size * spacing);
+        if spacing != DEFAULT_LINE_SPACING || extra.iter().any(|&e| e != 0.0) {
+            let rows: Vec<String> = extra.iter().skip(1)
+                .map(|e| em((spacing - 1.0 + e / self.full_size).max(0.0)))
+                .collect();
+            if !rows.is_empty() {
+                attributes.push_str(&format!(" rowspacing=\"{}\"", rows.join(" ")));
+            }
+        }
+        let lines: Vec<&Line> = pile.lines.iter().collect();
+        self.table(&lines, pile.pile.ruler.as_ref(), Some(align), &attributes)
+    }
+
+    /// `<mtable>` of the lines, a cell per tab separated part. Columns are aligned
+    /// after the ruler, or all as `align`; `attributes` are added as they are.
+    fn table(&self, lines: &[&Line], ruler: Option<&MTRuler>, align: Option<&str>, attributes: &str) -> String {
+        let rows: Vec<Vec<&[Node]>> = lines.iter().map(|line| line.nodes.split(is_tab).collect()).collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut out = match (ruler, align) {
+            (Some(ruler), _) => format!("<mtable columnalign=\"{}\"{}>", column_align(ruler, columns).join(" "), attributes),
+            (None, Some(align)) => format!("<mtable columnalign=\"{}\"{}>", align, attributes),
+            (None, None) => format!("<mtable{}>", attributes),
+        };
+        for row in &rows {
+            out.push_str("<mtr>");
+            for cell in row {
+                out.push_str(&format!("<mtd>{}</mtd>", self.row(cell).concat()));
+            }
+            out.push_str("</mtr>");
+        }
+        out.push_str("</mtable>");
+        out
+    }
+
+    fn slot(&self, slot: Option<&Line>) -> String {
+        match slot {
+            Some(line) => group(self.row(&line.nodes)),
+            None => "<mrow/>".to_string(),
+        }
+    }
+
+    /// Elements of a line. Scripts wrap the element before them, prescripts the one after.
+    fn row(&self, nodes: &[Node]) -> Vec<String> {
+        let mut items = vec![];
+        // a function name waits for its scripts before the function application
+        let mut apply = false;
+        let mut prescripts: Option<String> = None;
+        let mut i = 0;
+        while i < nodes.len() {
+            // tabs only separate the cells of top-level lines
+            if is_tab(&nodes[i]) {
+                i += 1;
+                continue;
+            }
+            let (item, n) = match &nodes[i] {
+                Node::Char(c) => match typeface(c.ch) {
+                    Some(FN_SPACE) => {
+                        let run = char_run(&nodes[i..], Some(FN_SPACE));
+                        (Some(self.space(&run)), run.len())
+                    }
+                    face @ Some(FN_FUNCTION) | face @ Some(FN_TEXT) => {
+                        let run = char_run(&nodes[i..], face);
+                        let text = escape(&run.iter().filter_map(|ch| unicode(ch)).collect::<String>());
+                        let attributes = self.attributes(run[0], None);
+                        match face {
+                            Some(FN_FUNCTION) => (Some(format!("<mi{}>{}</mi>", attributes, text)), run.len()),
+                            _ => (Some(format!("<mtext{}>{}</mtext>", attributes, text)), run.len()),
+                        }
+                    }
+                    _ => match number(&nodes[i..]) {
+                        Some((number, n)) => (Some(format!("<mn>{}</mn>", number)), n),
+                        None => (self.embellished(c), 1),
+                    },
+                },
+                Node::Tmpl(t) => match t.scripts() {
+                    Some(scripts) if scripts.precedes => {
+                        prescripts = Some(self.prescripts(&scripts));
+                        i += 1;
+                        continue;
+                    }
+                    Some(scripts) => {
+                        let base = items.pop().unwrap_or_else(|| "<mrow/>".to_string());
+                        items.push(self.scripts(base, &scripts));
+                        i += 1;
+                        continue;
+                    }
+                    None => (Some(self.tmpl(t)), 1),
+                },
+                Node::Pile(p) => (Some(self.pile(p)), 1),
+                Node::Marker(_) => (None, 1),
+                Node::Placeholder => (Some("<mi>&#x25A1;</mi>".to_string()), 1),
+            };
+            if let Some(mut item) = item {
+                if apply {
+                    items.push("<mo>&#x2061;</mo>".to_string());
+                }
+                if let Some(prescripts) = prescripts.take() {
+                    item = format!("<mmultiscripts>{}{}</mmultiscripts>", item, prescripts);
+                }
+                apply = matches!(&nodes[i], Node::Char(c) if typeface(c.ch) == Some(FN_FUNCTION));
+                items.push(item);
+            }
+            i += n;
+        }
+        items
+    }
+
+    /// A run of explicit spaces as one `<mspace>`.
+    fn space(&self, run: &[&MTChar]) -> String {
+        let width: f32 = run.iter().map(|ch| self.space_width(ch.mtcode.unwrap_or(0))).sum();
+        format!("<mspace width=\"{}\"/>", em(width))
+    }
+
+    /// Width in em of MathType's explicit spaces (MT Extra private use codes),
+    /// and of the Unicode spaces typed in the space style.
+    fn space_width(&self, code: u16) -> f32 {
+        match code {
+            // zero space
+            0xEF00 => 0.0,
+            // 1-point space
+            0xEF01 => 1.0 / self.full_size,
+            // thin space
+            0xEF02 | 0x2009 => 1.0 / 6.0,
+            // thick space
+            0xEF04 => 5.0 / 18.0,
+            // em space
+            0xEF05 | 0x2003 => 1.0,
+            0x0020 | 0x00A0 => 0.25,
+            0x2002 => 0.5,
+            0x200A => 1.0 / 12.0,
+            0x200B => 0.0,
+            // unknown spaces are thin, as in the LaTeX output
+            _ => 1.0 / 6.0,
+        }
+    }
+
+    /// Embellishments wrap the character in list order, the first one innermost.
+    fn embellished(&self, c: &Char) -> Option<String> {
+        let mut out = self.char(c.ch)?;
+        for embell in &c.embells {
+            out = match embellishment(embell.embell) {
+                Some(Mark::Over(mark)) => format!("<mover accent=\"true\">{}<mo>{}</mo></mover>", out, mark),
+                Some(Mark::Under(mark)) => format!("<munder accentunder=\"true\">{}<mo>{}</mo></munder>", out, mark),
+                Some(Mark::Prime(prime)) => format!("<msup>{}<mo>{}</mo></msup>", out, prime),
+                Some(Mark::BackPrime) => format!("<mmultiscripts>{}<mprescripts/><none/><mo>\u{2035}</mo></mmultiscripts>", out),
+                Some(Mark::Enclose(notation)) => format!("<menclose notation=\"{}\">{}</menclose>", notation, out),
+                None => out,
+            };
+        }
+        Some(out)
+    }
+
+    fn char(&self, ch: &MTChar) -> Option<String> {
+        let c = match charmap::resolve(self.eqn, ch, None)? {
+            MappedChar::Char(c) => c,
+            MappedChar::Latex(_) => return None,
+        };
+        let face = typeface(ch);
+        // characters in the Greek typefaces may carry Symbol font positions
+        let c = match face {
+            Some(FN_LCGREEK) | Some(FN_UCGREEK) if c.is_ascii_alphabetic() => charmap::symbol_font(c as u8).unwrap_or(c),
+            _ => c,
+        };
+        let text = escape(c.encode_utf8(&mut [0; 4]));
+        let (tag, variant) = match face {
+            _ if c.is_ascii_digit() => ("mn", None),
+            Some(FN_VECTOR) if c.is_alphabetic() => ("mi", Some("bold")),
+            Some(FN_UCGREEK) if c.is_alphabetic() => ("mi", Some("normal")),
+            _ if c.is_alphabetic() => ("mi", None),
+            _ => ("mo", None),
+        };
+        Some(format!("<{0}{1}>{2}</{0}>", tag, self.attributes(ch, variant), text))
+    }
+
+    /// `mathvariant` and `fontfamily` of a token element for `ch`, from the options
+    /// for its style or else its font. `variant` is the backend's own choice.
+    fn attributes(&self, ch: &MTChar, variant: Option<&str>) -> String {
+        let configured = typeface(ch).and_then(|face| self.options.styles.get(&face))
+            .or_else(|| self.options.fonts.get(self.eqn.font_name(ch.typeface)?));
+        let (variant, family) = match configured {
+            Some(attributes) => (attributes.mathvariant.as_deref(), attributes.fontfamily.as_deref()),
+            None => (variant, None),
+        };
+        let mut out = String::new();
+        if let Some(variant) = variant {
+            out.push_str(&format!(" mathvariant=\"{}\"", escape(variant)));
+        }
+        if let Some(family) = family {
+            out.push_str(&format!(" fontfamily=\"{}\"", escape(family)));
+        }
+        out
+    }
+
+    fn tmpl(&self, t: &Tmpl) -> String {
+        let translated = match t.tmpl.selector {
+            TM_ANGLE | TM_PAREN | TM_BRACE | TM_BRACK | TM_BAR | TM_DBAR | TM_FLOOR | TM_CEILING => {
+                t.fence().map(|fence| self.fence(&fence))
+            }
+            TM_ROOT => Some(self.root(t)),
+            TM_FRACT => Some(format!("<mfrac>{}{}</mfrac>", self.slot(t.slot(0)), self.slot(t.slot(1)))),
+            TM_HBRACE | TM_HBRACK => t.hbrace().map(|hbrace| self.hbrace(&hbrace)),
+            TM_ARROW => t.arrow().map(|arrow| self.arrow(&arrow)),
+            TM_STRIKE => t.strike().map(|strike| self.strike(&strike)),
+            TM_BOX => t.boxed().map(|boxed| self.boxed(&boxed)),
+            TM_INTEG | TM_SUM | TM_PROD | TM_COPROD | TM_UNION | TM_INTER | TM_INTOP | TM_SUMOP => {
+                t.big_op().map(|big_op| self.big_op(&big_op))
+            }
+            TM_LIM => t.limit().map(|limit| self.limits(self.slot(limit.name), limit.lower, limit.upper, true)),
+            _ => None,
+        };
+        // not translated specifically yet, keep the content
+        translated.unwrap_or_else(|| {
+            group(t.slots.iter().map(|slot| self.slot(Some(slot))).collect())
+        })
+    }
+
+    /// Radicals: the first slot is the radicand, nth roots carry their degree in the second one.
+    fn root(&self, t: &Tmpl) -> String {
+        match t.slot(1).filter(|_| TV_ROOT_NTH == t.tmpl.variation & TV_ROOT_NTH) {
+            Some(degree) => format!("<mroot>{}{}</mroot>", self.slot(t.slot(0)), self.slot(Some(degree))),
+            None => format!("<msqrt>{}</msqrt>", self.slot(t.slot(0))),
+        }
+    }
+
+    /// The operator with its limits, followed by the main slot.
+    fn big_op(&self, big_op: &BigOp) -> String {
+        // the operator character is optional, the template says which operator it is
+        let op = big_op.op.and_then(|op| self.char(op)).unwrap_or_else(|| {
+            let c = match big_op.kind {
+                BigOpKind::Integral { count, contour: false } => ['\u{222B}', '\u{222C}', '\u{222D}'][count.min(3) as usize - 1],
+                BigOpKind::Integral { count, contour: true } => ['\u{222E}', '\u{222F}', '\u{2230}'][count.min(3) as usize - 1],
+                BigOpKind::Sum => '\u{2211}',
+                BigOpKind::Prod => '\u{220F}',
+                BigOpKind::Coprod => '\u{2210}',
+                BigOpKind::Union => '\u{22C3}',
+                BigOpKind::Inter => '\u{22C2}',
+                BigOpKind::Other => return "<mrow/>".to_string(),
+            };
+            format!("<mo>{}</mo>", c)
+        });
+        let op = self.limits(op, big_op.lower, big_op.upper, big_op.over_under);
+        match big_op.body {
+            Some(body) => format!("<mrow>{}{}</mrow>", op, self.slot(Some(body))),
+            None => op,
+        }
+    }
+
+    /// Limits below and above `base`, or beside it as scripts.
+    fn limits(&self, base: String, lower: Option<&Line>, upper: Option<&Line>, over_under: bool) -> String {
+        let (both, below, above) = match over_under {
+            true => ("munderover", "munder", "mover"),
+            false => ("msubsup", "msub", "msup"),
+        };
+        match (lower, upper) {
+            (Some(lower), Some(upper)) => format!("<{0}>{1}{2}{3}</{0}>", both, base, self.slot(Some(lower)), self.slot(Some(upper))),
+            (Some(lower), None) => format!("<{0}>{1}{2}</{0}>", below, base, self.slot(Some(lower))),
+            (None, Some(upper)) => format!("<{0}>{1}{2}</{0}>", above, base, self.slot(Some(upper))),
+            (None, None) => base,
+        }
+    }
+
+    fn scripts(&self, base: String, scripts: &Scripts) -> String {
+        self.limits(base, scripts.sub, scripts.sup, false)
+    }
+
+    /// `<mprescripts/>` and the script pair following the base of an `<mmultiscripts>`.
+    fn prescripts(&self, scripts: &Scripts) -> String {
+        let script = |slot: Option<&Line>| match slot {
+            Some(_) => self.slot(slot),
+            None => "<none/>".to_string(),
+        };
+        format!("<mprescripts/>{}{}", script(scripts.sub), script(scripts.sup))
+    }
+
+    /// The brace is an accent of the body, the label a limit of both.
+    fn hbrace(&self, hbrace: &HBrace) -> String {
+        let brace = match (hbrace.top, hbrace.bracket) {
+            (true, false) => '\u{23DE}',
+            (false, false) => '\u{23DF}',
+            (true, true) => '\u{23B4}',
+            (false, true) => '\u{23B5}',
+        };
+        let (tag, accent) = match hbrace.top {
+            true => ("mover", "accent"),
+            false => ("munder", "accentunder"),
+        };
+        let braced = format!("<{0} {1}=\"true\">{2}<mo stretchy=\"true\">{3}</mo></{0}>", tag, accent, self.slot(hbrace.body), brace);
+        match hbrace.label {
+            Some(label) => format!("<{0}>{1}{2}</{0}>", tag, braced, self.slot(Some(label))),
+            None => braced,
+        }
+    }
+
+    fn arrow(&self, arrow: &Arrow) -> String {
+        let mo = format!("<mo stretchy=\"true\">{}</mo>", match arrow.kind {
+            ArrowKind::Right => '\u{2192}',
+            ArrowKind::Left => '\u{2190}',
+            ArrowKind::LeftRight => '\u{2194}',
+            ArrowKind::Double => '\u{21C4}',
+            ArrowKind::Harpoons => '\u{21CC}',
+        });
+        match (arrow.bottom, arrow.top) {
+            (Some(bottom), Some(top)) => format!("<munderover>{}{}{}</munderover>", mo, self.slot(Some(bottom)), self.slot(Some(top))),
+            (Some(bottom), None) => format!("<munder>{}{}</munder>", mo, self.slot(Some(bottom))),
+            (None, Some(top)) => format!("<mover>{}{}</mover>", mo, self.slot(Some(top))),
+            (None, None) => mo,
+        }
+    }
+
+    fn strike(&self, strike: &Strike) -> String {
+        let notation: Vec<&str> = [
+            (strike.horizontal, "horizontalstrike"),
+            (strike.up, "updiagonalstrike"),
+            (strike.down, "downdiagonalstrike"),
+        ].iter().filter(|(drawn, _)| *drawn).map(|(_, notation)| *notation).collect();
+        self.enclose(&notation, strike.body)
+    }
+
+    /// Full boxes are `box` or `roundedbox`, partial ones list their edges.
+    fn boxed(&self, boxed: &Boxed) -> String {
+        let notation: Vec<&str> = match (boxed.is_full(), boxed.round) {
+            (true, true) => vec!["roundedbox"],
+            (true, false) => vec!["box"],
+            _ => [
+                (boxed.left, "left"),
+                (boxed.right, "right"),
+                (boxed.top, "top"),
+                (boxed.bottom, "bottom"),
+            ].iter().filter(|(drawn, _)| *drawn).map(|(_, notation)| *notation).collect(),
+        };
+        self.enclose(&notation, boxed.body)
+    }
+
+    fn enclose(&self, notation: &[&str], body: Option<&Line>) -> String {
+        match notation.is_empty() {
+            true => self.slot(body),
+            false => format!("<menclose notation=\"{}\">{}</menclose>", notation.join(" "), self.slot(body)),
+        }
+    }
+
+    fn fence(&self, fence: &Fence) -> String {
+        let (open, close) = match fence.kind {
+            FenceKind::Angle => ('\u{27E8}', '\u{27E9}'),
+            FenceKind::Paren => ('(', ')'),
+            FenceKind::Brace => ('{', '}'),
+            FenceKind::Brack => ('[', ']'),
+            FenceKind::Bar => ('|', '|'),
+            FenceKind::DBar => ('\u{2016}', '\u{2016}'),
+            FenceKind::Floor => ('\u{230A}', '\u{230B}'),
+            FenceKind::Ceiling => ('\u{2308}', '\u{2309}'),
+        };
+        let mut out = String::from("<mrow>");
+        if fence.left {
+            out.push_str(&format!("<mo fence=\"true\" form=\"prefix\">{}</mo>", open));
+        }
+        out.push_str(&self.slot(fence.body));
+        if fence.right {
+            out.push_str(&format!("<mo fence=\"true\" form=\"postfix\">{}</mo>", close));
+        }
+        out.push_str("</mrow>");
+        out
+    }
+}
+
+/// How an embellishment is drawn around its character.
+enum Mark {
+    Over(char),
+    Under(char),
+    Prime(char),
+    BackPrime,
+    Enclose(&'static str),
+}
+
+fn embellishment(embell: u8) -> Option<Mark> {
+    let mark = match embell {
+        EMB_1DOT => Mark::Over('\u{02D9}'),
+        EMB_2DOT => Mark::Over('\u{00A8}'),
+        EMB_3DOT => Mark::Over('\u{20DB}'),
+        EMB_4DOT => Mark::Over('\u{20DC}'),
+        EMB_1PRIME => Mark::Prime('\u{2032}'),
+        EMB_2PRIME => Mark::Prime('\u{2033}'),
+        EMB_3PRIME => Mark::Prime('\u{2034}'),
+        EMB_BPRIME => Mark::BackPrime,
+        EMB_TILDE => Mark::Over('\u{02DC}'),
+        EMB_HAT => Mark::Over('\u{02C6}'),
+        EMB_NOT => Mark::Enclose("updiagonalstrike"),
+        EMB_RARROW => Mark::Over('\u{2192}'),
+        EMB_LARROW => Mark::Over('\u{2190}'),
+        EMB_BARROW => Mark::Over('\u{2194}'),
+        EMB_R1ARROW => Mark::Over('\u{21C0}'),
+        EMB_L1ARROW => Mark::Over('\u{21BC}'),
+        EMB_MBAR => Mark::Enclose("horizontalstrike"),
+        EMB_OBAR => Mark::Over('\u{00AF}'),
+        EMB_FROWN => Mark::Over('\u{2322}'),
+        EMB_SMILE => Mark::Over('\u{2323}'),
+        EMB_X_BARS => Mark::Enclose("updiagonalstrike downdiagonalstrike"),
+        EMB_UP_BAR => Mark::Enclose("updiagonalstrike"),
+        EMB_DOWN_BAR => Mark::Enclose("downdiagonalstrike"),
+        EMB_U_1DOT => Mark::Under('\u{02D9}'),
+        EMB_U_2DOT => Mark::Under('\u{00A8}'),
+        EMB_U_3DOT => Mark::Under('\u{20DB}'),
+        EMB_U_4DOT => Mark::Under('\u{20DC}'),
+        EMB_U_BAR => Mark::Under('_'),
+        EMB_U_TILDE => Mark::Under('\u{02DC}'),
+        EMB_U_FROWN => Mark::Under('\u{2322}'),
+        EMB_U_SMILE => Mark::Under('\u{2323}'),
+        EMB_U_RARROW => Mark::Under('\u{2192}'),
+        EMB_U_LARROW => Mark::Under('\u{2190}'),
+        EMB_U_BARROW => Mark::Under('\u{2194}'),
+        EMB_U_R1ARROW => Mark::Under('\u{21C0}'),
+        EMB_U_L1ARROW => Mark::Under('\u{21BC}'),
+        _ => return None,
+    };
+    Some(mark)
+}
+
+/// Column alignment from the tab stops: the cell after a stop is aligned as the stop
+/// says. Relational and decimal stops align on the cell's first character, the
+/// cell before them ends at the stop.
+fn column_align(ruler: &MTRuler, columns: usize) -> Vec<&'static str> {
+    let mut align = vec!["left"; columns];
+    for (i, stop) in ruler.tab_stops.iter().enumerate().take(columns.saturating_sub(1)) {
+        match stop.kind {
+            TAB_CENTER => align[i + 1] = "center",
+            TAB_RIGHT => align[i + 1] = "right",
+            TAB_RELATION | TAB_DECIMAL => align[i] = "right",
+            _ => {}
+        }
+    }
+    align
+}
+
+/// Elements in an `<mrow>`, unless there is exactly one.
+fn group(mut items: Vec<String>) -> String {
+    match items.len() {
+        1 => items.remove(0),
+        _ => format!("<mrow>{}</mrow>", items.concat()),
+    }
+}
+
+fn em(width: f32) -> String {
+    format!("{}em", (width * 10000.0).round() / 10000.0)
+}
+
+fn is_tab(node: &Node) -> bool {
+    matches!(node, Node::Char(c) if c.ch.mtcode == Some(0x09))
+}
+
+/// A number at the start of `nodes`, digits and decimal points without embellishments.
+/// Returns it with the number of nodes it spans.
+fn number(nodes: &[Node]) -> Option<(String, usize)> {
+    let number: String = nodes.iter()
+        .map_while(|n| match n {
+            Node::Char(c) if c.embells.is_empty() && !matches!(typeface(c.ch), Some(FN_TEXT) | Some(FN_FUNCTION)) => {
+                unicode(c.ch).filter(|c| c.is_ascii_digit() || '.' == *c)
+            }
+            _ => None,
+        })
+        .collect();
+    match number.chars().next() {
+        Some(c) if c.is_ascii_digit() => {
+            let n = number.len();
+            Some((number, n))
+        }
+        _ => None,
+    }
+}
+
+/// Characters at the start of `nodes` sharing the typeface `face`.
+fn char_run<'a>(nodes: &[Node<'a>], face: Option<u8>) -> Vec<&'a MTChar> {
+    nodes.iter()
+        .map_while(|n| match n {
+            Node::Char(c) if typeface(c.ch) == face && !is_tab(n) => Some(c.ch),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Typeface without its 128 bias, `None` for explicit font indices.
+fn typeface(ch: &MTChar) -> Option<u8> {
+    ch.typeface.style()
+}
+
+fn unicode(ch: &MTChar) -> Option<char> {
+    ch.mtcode.and_then(|c| std::char::from_u32(c as u32))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
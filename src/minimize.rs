@@ -0,0 +1,68 @@
+//! Reduction of failing inputs to small reproductions, for bug reports on files
+//! that cannot be shared as they are.
+
+use std::io::Cursor;
+
+use super::eqn::{decode_header, decode_record};
+
+/// Smallest record sequence of `buf` (MTEF, header included) for which `fails` still
+/// holds, e.g. "parsing errors" or "translation panics".
+///
+/// Records are removed by delta debugging: halves of the sequence first, then ever
+/// smaller parts, until no single record can go. The header is always kept. Bytes
+/// after the last record the parser can delimit stay together as one part.
+/// Input for which `fails` does not hold, or without a readable header, is returned as is.
+pub fn minimize_failing_input<F: FnMut(&[u8]) -> bool>(buf: &[u8], mut fails: F) -> Vec<u8> {
+    let (header, mut parts) = match split_records(buf) {
+        Some(split) if fails(buf) => split,
+        _ => return buf.to_vec(),
+    };
+    let join = |parts: &[&[u8]]| -> Vec<u8> {
+        let mut out = header.to_vec();
+        for part in parts {
+            out.extend_from_slice(part);
+        }
+        out
+    };
+    let mut n = 2;
+    while parts.len() >= 2 {
+        let size = parts.len().div_ceil(n);
+        let mut reduced = false;
+        for start in (0..parts.len()).step_by(size) {
+            let candidate: Vec<&[u8]> = parts[..start].iter()
+                .chain(parts[(start + size).min(parts.len())..].iter())
+                .cloned()
+                .collect();
+            if fails(&join(&candidate)) {
+                parts = candidate;
+                n = (n - 1).max(2);
+                reduced = true;
+                break;
+            }
+        }
+        if !reduced {
+            if n >= parts.len() {
+                break;
+            }
+            n = (n * 2).min(parts.len());
+        }
+    }
+    join(&parts)
+}
+
+/// Header bytes and the bytes of each record.
+fn split_records(buf: &[u8]) -> Option<(&[u8], Vec<&[u8]>)> {
+    let mut cur = Cursor::new(buf);
+    decode_header(&mut cur).ok()?;
+    let header = &buf[..cur.position() as usize];
+    let mut parts = vec![];
+    while (cur.position() as usize) < buf.len() {
+        let start = cur.position() as usize;
+        if decode_record(&mut cur).is_err() {
+            parts.push(&buf[start..]);
+            break;
+        }
+        parts.push(&buf[start..cur.position() as usize]);
+    }
+    Some((header, parts))
+}
@@ -0,0 +1,107 @@
+//! Equations in OpenDocument files (.odt, .odp...), enabled by the `odt` feature.
+//!
+//! Documents converted from Word by LibreOffice keep each OLE object as a package
+//! entry of its own ("Object 1"...), next to its preview under "ObjectReplacements/".
+//! The package is a zip archive; only the stored and deflated entries it needs are read.
+
+use std::io::Read;
+use std::path::Path;
+
+use byteorder::{ByteOrder, LittleEndian};
+use flate2::read::DeflateDecoder;
+
+use super::eqn::{MTEquation, CFB_MAGIC};
+use super::error::Error;
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const CENTRAL_DIRECTORY_HEADER: u32 = 0x0201_4b50;
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// Equations of the OLE objects embedded in the package at `path`, with the name
+/// of the entry each one was read from. OLE objects other than equations are skipped.
+pub fn equations(path: &Path) -> Result<Vec<(String, MTEquation)>, Error> {
+    let package = std::fs::read(path)?;
+    let mut equations = vec![];
+    for entry in entries(&package)? {
+        let data = entry.data()?;
+        if !data.starts_with(&CFB_MAGIC) {
+            continue;
+        }
+        match MTEquation::from_ole_reader(&data[..]) {
+            Ok(eqn) => equations.push((entry.name, eqn)),
+            Err(Error::InvalidOLEFile) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(equations)
+}
+
+/// A file of the package.
+pub(crate) struct Entry<'p> {
+    pub(crate) name: String,
+    method: u16,
+    compressed: &'p [u8],
+    size: usize,
+}
+
+impl Entry<'_> {
+    pub(crate) fn data(&self) -> Result<Vec<u8>, Error> {
+        match self.method {
+            STORED => Ok(self.compressed.to_vec()),
+            DEFLATED => {
+                let mut data = Vec::with_capacity(self.size);
+                DeflateDecoder::new(self.compressed).read_to_end(&mut data)?;
+                Ok(data)
+            }
+            _ => Err(Error::InvalidPackage("unsupported compression method")),
+        }
+    }
+}
+
+/// Entries listed by the central directory, found through the end of central
+/// directory record (followed by a comment of at most 64 KiB).
+pub(crate) fn entries(package: &[u8]) -> Result<Vec<Entry<'_>>, Error> {
+    let eocd = (0..package.len().saturating_sub(21)).rev()
+        .take(0x10000 + 22)
+        .find(|&i| LittleEndian::read_u32(&package[i..]) == END_OF_CENTRAL_DIRECTORY)
+        .ok_or(Error::InvalidPackage("no end of central directory"))?;
+    let count = LittleEndian::read_u16(&package[eocd + 10..]) as usize;
+    let mut pos = LittleEndian::read_u32(&package[eocd + 16..]) as usize;
+    let mut entries = vec![];
+    for _ in 0..count {
+        let header = package.get(pos..pos + 46).ok_or_else(truncated)?;
+        if LittleEndian::read_u32(header) != CENTRAL_DIRECTORY_HEADER {
+            return Err(Error::InvalidPackage("bad central directory header"));
+        }
+        let method = LittleEndian::read_u16(&header[10..]);
+        let compressed_size = LittleEndian::read_u32(&header[20..]) as usize;
+        let size = LittleEndian::read_u32(&header[24..]) as usize;
+        let name_len = LittleEndian::read_u16(&header[28..]) as usize;
+        let extra_len = LittleEndian::read_u16(&header[30..]) as usize;
+        let comment_len = LittleEndian::read_u16(&header[32..]) as usize;
+        let offset = LittleEndian::read_u32(&header[42..]) as usize;
+        let name = package.get(pos + 46..pos + 46 + name_len).ok_or_else(truncated)?;
+        pos += 46 + name_len + extra_len + comment_len;
+
+        // sizes in the central directory, the local header may defer them to a data descriptor
+        let local = package.get(offset..offset + 30).ok_or_else(truncated)?;
+        if LittleEndian::read_u32(local) != LOCAL_FILE_HEADER {
+            return Err(Error::InvalidPackage("bad local file header"));
+        }
+        let start = offset + 30 + LittleEndian::read_u16(&local[26..]) as usize + LittleEndian::read_u16(&local[28..]) as usize;
+        entries.push(Entry {
+            name: String::from_utf8_lossy(name).into_owned(),
+            method,
+            compressed: package.get(start..start + compressed_size).ok_or_else(truncated)?,
+            size,
+        });
+    }
+    Ok(entries)
+}
+
+fn truncated() -> Error {
+    Error::InvalidPackage("truncated package")
+}
@@ -0,0 +1,18 @@
+//! Writing equations out: the LaTeX and MathML backends, MTEF, JSON, and what
+//! renderers and indexers read off an equation.
+
+pub use super::bounds::Bounds;
+pub use super::charmap::{CharMapper, DefaultCharMapper, MappedChar};
+pub use super::color::{ColorPalette, NamedColor};
+pub use super::json::JSON_SCHEMA_VERSION;
+#[cfg(feature = "latex")]
+pub use super::latex::{BracePileStyle, CjkPolicy, DecimalComma, GreekVariants, MathAlphabet, MathDelimiters,
+                       TranslateOptions, UnknownChars};
+#[cfg(feature = "mathml")]
+pub use super::mathml::{FontAttributes, MathmlOptions};
+pub use super::runs::StyleRun;
+#[cfg(feature = "service")]
+pub use super::service::{CacheLimits, CacheStats, ConversionCache};
+pub use super::visit::{MtefVisitor, MtefVisitorMut};
+pub use super::warning::{Warning, WarningCategory, WarningFilter};
+pub use super::writer::validate_strict;
@@ -0,0 +1,177 @@
+//! Conversion parity against reference LaTeX written by established converters.
+//!
+//! A fixture directory holds OLE objects `<name>.bin`, each next to `<name>.tex`, the
+//! reference output for it. Both outputs are compared as token sequences (control
+//! words, control symbols and single characters, spaces ignored), so that layout
+//! differences do not count; differing fixtures report the tokens missing from and
+//! added to our output, and how similar the two are.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use super::eqn::MTEquation;
+use super::json::json_string;
+
+#[derive(Debug, Default)]
+pub struct ParityReport {
+    pub cases: Vec<ParityCase>,
+}
+
+#[derive(Debug)]
+pub struct ParityCase {
+    /// fixture name, without extension
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug)]
+pub enum Outcome {
+    /// Same tokens as the reference.
+    Match,
+    Differs {
+        /// reference tokens our output lacks, in order
+        missing: Vec<String>,
+        /// tokens of our output the reference lacks, in order
+        extra: Vec<String>,
+        /// 2 × common tokens / all tokens, 1.0 for identical outputs
+        similarity: f64,
+    },
+    /// No reference next to the object, or it could not be converted.
+    Error(String),
+}
+
+impl ParityReport {
+    /// Compares every fixture of `dir`, in name order.
+    pub fn collect(dir: &Path) -> ParityReport {
+        let mut objects: Vec<_> = fs::read_dir(dir).into_iter().flatten().flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "bin"))
+            .collect();
+        objects.sort();
+        let cases = objects.iter()
+            .map(|object| ParityCase {
+                name: object.file_stem().unwrap_or_default().to_string_lossy().into_owned(),
+                outcome: compare(object),
+            })
+            .collect();
+        ParityReport { cases }
+    }
+
+    pub fn matches(&self) -> usize {
+        self.cases.iter().filter(|case| matches!(case.outcome, Outcome::Match)).count()
+    }
+
+    /// Mean similarity over the fixtures, failed conversions counting as 0.
+    pub fn similarity(&self) -> f64 {
+        if self.cases.is_empty() {
+            return 1.0;
+        }
+        let total: f64 = self.cases.iter()
+            .map(|case| match case.outcome {
+                Outcome::Match => 1.0,
+                Outcome::Differs { similarity, .. } => similarity,
+                Outcome::Error(_) => 0.0,
+            })
+            .sum();
+        total / self.cases.len() as f64
+    }
+
+    pub fn to_json(&self) -> String {
+        let tokens = |tokens: &[String]| tokens.iter().map(|t| json_string(t)).collect::<Vec<_>>().join(", ");
+        let mut out = String::from("{\n  \"cases\": [");
+        for (i, case) in self.cases.iter().enumerate() {
+            let outcome = match &case.outcome {
+                Outcome::Match => "\"status\": \"match\"".to_string(),
+                Outcome::Differs { missing, extra, similarity } => format!(
+                    "\"status\": \"differs\", \"similarity\": {:.3}, \"missing\": [{}], \"extra\": [{}]",
+                    similarity, tokens(missing), tokens(extra)),
+                Outcome::Error(e) => format!("\"status\": \"error\", \"error\": {}", json_string(e)),
+            };
+            write!(out, "{}\n    {{\"name\": {}, {}}}", if i > 0 { "," } else { "" }, json_string(&case.name), outcome).unwrap();
+        }
+        writeln!(out, "\n  ],\n  \"fixtures\": {},\n  \"matches\": {},\n  \"similarity\": {:.3}\n}}",
+                 self.cases.len(), self.matches(), self.similarity()).unwrap();
+        out
+    }
+}
+
+fn compare(object: &Path) -> Outcome {
+    let reference = match fs::read_to_string(object.with_extension("tex")) {
+        Ok(reference) => reference,
+        Err(e) => return Outcome::Error(format!("reference: {}", e)),
+    };
+    let ours = match MTEquation::from_ole(object).and_then(|eqn| eqn.translate()) {
+        Ok(ours) => ours,
+        Err(e) => return Outcome::Error(e.to_string()),
+    };
+    let (expected, actual) = (tokens(&reference), tokens(&ours));
+    if expected == actual {
+        return Outcome::Match;
+    }
+    let (missing, extra, common) = diff(&expected, &actual);
+    Outcome::Differs {
+        missing,
+        extra,
+        similarity: 2.0 * common as f64 / (expected.len() + actual.len()) as f64,
+    }
+}
+
+/// Control words (`\alpha`), control symbols (`\{`) and other characters one by one,
+/// without whitespace.
+fn tokens(latex: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut chars = latex.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let mut token = String::from("\\");
+                while let Some(&next) = chars.peek() {
+                    if !next.is_ascii_alphabetic() {
+                        break;
+                    }
+                    token.push(next);
+                    chars.next();
+                }
+                if token.len() == 1 {
+                    token.extend(chars.next());
+                }
+                tokens.push(token);
+            }
+            c if c.is_whitespace() => {}
+            c => tokens.push(c.to_string()),
+        }
+    }
+    tokens
+}
+
+/// Tokens only in `expected`, tokens only in `actual`, and the length of their
+/// longest common subsequence.
+fn diff(expected: &[String], actual: &[String]) -> (Vec<String>, Vec<String>, usize) {
+    let (n, m) = (expected.len(), actual.len());
+    // lcs[i][j]: longest common subsequence of expected[i..] and actual[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = match expected[i] == actual[j] {
+                true => lcs[i + 1][j + 1] + 1,
+                false => lcs[i + 1][j].max(lcs[i][j + 1]),
+            };
+        }
+    }
+    let (mut missing, mut extra) = (vec![], vec![]);
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && expected[i] == actual[j] {
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            missing.push(expected[i].clone());
+            i += 1;
+        } else {
+            extra.push(actual[j].clone());
+            j += 1;
+        }
+    }
+    (missing, extra, lcs[0][0])
+}
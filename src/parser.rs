@@ -0,0 +1,25 @@
+//! Reading equations: MTEF records out of bytes, streams and damaged data.
+
+#[cfg(feature = "std")]
+pub use super::class::RecordClass;
+pub use super::constants::RecordType;
+#[cfg(feature = "std")]
+pub use super::cst::{Cst, CstRecord};
+pub use super::eqn::{CharOptions, ColorDefOptions, ColorValue, DecodeError, MTChar, MTColorDef, MTEmbell, MTHeader,
+                     LineOptions, MTLine, MTPile, MTRecords, MTRuler, MTSize, MTTabStop, MTTmpl, PileOptions,
+                     TemplateOptions, Typeface, UnknownRecord};
+#[cfg(feature = "std")]
+pub use super::eqn::{EquationLine, MTEquation, ParseMode, ParseOptions};
+#[cfg(feature = "std")]
+pub use super::lazy::{IndexedRecord, LazyEquation};
+#[cfg(feature = "std")]
+pub use super::generator::{Generator, MtefVersion, Platform, Product};
+#[cfg(feature = "std")]
+pub use super::minimize::minimize_failing_input;
+#[cfg(feature = "std")]
+pub use super::repair::{Repair, RepairReport};
+pub use super::stream::StreamParser;
+#[cfg(feature = "std")]
+pub use super::stream::{Event, MtefEvents};
+#[cfg(feature = "std")]
+pub use super::upgrade::upgrade;
@@ -0,0 +1,235 @@
+//! Equations as linear Unicode text.
+
+use super::ast::{ArrowKind, BigOpKind, FenceKind, Line, Node, Pile, Tmpl};
+use super::charmap::{self, MappedChar};
+use super::constants::embellishments::*;
+use super::constants::selectors::*;
+use super::constants::typeface::FN_SPACE;
+use super::constants::variations::TV_ROOT_NTH;
+use super::eqn::{MTChar, MTEquation};
+
+/// Written with a space on either side.
+const SPACED: &[char] = &['=', '<', '>', '≈', '≠', '≡', '≤', '≥', '→', '←', '↔', '⇒', '⇔', '±', '∓'];
+
+impl MTEquation {
+    /// The equation as linear Unicode text, for search indexes and previews:
+    /// `x = (-b ± √(b²-4ac))/(2a)`. Lines of multi-line equations are separated by
+    /// newlines.
+    ///
+    /// The text is best effort and not meant to be parsed back. Slots of more than one
+    /// character are parenthesized where their extent would be lost (fractions, roots,
+    /// scripts), scripts made of digits and signs use the superscript and subscript
+    /// characters, and what has no linear form, such as the braces over an expression
+    /// or the edges of a box, is left out around its content.
+    pub fn to_plain_text(&self) -> String {
+        self.tree().iter().map(|line| Px { eqn: self, out: String::new() }.render(line)).collect::<Vec<_>>().join("\n")
+    }
+}
+
+struct Px<'e> {
+    eqn: &'e MTEquation,
+    out: String,
+}
+
+impl Px<'_> {
+    fn render(mut self, line: &Line) -> String {
+        self.line(line);
+        self.out.trim().to_string()
+    }
+
+    /// Text of a slot on its own.
+    fn slot(&self, line: Option<&Line>) -> String {
+        line.map_or(String::new(), |line| Px { eqn: self.eqn, out: String::new() }.render(line))
+    }
+
+    /// Text of a slot, parenthesized when longer than a character and its accents.
+    fn operand(&self, line: Option<&Line>) -> String {
+        let text = self.slot(line);
+        match text.chars().filter(|c| !is_combining(*c)).count() {
+            0 | 1 => text,
+            _ => format!("({})", text),
+        }
+    }
+
+    fn line(&mut self, line: &Line) {
+        for node in &line.nodes {
+            match node {
+                Node::Char(c) => {
+                    self.char(c.ch);
+                    for embell in &c.embells {
+                        self.out.push_str(embellishment(embell.embell));
+                    }
+                }
+                Node::Tmpl(t) => self.tmpl(t),
+                Node::Pile(p) => self.pile(p),
+                Node::Marker(_) => {}
+                Node::Placeholder => self.out.push('\u{25A1}'),
+            }
+        }
+    }
+
+    fn char(&mut self, ch: &MTChar) {
+        if ch.typeface.style() == Some(FN_SPACE) || ch.mtcode == Some(0x09) {
+            self.out.push(' ');
+            return;
+        }
+        match charmap::resolve(self.eqn, ch, None) {
+            Some(MappedChar::Char('\u{2212}')) => self.out.push('-'),
+            Some(MappedChar::Char(c)) if SPACED.contains(&c) => {
+                self.out.push(' ');
+                self.out.push(c);
+                self.out.push(' ');
+            }
+            Some(MappedChar::Char(c)) => self.out.push(c),
+            Some(MappedChar::Latex(_)) | None => {}
+        }
+    }
+
+    /// Lines of a pile, separated by semicolons.
+    fn pile(&mut self, pile: &Pile) {
+        let lines: Vec<String> = pile.lines.iter().map(|line| self.slot(Some(line))).collect();
+        self.out.push_str(&lines.join("; "));
+    }
+
+    fn tmpl(&mut self, t: &Tmpl) {
+        if let Some(fence) = t.fence() {
+            let (open, close) = match fence.kind {
+                FenceKind::Angle => ('⟨', '⟩'),
+                FenceKind::Paren => ('(', ')'),
+                FenceKind::Brace => ('{', '}'),
+                FenceKind::Brack => ('[', ']'),
+                FenceKind::Bar => ('|', '|'),
+                FenceKind::DBar => ('‖', '‖'),
+                FenceKind::Floor => ('⌊', '⌋'),
+                FenceKind::Ceiling => ('⌈', '⌉'),
+            };
+            if fence.left {
+                self.out.push(open);
+            }
+            self.out.push_str(&self.slot(fence.body));
+            if fence.right {
+                self.out.push(close);
+            }
+        } else if let Some(scripts) = t.scripts() {
+            self.script(scripts.sub, SUBSCRIPTS, '_');
+            self.script(scripts.sup, SUPERSCRIPTS, '^');
+        } else if let Some(big_op) = t.big_op() {
+            match big_op.kind {
+                BigOpKind::Integral { count, contour: false } => self.out.push(['∫', '∬', '∭'][count.clamp(1, 3) as usize - 1]),
+                BigOpKind::Integral { count, contour: true } => self.out.push(['∮', '∯', '∰'][count.clamp(1, 3) as usize - 1]),
+                BigOpKind::Sum => self.out.push('∑'),
+                BigOpKind::Prod => self.out.push('∏'),
+                BigOpKind::Coprod => self.out.push('∐'),
+                BigOpKind::Union => self.out.push('⋃'),
+                BigOpKind::Inter => self.out.push('⋂'),
+                BigOpKind::Other => {
+                    if let Some(op) = big_op.op {
+                        self.char(op);
+                    }
+                }
+            }
+            self.script(big_op.lower, SUBSCRIPTS, '_');
+            self.script(big_op.upper, SUPERSCRIPTS, '^');
+            self.out.push(' ');
+            self.out.push_str(&self.slot(big_op.body));
+        } else if let Some(limit) = t.limit() {
+            self.out.push_str(&self.slot(limit.name));
+            self.script(limit.lower, SUBSCRIPTS, '_');
+            self.script(limit.upper, SUPERSCRIPTS, '^');
+            self.out.push(' ');
+        } else if let Some(arrow) = t.arrow() {
+            self.out.push_str(match arrow.kind {
+                ArrowKind::Right => " → ",
+                ArrowKind::Left => " ← ",
+                ArrowKind::LeftRight => " ↔ ",
+                ArrowKind::Double => " ⇄ ",
+                ArrowKind::Harpoons => " ⇌ ",
+            });
+            self.script(arrow.top, SUPERSCRIPTS, '^');
+            self.script(arrow.bottom, SUBSCRIPTS, '_');
+        } else {
+            match t.tmpl.selector {
+                TM_FRACT => {
+                    let (num, den) = (self.operand(t.slot(0)), self.operand(t.slot(1)));
+                    self.out.push_str(&format!("{}/{}", num, den));
+                }
+                TM_ROOT => {
+                    let degree = match TV_ROOT_NTH == t.tmpl.variation & TV_ROOT_NTH {
+                        true => self.slot(t.slot(1)),
+                        false => String::new(),
+                    };
+                    match degree.as_str() {
+                        "" | "2" => self.out.push('√'),
+                        "3" => self.out.push('∛'),
+                        "4" => self.out.push('∜'),
+                        degree => self.out.push_str(&format!("{}√", convert(degree, SUPERSCRIPTS).unwrap_or_else(|| degree.to_string()))),
+                    }
+                    let radicand = self.operand(t.slot(0));
+                    self.out.push_str(&radicand);
+                }
+                // braces, boxes, strikes...: the content alone
+                _ => {
+                    for slot in &t.slots {
+                        self.line(slot);
+                    }
+                }
+            }
+        }
+    }
+
+    /// A script as script characters when it has them all, else after `mark`.
+    fn script(&mut self, line: Option<&Line>, chars: &[(char, char)], mark: char) {
+        let text = self.slot(line);
+        if text.is_empty() {
+            return;
+        }
+        match convert(&text, chars) {
+            Some(script) => self.out.push_str(&script),
+            None => {
+                self.out.push(mark);
+                self.out.push_str(&self.operand(line));
+            }
+        }
+    }
+}
+
+const SUPERSCRIPTS: &[(char, char)] = &[
+    ('0', '⁰'), ('1', '¹'), ('2', '²'), ('3', '³'), ('4', '⁴'), ('5', '⁵'), ('6', '⁶'), ('7', '⁷'), ('8', '⁸'),
+    ('9', '⁹'), ('+', '⁺'), ('-', '⁻'), ('=', '⁼'), ('(', '⁽'), (')', '⁾'), ('n', 'ⁿ'), ('i', 'ⁱ'),
+];
+
+const SUBSCRIPTS: &[(char, char)] = &[
+    ('0', '₀'), ('1', '₁'), ('2', '₂'), ('3', '₃'), ('4', '₄'), ('5', '₅'), ('6', '₆'), ('7', '₇'), ('8', '₈'),
+    ('9', '₉'), ('+', '₊'), ('-', '₋'), ('=', '₌'), ('(', '₍'), (')', '₎'),
+];
+
+/// `text` in script characters, `None` when one has none.
+fn convert(text: &str, chars: &[(char, char)]) -> Option<String> {
+    text.chars().map(|c| chars.iter().find(|(from, _)| *from == c).map(|(_, to)| *to)).collect()
+}
+
+fn is_combining(c: char) -> bool {
+    matches!(c, '\u{0300}'..='\u{036F}' | '\u{20D0}'..='\u{20FF}')
+}
+
+/// Primes, and combining marks for the accents that have one.
+fn embellishment(embell: u8) -> &'static str {
+    match embell {
+        EMB_1PRIME => "′",
+        EMB_2PRIME => "″",
+        EMB_3PRIME => "‴",
+        EMB_1DOT => "\u{0307}",
+        EMB_2DOT => "\u{0308}",
+        EMB_3DOT => "\u{20DB}",
+        EMB_4DOT => "\u{20DC}",
+        EMB_TILDE => "\u{0303}",
+        EMB_HAT => "\u{0302}",
+        EMB_NOT => "\u{0338}",
+        EMB_RARROW => "\u{20D7}",
+        EMB_LARROW => "\u{20D6}",
+        EMB_BARROW => "\u{20E1}",
+        EMB_OBAR => "\u{0305}",
+        EMB_U_BAR => "\u{0332}",
+        _ => "",
+    }
+}
@@ -0,0 +1,23 @@
+//! What most programs need, in one import:
+//!
+//! ```ignore
+//! use mtef::prelude::*;
+//!
+//! let eqn = MTEquation::from_ole_with(path, &ParseOptions::default())?;
+//! let latex = eqn.translate_with(&TranslateOptions { siunitx: true, ..Default::default() })?;
+//! ```
+//!
+//! Names in the prelude keep their paths across releases; the modules behind them
+//! are free to move.
+
+#[cfg(feature = "std")]
+pub use super::eqn::{MTEquation, ParseMode, ParseOptions};
+pub use super::error::Error;
+#[cfg(feature = "latex")]
+pub use super::latex::TranslateOptions;
+#[cfg(feature = "mathml")]
+pub use super::mathml::MathmlOptions;
+#[cfg(feature = "latex")]
+pub use super::convert::{bytes_to_latex, ole_to_latex};
+#[cfg(feature = "mathml")]
+pub use super::convert::ole_to_mathml;
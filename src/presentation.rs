@@ -0,0 +1,123 @@
+//! Size of the picture Word shows for an equation object, for re-rendering pipelines
+//! that scale their output to the footprint of the original equation.
+//!
+//! OLE objects cache the picture in `\x02OlePres000` streams (MS-OLEDS 2.3.4),
+//! whose header gives its size. .docx packages keep it as a WMF or EMF image next to
+//! the object instead, `picture_size` reads the size out of those.
+
+use std::io::Cursor;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use super::eqn::{read_entry, MTEquation};
+
+/// Extent of a presentation picture, in points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PictureSize {
+    pub format: PictureFormat,
+    pub width: f32,
+    pub height: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PictureFormat {
+    /// Windows metafile
+    Wmf,
+    /// enhanced metafile
+    Emf,
+    /// another standard clipboard format
+    Other(u32),
+}
+
+/// Clipboard formats of cached pictures.
+const CF_METAFILEPICT: u32 = 3;
+const CF_ENHMETAFILE: u32 = 14;
+/// Prefix of the presentation streams, numbered from 000.
+const OLE_PRES: &str = "\u{2}OlePres";
+/// Key of placeable WMF headers.
+const WMF_PLACEABLE: u32 = 0x9AC6_CDD7;
+/// " EMF", signature of EMF headers.
+const EMF_SIGNATURE: u32 = 0x464D_4520;
+
+impl MTEquation {
+    /// Size of the object's cached picture, for equations read from OLE files that
+    /// have one.
+    pub fn presentation_size(&self) -> Option<PictureSize> {
+        self.presentation
+    }
+}
+
+/// Size of a WMF (placeable) or EMF image, as found next to objects in .docx packages.
+pub fn picture_size(buf: &[u8]) -> Option<PictureSize> {
+    let mut cur = Cursor::new(buf);
+    match cur.read_u32::<LittleEndian>().ok()? {
+        WMF_PLACEABLE => {
+            cur.read_u16::<LittleEndian>().ok()?;
+            let mut bbox = [0i16; 4];
+            cur.read_i16_into::<LittleEndian>(&mut bbox).ok()?;
+            let inch = cur.read_u16::<LittleEndian>().ok()?;
+            if inch == 0 {
+                return None;
+            }
+            let points = |from: i16, to: i16| (to as i32 - from as i32) as f32 * 72.0 / inch as f32;
+            Some(PictureSize {
+                format: PictureFormat::Wmf,
+                width: points(bbox[0], bbox[2]),
+                height: points(bbox[1], bbox[3]),
+            })
+        }
+        // EMR_HEADER
+        1 => {
+            cur.set_position(24);
+            let mut frame = [0i32; 4];
+            cur.read_i32_into::<LittleEndian>(&mut frame).ok()?;
+            if cur.read_u32::<LittleEndian>().ok()? != EMF_SIGNATURE {
+                return None;
+            }
+            Some(PictureSize {
+                format: PictureFormat::Emf,
+                width: himetric(frame[2].saturating_sub(frame[0])),
+                height: himetric(frame[3].saturating_sub(frame[1])),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Size given by the first readable presentation stream of the object.
+pub(crate) fn from_ole(reader: &ole::Reader) -> Option<PictureSize> {
+    reader.iterate()
+        .filter(|entry| entry.name().starts_with(OLE_PRES))
+        .find_map(|entry| read_entry(reader, entry).ok().and_then(|buf| parse_ole_pres(&buf)))
+}
+
+fn parse_ole_pres(buf: &[u8]) -> Option<PictureSize> {
+    let mut cur = Cursor::new(buf);
+    // standard clipboard formats only, registered ones come by name
+    let format = match cur.read_u32::<LittleEndian>().ok()? {
+        0xFFFF_FFFF | 0xFFFF_FFFE => cur.read_u32::<LittleEndian>().ok()?,
+        _ => return None,
+    };
+    // the size counts itself
+    let target_device = cur.read_u32::<LittleEndian>().ok()?;
+    // aspect, lindex, advf and a reserved field
+    cur.set_position(cur.position() + target_device.saturating_sub(4) as u64 + 16);
+    let width = cur.read_i32::<LittleEndian>().ok()?;
+    let height = cur.read_i32::<LittleEndian>().ok()?;
+    Some(PictureSize {
+        format: match format {
+            CF_METAFILEPICT => PictureFormat::Wmf,
+            CF_ENHMETAFILE => PictureFormat::Emf,
+            format => PictureFormat::Other(format),
+        },
+        width: himetric(width),
+        height: himetric(height),
+    })
+}
+
+/// Points in hundredths of millimeters.
+fn himetric(v: i32) -> f32 {
+    v as f32 * 72.0 / 2540.0
+}
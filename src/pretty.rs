@@ -0,0 +1,155 @@
+//! Equations as an indented tree of their records.
+
+use std::fmt::{self, Write};
+
+use super::constants::{embellishments, selectors};
+use super::eqn::{ColorValue, MTChar, MTEquation, MTRecords, MTRuler, MTSize};
+use super::eqn::opens_scope;
+
+const INDENT: &str = "  ";
+
+impl MTEquation {
+    /// The records as an indented tree, for reading when a translation goes wrong:
+    ///
+    /// ```text
+    /// MTEF 5 (DSMT6), inline
+    /// FONT_DEF 1 "Times New Roman"
+    /// EQN_PREFS 12 sizes, 11 spaces, 12 styles
+    /// LINE
+    ///   TMPL tmFRACT variation 0x0000
+    ///     LINE
+    ///       CHAR 'x' U+0078 Variable
+    ///     LINE
+    ///       CHAR '2' U+0032 Number
+    /// ```
+    ///
+    /// Records are listed in stream order, those between a LINE, TMPL, PILE or
+    /// embellished CHAR and its END one level deeper; the ENDs themselves are not shown.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        write!(out, "MTEF {} ({})", self.m_mtef_ver, self.m_application).unwrap();
+        if self.m_inline != 0 {
+            out.push_str(", inline");
+        }
+        out.push('\n');
+        let mut depth = 0usize;
+        for record in &self.records {
+            if let MTRecords::END = record {
+                depth = depth.saturating_sub(1);
+                continue;
+            }
+            for _ in 0..depth {
+                out.push_str(INDENT);
+            }
+            write_record(&mut out, record);
+            out.push('\n');
+            if opens_scope(record) {
+                depth += 1;
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for MTEquation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.pretty())
+    }
+}
+
+fn write_record(out: &mut String, record: &MTRecords) {
+    out.push_str(record.record_type().name());
+    match record {
+        MTRecords::LINE(line) => {
+            if line.null {
+                out.push_str(" null");
+            }
+            if line.line_spacing != 0 {
+                write!(out, " spacing {}", line.line_spacing).unwrap();
+            }
+            write_ruler(out, &line.ruler);
+            write_nudge(out, line.nudge);
+        }
+        MTRecords::CHAR(ch) => {
+            write_char(out, ch);
+            write_nudge(out, ch.nudge);
+        }
+        MTRecords::TMPL(tmpl) => {
+            match selectors::selector_name(tmpl.selector) {
+                Some(name) => write!(out, " {}", name).unwrap(),
+                None => write!(out, " selector {}", tmpl.selector).unwrap(),
+            }
+            write!(out, " variation 0x{:04x}", tmpl.variation).unwrap();
+            if tmpl.options.bits() != 0 {
+                write!(out, " options {:?}", tmpl.options).unwrap();
+            }
+            write_nudge(out, tmpl.nudge);
+        }
+        MTRecords::PILE(pile) => {
+            write!(out, " halign {} valign {}", pile.halign, pile.valign).unwrap();
+            write_ruler(out, &pile.ruler);
+            write_nudge(out, pile.nudge);
+        }
+        MTRecords::EMBELL(embell) => {
+            match embellishments::embellishment_name(embell.embell) {
+                Some(name) => write!(out, " {}", name).unwrap(),
+                None => write!(out, " {}", embell.embell).unwrap(),
+            }
+            write_nudge(out, embell.nudge);
+        }
+        MTRecords::ENCODING_DEF(name) => write!(out, " {:?}", name).unwrap(),
+        MTRecords::FONT_DEF { enc_def_index, name } => write!(out, " {} {:?}", enc_def_index, name).unwrap(),
+        MTRecords::FONT_STYLE_DEF { font_def_index, char_style } => {
+            write!(out, " font {} style {}", font_def_index, char_style).unwrap()
+        }
+        MTRecords::EQN_PREFS { sizes, spaces, styles } => {
+            write!(out, " {} sizes, {} spaces, {} styles", sizes.len(), spaces.len(), styles.len()).unwrap()
+        }
+        MTRecords::SIZE(MTSize::Points(points)) => write!(out, " {}/32 pt", points).unwrap(),
+        MTRecords::SIZE(MTSize::Delta { lsize, delta }) => write!(out, " lsize {} delta {:+}/32 pt", lsize, delta).unwrap(),
+        MTRecords::COLOR(index) => write!(out, " {}", index).unwrap(),
+        MTRecords::COLOR_DEF(def) => {
+            match def.value {
+                ColorValue::Rgb(rgb) => write!(out, " rgb {:?}", rgb).unwrap(),
+                ColorValue::Cmyk(cmyk) => write!(out, " cmyk {:?}", cmyk).unwrap(),
+            }
+            if def.spot {
+                out.push_str(" spot");
+            }
+            if let Some(name) = &def.name {
+                write!(out, " {:?}", name).unwrap();
+            }
+        }
+        MTRecords::FUTURE(record) => write!(out, " tag {}, {} bytes", record.tag, record.bytes.len()).unwrap(),
+        MTRecords::END | MTRecords::FULL | MTRecords::SUB | MTRecords::SUB2 | MTRecords::SYM | MTRecords::SUBSYM => {}
+    }
+}
+
+/// The character, when printable, its codes, then its typeface.
+fn write_char(out: &mut String, ch: &MTChar) {
+    if let Some(mtcode) = ch.mtcode {
+        match std::char::from_u32(u32::from(mtcode)).filter(|c| !c.is_control()) {
+            Some(c) => write!(out, " {:?} U+{:04X}", c, mtcode).unwrap(),
+            None => write!(out, " U+{:04X}", mtcode).unwrap(),
+        }
+    }
+    if let Some(fp8) = ch.fp8 {
+        write!(out, " fp8 0x{:02x}", fp8).unwrap();
+    }
+    if let Some(fp16) = ch.fp16 {
+        write!(out, " fp16 0x{:04x}", fp16).unwrap();
+    }
+    write!(out, " {:?}", ch.typeface).unwrap();
+}
+
+fn write_ruler(out: &mut String, ruler: &Option<MTRuler>) {
+    if let Some(ruler) = ruler {
+        write!(out, " ruler {} tab stops", ruler.tab_stops.len()).unwrap();
+    }
+}
+
+fn write_nudge(out: &mut String, nudge: (i16, i16)) {
+    if nudge != (0, 0) {
+        write!(out, " nudge {:?}", nudge).unwrap();
+    }
+}
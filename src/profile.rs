@@ -0,0 +1,79 @@
+//! Record-level statistics, enabled by the `profile` feature: how many records of
+//! each type were decoded, how many bytes they took and how long decoding them took.
+//!
+//! Statistics are kept per thread and accumulate over every equation parsed on it
+//! until `take` is called:
+//!
+//! ```ignore
+//! for path in corpus {
+//!     MTEquation::from_ole(path)?;
+//! }
+//! print!("{}", profile::format(&profile::take()));
+//! ```
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::io::Cursor;
+use std::time::{Duration, Instant};
+
+use super::constants::RecordType;
+use super::eqn::DecodeError;
+
+/// Totals for one record type.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RecordStats {
+    pub count: u64,
+    /// record type and payload
+    pub bytes: u64,
+    pub time: Duration,
+}
+
+thread_local! {
+    static STATS: RefCell<BTreeMap<u8, RecordStats>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+/// Statistics gathered on this thread so far, by record type, resetting them.
+pub fn take() -> BTreeMap<u8, RecordStats> {
+    STATS.with(|stats| std::mem::take(&mut *stats.borrow_mut()))
+}
+
+/// One line per record type, the most time consuming first.
+pub fn format(stats: &BTreeMap<u8, RecordStats>) -> String {
+    let mut sorted: Vec<_> = stats.iter().collect();
+    sorted.sort_by_key(|(_, s)| std::cmp::Reverse(s.time));
+    let mut out = String::new();
+    for (tag, s) in sorted {
+        writeln!(out, "{:<14} {:>8} records {:>10} bytes {:>10.3} ms",
+            record_name(*tag), s.count, s.bytes, s.time.as_secs_f64() * 1000.0).unwrap();
+    }
+    out
+}
+
+/// Runs `decode` on the record at the cursor, adding it to the statistics when it
+/// is complete and valid.
+pub(crate) fn measure<T, F>(cur: &mut Cursor<&[u8]>, decode: F) -> Result<T, DecodeError>
+    where F: FnOnce(&mut Cursor<&[u8]>) -> Result<T, DecodeError>
+{
+    let start = cur.position();
+    let tag = cur.get_ref().get(start as usize).cloned();
+    let began = Instant::now();
+    let decoded = decode(cur)?;
+    let time = began.elapsed();
+    if let Some(tag) = tag {
+        STATS.with(|stats| {
+            let mut stats = stats.borrow_mut();
+            let s = stats.entry(tag).or_default();
+            s.count += 1;
+            s.bytes += cur.position() - start;
+            s.time += time;
+        });
+    }
+    Ok(decoded)
+}
+
+/// Name of the record type, undefined types counted as FUTURE.
+fn record_name(tag: u8) -> &'static str {
+    RecordType::try_from(tag).map_or("FUTURE", RecordType::name)
+}
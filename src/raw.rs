@@ -0,0 +1,44 @@
+//! Byte-level decoding of the MTEF value encodings, for tools reading MTEF their own way.
+//!
+//! Every function reads at the cursor and leaves it after the value. When the bytes
+//! end before the value does, `DecodeError::Incomplete` is returned and the cursor
+//! position is unspecified: rewind it before retrying with more bytes.
+
+use std::io::Cursor;
+
+pub use super::eqn::DecodeError;
+use super::eqn::{read_dimension_arrays, read_null_terminated_string, read_nudge_values};
+
+/// Nudge values following a tag with the MTEF_OPT_NUDGE option, horizontal then vertical.
+///
+/// Offsets between -128 and +127 take a byte each, biased by 128. Others are written
+/// after two 128 bytes as 16-bit integers. Either way the offsets are returned
+/// signed, so the two forms of an offset read the same.
+pub fn read_nudge(cur: &mut Cursor<&[u8]>) -> Result<(i16, i16), DecodeError> {
+    read_nudge_values(cur)
+}
+
+/// `count` dimensions of an EQN_PREFS record, written as nibbles: a unit (in, cm, pt,
+/// pc, %) then the digits, '.' and '-' of the value, closed by 0xF. The unit comes
+/// first in the returned strings, e.g. "pt12" and "%150".
+pub fn read_dimensions(cur: &mut Cursor<&[u8]>, count: u8) -> Result<Vec<String>, DecodeError> {
+    read_dimension_arrays(cur, count)
+}
+
+/// Null-terminated string, such as font and encoding names. MathType writes them in
+/// the system code page, decoded as GBK (a superset of ASCII).
+pub fn read_string(cur: &mut Cursor<&[u8]>) -> Result<String, DecodeError> {
+    read_null_terminated_string(cur)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nudges_of_both_forms() {
+        assert_eq!(read_nudge(&mut Cursor::new(&[130, 126][..])).unwrap(), (2, -2));
+        assert_eq!(read_nudge(&mut Cursor::new(&[128, 128, 2, 0, 0xFE, 0xFF][..])).unwrap(), (2, -2));
+        assert_eq!(read_nudge(&mut Cursor::new(&[128, 128, 130, 0, 130, 0][..])).unwrap(), (130, 130));
+    }
+}
@@ -0,0 +1,160 @@
+//! Best-effort recovery of damaged equations, as found in real corpora: objects whose
+//! header declares more MTEF than the stream holds, and MTEF ending in the middle of
+//! a record or before its templates and lines are closed.
+//!
+//! Whatever decodes is kept, the rest is dropped, and synthetic END records close
+//! what was left open. The report lists everything that was changed, an empty one
+//! meaning the equation was intact.
+
+use std::fmt;
+use std::io::Cursor;
+use std::path::Path;
+
+use super::eqn::{decode_header, decode_record, inflate, is_zlib, native_data, opens_scope, DecodeError, EqnOleFileHdr,
+                 MTEquation, MTRecords};
+use super::constants::record_types::{FUTURE, MATRIX};
+use super::error::Error;
+
+/// Changes made to get an equation out of damaged data.
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    pub repairs: Vec<Repair>,
+}
+
+impl RepairReport {
+    /// Nothing had to be repaired.
+    pub fn is_intact(&self) -> bool {
+        self.repairs.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Repair {
+    /// The EQNOLEFILEHDR declared `declared` bytes of MTEF, only `available` followed it.
+    SizeClamped { declared: usize, available: usize },
+    /// The data ended in the middle of the record at `offset` of the MTEF, tagged
+    /// `tag`, its `dropped` bytes were ignored.
+    TruncatedRecord { offset: usize, tag: u8, dropped: usize },
+    /// The record at `offset`, tagged `tag`, could not be decoded, it and the
+    /// `dropped` bytes from it on were ignored.
+    InvalidRecord { offset: usize, tag: u8, dropped: usize, error: String },
+    /// END records appended to close the lines, templates, piles and embellishment
+    /// lists left open, and the equation.
+    ClosedScopes(usize),
+}
+
+impl fmt::Display for Repair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Repair::SizeClamped { declared, available } => {
+                write!(f, "header declares {} bytes of MTEF, {} available", declared, available)
+            }
+            Repair::TruncatedRecord { offset, dropped, .. } => {
+                write!(f, "truncated record at offset {}, {} bytes dropped", offset, dropped)
+            }
+            Repair::InvalidRecord { offset, dropped, error, .. } => {
+                write!(f, "invalid record at offset {} ({}), {} bytes dropped", offset, error, dropped)
+            }
+            Repair::ClosedScopes(n) => write!(f, "{} END records added", n),
+        }
+    }
+}
+
+impl MTEquation {
+    /// `from_ole`, repairing damaged MTEF instead of failing on it. The OLE file
+    /// itself must be readable.
+    pub fn from_ole_repaired<P: AsRef<Path>>(path: P) -> Result<(MTEquation, RepairReport), Error> {
+        let reader = ole::Reader::new(std::fs::File::open(path)?).map_err(|_| Error::InvalidOLEFile)?;
+        let (mut eqn, report) = MTEquation::from_equation_native_repaired(&native_data(&reader)?)?;
+        eqn.presentation = super::presentation::from_ole(&reader);
+        Ok((eqn, report))
+    }
+
+    /// Content of an "Equation Native" stream, repaired as needed. Compressed MTEF
+    /// and MTEF older than version 5 are not repaired, they decode as usual or fail.
+    pub fn from_equation_native_repaired(buf: &[u8]) -> Result<(MTEquation, RepairReport), Error> {
+        let hdr = EqnOleFileHdr::parse_ole_hdr(buf)?;
+        let mut report = RepairReport::default();
+        let start = (hdr.cb_hdr as usize).min(buf.len());
+        let declared = hdr.size as usize;
+        let available = buf.len() - start;
+        if declared > available {
+            report.repairs.push(Repair::SizeClamped { declared, available });
+        }
+        let payload = &buf[start..start + declared.min(available)];
+        let mtef = match is_zlib(payload) {
+            true => inflate(payload, u64::MAX)?,
+            false => payload.to_vec(),
+        };
+        let eqn = match super::upgrade::needs_upgrade(&mtef) {
+            true => super::upgrade::upgrade(&mtef)?,
+            false => {
+                let (eqn, repaired) = MTEquation::parse_repaired(&mtef)?;
+                report.repairs.extend(repaired.repairs);
+                eqn
+            }
+        };
+        Ok((eqn, report))
+    }
+
+    /// `parse`, keeping the records decoded before damage and closing what is left
+    /// open. Fails only when the header itself is incomplete or invalid.
+    pub fn parse_repaired(buf: &[u8]) -> Result<(MTEquation, RepairReport), Error> {
+        let mut cur = Cursor::new(buf);
+        let header = decode_header(&mut cur).map_err(|e| match e {
+            DecodeError::Incomplete => Error::MalformedMTEF("truncated MTEF header"),
+            DecodeError::Invalid(e) => e,
+        })?;
+        let mut report = RepairReport::default();
+        let mut records = vec![];
+        let mut spans = vec![];
+        let mut skipped_matrices = vec![];
+        // the equation itself is closed by the last END
+        let mut open = 1usize;
+        while (cur.position() as usize) < buf.len() {
+            let offset = cur.position() as usize;
+            let (tag, dropped) = (buf[offset], buf.len() - offset);
+            match decode_record(&mut cur) {
+                // the length of undefined records is unknown, nothing after them can be trusted
+                Ok(Some(MTRecords::FUTURE(record))) if record.tag < FUTURE => {
+                    let error = format!("unknown record type {}", record.tag);
+                    report.repairs.push(Repair::InvalidRecord { offset, tag, dropped, error });
+                    break;
+                }
+                Ok(Some(record)) => {
+                    match &record {
+                        MTRecords::END => open = open.saturating_sub(1),
+                        record if opens_scope(record) => open += 1,
+                        _ => {}
+                    }
+                    records.push(record);
+                    spans.push(offset..cur.position() as usize);
+                }
+                Ok(None) => {
+                    if tag == MATRIX {
+                        skipped_matrices.push(offset);
+                    }
+                }
+                Err(DecodeError::Incomplete) => {
+                    report.repairs.push(Repair::TruncatedRecord { offset, tag, dropped });
+                    break;
+                }
+                Err(DecodeError::Invalid(e)) => {
+                    report.repairs.push(Repair::InvalidRecord { offset, tag, dropped, error: e.to_string() });
+                    break;
+                }
+            }
+        }
+        if open > 0 {
+            records.extend(std::iter::repeat_with(|| MTRecords::END).take(open));
+            // the added ENDs were read from nothing
+            spans.resize(records.len(), buf.len()..buf.len());
+            report.repairs.push(Repair::ClosedScopes(open));
+        }
+        let mut eqn = MTEquation::from_parts(header, records);
+        eqn.spans = spans;
+        eqn.skipped_matrices = skipped_matrices;
+        Ok((eqn, report))
+    }
+}
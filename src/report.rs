@@ -0,0 +1,228 @@
+//! Corpus report: what a set of documents holds and how well it is covered, the
+//! first thing to look at before migrating their equations.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use super::ast::{self, Line, Node};
+use super::capabilities::{capabilities, Capabilities};
+use super::charmap;
+use super::constants::{embellishments, selectors};
+use super::eqn::{self, MTEquation, CFB_MAGIC};
+use super::json::json_string;
+
+#[derive(Debug, Default)]
+pub struct FileReport {
+    pub path: String,
+    pub equations: usize,
+    pub errors: Vec<String>,
+}
+
+/// Aggregate over every compound file found under a directory.
+#[derive(Debug, Default)]
+pub struct Report {
+    pub files: Vec<FileReport>,
+    /// Files skipped for not being compound files.
+    pub skipped: usize,
+    pub records: BTreeMap<&'static str, usize>,
+    pub templates: BTreeMap<&'static str, usize>,
+    pub embellishments: BTreeMap<&'static str, usize>,
+    /// Characters no mapper knows, by encoding and code.
+    pub unmapped: BTreeMap<String, usize>,
+    /// Equations by the program and platform that wrote them, "MathType 6.9 on Windows".
+    pub generators: BTreeMap<String, usize>,
+    /// Equations per tenth of translation confidence, the share of characters
+    /// and templates having a dedicated translation.
+    pub confidence: [usize; 10],
+}
+
+impl Report {
+    pub fn collect(dir: &Path) -> Report {
+        let mut report = Report::default();
+        let caps = capabilities();
+        let mut paths = vec![];
+        walk(dir, &mut paths);
+        paths.sort();
+        for path in paths {
+            if !is_compound_file(&path) {
+                report.skipped += 1;
+                continue;
+            }
+            let mut file = FileReport { path: path.display().to_string(), ..Default::default() };
+            for eqn in equations(&path) {
+                match eqn {
+                    Ok(eqn) => {
+                        file.equations += 1;
+                        report.add(&eqn, &caps);
+                    }
+                    Err(e) => file.errors.push(e),
+                }
+            }
+            report.files.push(file);
+        }
+        report
+    }
+
+    fn add(&mut self, eqn: &MTEquation, caps: &Capabilities) {
+        *self.generators.entry(format!("{} on {}", eqn.generator(), eqn.platform())).or_insert(0) += 1;
+        for record in &eqn.records {
+            *self.records.entry(record.record_type().name()).or_insert(0) += 1;
+        }
+        let mut coverage = (0, 0);
+        for line in ast::build(&eqn.records, eqn.fill_missing_slots) {
+            self.line(eqn, &line, caps, &mut coverage);
+        }
+        let (known, total) = coverage;
+        let ratio = match total {
+            0 => 1.0,
+            _ => known as f64 / total as f64,
+        };
+        self.confidence[((ratio * 10.0) as usize).min(9)] += 1;
+    }
+
+    fn line(&mut self, eqn: &MTEquation, line: &Line, caps: &Capabilities, coverage: &mut (usize, usize)) {
+        for node in &line.nodes {
+            match node {
+                Node::Char(c) => {
+                    for embell in &c.embells {
+                        let name = embellishments::embellishment_name(embell.embell).unwrap_or("unknown");
+                        *self.embellishments.entry(name).or_insert(0) += 1;
+                    }
+                    coverage.1 += 1;
+                    match charmap::resolve(eqn, c.ch, None) {
+                        Some(_) => coverage.0 += 1,
+                        None => {
+                            let key = match charmap::char_code(eqn, c.ch) {
+                                Some((encoding, code)) => format!("{} 0x{:04X}", encoding, code),
+                                None => "no code".to_string(),
+                            };
+                            *self.unmapped.entry(key).or_insert(0) += 1;
+                        }
+                    }
+                }
+                Node::Tmpl(t) => {
+                    let selector = t.tmpl.selector;
+                    *self.templates.entry(selectors::selector_name(selector).unwrap_or("unknown")).or_insert(0) += 1;
+                    coverage.1 += 1;
+                    if caps.supports_selector(selector) {
+                        coverage.0 += 1;
+                    }
+                    for slot in &t.slots {
+                        self.line(eqn, slot, caps, coverage);
+                    }
+                }
+                Node::Marker(_) | Node::Placeholder => {}
+                Node::Pile(p) => {
+                    for line in &p.lines {
+                        self.line(eqn, line, caps, coverage);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn equations(&self) -> usize {
+        self.files.iter().map(|f| f.equations).sum()
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::from("{\n  \"files\": [");
+        for (i, file) in self.files.iter().enumerate() {
+            let errors: Vec<String> = file.errors.iter().map(|e| json_string(e)).collect();
+            write!(out, "{}\n    {{\"path\": {}, \"equations\": {}, \"errors\": [{}]}}",
+                   if i > 0 { "," } else { "" }, json_string(&file.path), file.equations, errors.join(", ")).unwrap();
+        }
+        write!(out, "\n  ],\n  \"equations\": {},\n  \"skipped\": {},\n", self.equations(), self.skipped).unwrap();
+        for (name, histogram) in self.histograms() {
+            let entries: Vec<String> = histogram.iter()
+                .map(|(key, count)| format!("{}: {}", json_string(key), count))
+                .collect();
+            writeln!(out, "  \"{}\": {{{}}},", name, entries.join(", ")).unwrap();
+        }
+        let confidence: Vec<String> = self.confidence.iter().map(|c| c.to_string()).collect();
+        write!(out, "  \"confidence\": [{}]\n}}\n", confidence.join(", ")).unwrap();
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::from("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>MTEF corpus report</title></head>\n<body>\n");
+        write!(out, "<h1>MTEF corpus report</h1>\n<p>{} equations in {} files, {} other files skipped.</p>\n",
+               self.equations(), self.files.len(), self.skipped).unwrap();
+        out.push_str("<h2>files</h2>\n<table>\n<tr><th>path</th><th>equations</th><th>errors</th></tr>\n");
+        for file in &self.files {
+            writeln!(out, "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                     html_escape(&file.path), file.equations, html_escape(&file.errors.join("; "))).unwrap();
+        }
+        out.push_str("</table>\n");
+        for (name, histogram) in self.histograms() {
+            write!(out, "<h2>{}</h2>\n<table>\n", name).unwrap();
+            for (key, count) in histogram {
+                writeln!(out, "<tr><td>{}</td><td>{}</td></tr>", html_escape(key), count).unwrap();
+            }
+            out.push_str("</table>\n");
+        }
+        out.push_str("<h2>confidence</h2>\n<table>\n");
+        for (i, count) in self.confidence.iter().enumerate() {
+            writeln!(out, "<tr><td>{}-{}%</td><td>{}</td></tr>", i * 10, i * 10 + 10, count).unwrap();
+        }
+        out.push_str("</table>\n</body>\n</html>\n");
+        out
+    }
+
+    fn histograms(&self) -> Vec<(&'static str, BTreeMap<&str, usize>)> {
+        let unmapped = self.unmapped.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        let generators = self.generators.iter().map(|(k, v)| (k.as_str(), *v)).collect();
+        vec![
+            ("records", self.records.clone()),
+            ("templates", self.templates.clone()),
+            ("embellishments", self.embellishments.clone()),
+            ("unmapped", unmapped),
+            ("generators", generators),
+        ]
+    }
+}
+
+fn walk(dir: &Path, paths: &mut Vec<std::path::PathBuf>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        match path.is_dir() {
+            true => walk(&path, paths),
+            false => paths.push(path),
+        }
+    }
+}
+
+fn is_compound_file(path: &Path) -> bool {
+    let mut magic = [0u8; 8];
+    fs::File::open(path).and_then(|mut f| f.read_exact(&mut magic)).is_ok() && magic == CFB_MAGIC
+}
+
+/// Every "Equation Native" stream of the file, a .doc holds one per equation object.
+fn equations(path: &Path) -> Vec<Result<MTEquation, String>> {
+    let reader = match fs::File::open(path) {
+        Ok(file) => ole::Reader::new(file),
+        Err(e) => return vec![Err(e.to_string())],
+    };
+    let reader = match reader {
+        Ok(reader) => reader,
+        Err(e) => return vec![Err(e.to_string())],
+    };
+    reader.iterate()
+        .filter(|entry| entry.name() == eqn::EQUATION_NATIVE)
+        .map(|entry| {
+            let buf = eqn::read_entry(&reader, entry).map_err(|e| e.to_string())?;
+            MTEquation::from_equation_native(&buf).map_err(|e| e.to_string())
+        })
+        .collect()
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,69 @@
+//! Characters grouped by appearance, for text extraction and search indexing that
+//! need to tell bold vectors from function names without walking the records.
+
+use super::charmap::{resolve, MappedChar};
+use super::constants::typesize::*;
+use super::eqn::{CharOptions, MTEquation, MTRecords, MTSize, Typeface};
+
+/// Characters following each other in the same line or slot, with the same
+/// typeface and size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StyleRun {
+    pub typeface: Typeface,
+    /// font of the typeface, when defined
+    pub font: Option<String>,
+    /// character style of the typeface, bit 0 italic and bit 1 bold, when defined
+    pub style: Option<u8>,
+    /// typesizes come as a zero delta from them
+    pub size: MTSize,
+    /// unmapped characters are U+FFFD
+    pub text: String,
+}
+
+impl MTEquation {
+    /// Style runs in record order. Lines, templates and piles end runs, embellishments
+    /// and markers do not.
+    pub fn style_runs(&self) -> Vec<StyleRun> {
+        let typesize = |lsize| MTSize::Delta { lsize, delta: 0 };
+        let mut runs: Vec<StyleRun> = vec![];
+        let mut size = typesize(SZ_FULL);
+        // whether the next character may join the last run
+        let mut joinable = false;
+        let mut embells = false;
+        for record in &self.records {
+            match record {
+                MTRecords::CHAR(ch) => {
+                    embells = ch.options.contains(CharOptions::EMBELL);
+                    if ch.typeface == Typeface::Marker {
+                        continue;
+                    }
+                    let c = match resolve(self, ch, None) {
+                        Some(MappedChar::Char(c)) => c,
+                        _ => '\u{FFFD}',
+                    };
+                    match runs.last_mut() {
+                        Some(run) if joinable && run.typeface == ch.typeface && run.size == size => run.text.push(c),
+                        _ => runs.push(StyleRun {
+                            typeface: ch.typeface,
+                            font: self.font_name(ch.typeface).map(str::to_string),
+                            style: self.char_style(ch.typeface),
+                            size,
+                            text: c.to_string(),
+                        }),
+                    }
+                    joinable = true;
+                }
+                MTRecords::EMBELL(_) => {}
+                MTRecords::END if embells => embells = false,
+                MTRecords::FULL => size = typesize(SZ_FULL),
+                MTRecords::SUB => size = typesize(SZ_SUB),
+                MTRecords::SUB2 => size = typesize(SZ_SUB2),
+                MTRecords::SYM => size = typesize(SZ_SYM),
+                MTRecords::SUBSYM => size = typesize(SZ_SUBSYM),
+                MTRecords::SIZE(s) => size = *s,
+                _ => joinable = false,
+            }
+        }
+        runs
+    }
+}
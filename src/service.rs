@@ -0,0 +1,139 @@
+//! Conversion cache for long-running services, enabled by the `service` feature.
+//!
+//! Worksheets repeat the same equations many times over, so a service converting
+//! them keeps its recent outputs by `content_hash` and translates each distinct
+//! equation once. The cache is bounded by entry count and by the bytes of output
+//! it holds, the least recently used outputs are dropped first.
+//!
+//! ```ignore
+//! let mut cache = ConversionCache::new(CacheLimits { max_entries: 10_000, max_bytes: 64 << 20 });
+//! let latex = cache.get_or_convert(&eqn, |eqn| eqn.translate())?;
+//! ```
+//!
+//! A cache holds the output of one conversion: services writing several formats,
+//! or honouring per-request options, keep one cache for each.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use super::eqn::MTEquation;
+use super::error::Error;
+
+/// Bookkeeping counted against `max_bytes` for each entry, on top of its output.
+const ENTRY_OVERHEAD: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CacheLimits {
+    /// Outputs kept at most.
+    pub max_entries: usize,
+    /// Bytes of output kept at most; an output larger than this is returned uncached.
+    pub max_bytes: usize,
+}
+
+impl Default for CacheLimits {
+    fn default() -> CacheLimits {
+        CacheLimits { max_entries: 4096, max_bytes: 16 << 20 }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+#[derive(Debug)]
+struct Entry {
+    output: Arc<str>,
+    last_used: u64,
+}
+
+/// Outputs of recently converted equations, least recently used evicted first.
+#[derive(Debug)]
+pub struct ConversionCache {
+    limits: CacheLimits,
+    entries: HashMap<u128, Entry>,
+    /// Content hashes by last use, oldest first.
+    recency: BTreeMap<u64, u128>,
+    tick: u64,
+    bytes: usize,
+    stats: CacheStats,
+}
+
+impl ConversionCache {
+    pub fn new(limits: CacheLimits) -> ConversionCache {
+        ConversionCache {
+            limits,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            bytes: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Output cached for an equation with the content of `eqn`, or the result of
+    /// `convert`, cached when it succeeds.
+    pub fn get_or_convert<F>(&mut self, eqn: &MTEquation, convert: F) -> Result<Arc<str>, Error>
+        where F: FnOnce(&MTEquation) -> Result<String, Error>
+    {
+        let key = eqn.content_hash();
+        self.tick += 1;
+        if let Some(entry) = self.entries.get_mut(&key) {
+            self.recency.remove(&entry.last_used);
+            self.recency.insert(self.tick, key);
+            entry.last_used = self.tick;
+            self.stats.hits += 1;
+            return Ok(entry.output.clone());
+        }
+        self.stats.misses += 1;
+        let output: Arc<str> = convert(eqn)?.into();
+        let size = output.len() + ENTRY_OVERHEAD;
+        if size > self.limits.max_bytes || self.limits.max_entries == 0 {
+            return Ok(output);
+        }
+        while self.entries.len() >= self.limits.max_entries || self.bytes + size > self.limits.max_bytes {
+            self.evict_oldest();
+        }
+        self.bytes += size;
+        self.recency.insert(self.tick, key);
+        self.entries.insert(key, Entry { output: output.clone(), last_used: self.tick });
+        Ok(output)
+    }
+
+    fn evict_oldest(&mut self) {
+        let (&tick, &key) = match self.recency.iter().next() {
+            Some(oldest) => oldest,
+            None => return,
+        };
+        self.recency.remove(&tick);
+        if let Some(entry) = self.entries.remove(&key) {
+            self.bytes -= entry.output.len() + ENTRY_OVERHEAD;
+            self.stats.evictions += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bytes counted against `max_bytes`.
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.bytes = 0;
+    }
+}
@@ -0,0 +1,319 @@
+//! Incremental parsing for equations arriving over a network stream.
+
+use alloc::vec::Vec;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+use super::constants::record_types::MATRIX;
+use super::eqn::{decode_header, decode_record, DecodeError, MTHeader, MTRecords};
+#[cfg(feature = "std")]
+use super::eqn::{CharOptions, MTChar, MTEmbell, MTLine, MTPile, MTTmpl};
+use super::error::Error;
+use super::io::Cursor;
+
+/// Resumable MTEF parser, for equations arriving over a network stream: bytes are
+/// pushed as they come, records are handed out as soon as they are complete and
+/// the bytes they used are dropped, so the payload is never buffered as a whole.
+///
+/// ```ignore
+/// let mut parser = StreamParser::new();
+/// while let Some(chunk) = socket.next_chunk()? {
+///     parser.push(&chunk);
+///     while let Some(record) = parser.next_record()? {
+///         handle(record);
+///     }
+/// }
+/// parser.finish()?;
+/// ```
+///
+/// `MtefEvents` pulls the bytes from a reader instead.
+#[derive(Debug, Default)]
+pub struct StreamParser {
+    /// Pushed bytes, those before `pos` consumed already.
+    buf: Vec<u8>,
+    /// Read position in `buf`.
+    pos: usize,
+    /// Bytes consumed so far.
+    offset: usize,
+    header: Option<MTHeader>,
+    last_span: Option<Range<usize>>,
+    /// Offsets of the MATRIX records read past, not being decoded yet.
+    skipped_matrices: Vec<usize>,
+}
+
+impl StreamParser {
+    pub fn new() -> StreamParser {
+        StreamParser::default()
+    }
+
+    pub fn push(&mut self, bytes: &[u8]) {
+        // drop the consumed bytes once they are most of the buffer, so each byte is
+        // moved a bounded number of times whatever the size of the chunks
+        if self.pos > self.buf.len() / 2 {
+            self.buf.drain(..self.pos);
+            self.pos = 0;
+        }
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Header of the equation, once its bytes have been pushed and `next_record` called.
+    pub fn header(&self) -> Option<&MTHeader> {
+        self.header.as_ref()
+    }
+
+    /// Bytes the record last returned by `next_record` was read from, counted from the
+    /// first byte pushed.
+    pub fn last_span(&self) -> Option<Range<usize>> {
+        self.last_span.clone()
+    }
+
+    /// Offsets of the MATRIX records passed over by `next_record`, their content
+    /// being left out of the records it returns.
+    #[cfg(feature = "std")]
+    pub(crate) fn skipped_matrices(&self) -> &[usize] {
+        &self.skipped_matrices
+    }
+
+    /// Next complete record, `None` until more bytes are pushed.
+    pub fn next_record(&mut self) -> Result<Option<MTRecords>, Error> {
+        if self.header.is_none() {
+            match self.decode(decode_header)? {
+                Some(header) => self.header = Some(header),
+                None => return Ok(None),
+            }
+        }
+        // records whose payload is not decoded yet come out as None, skip them
+        while self.pos < self.buf.len() {
+            let start = self.offset;
+            let tag = self.buf[self.pos];
+            match self.decode(decode_record)? {
+                Some(Some(record)) => {
+                    self.last_span = Some(start..self.offset);
+                    return Ok(Some(record));
+                }
+                Some(None) => {
+                    if tag == MATRIX {
+                        self.skipped_matrices.push(start);
+                    }
+                }
+                None => break,
+            }
+        }
+        Ok(None)
+    }
+
+    /// Decodes from the buffered bytes, consuming them on success. `None` when they
+    /// are not complete yet.
+    fn decode<T, F>(&mut self, f: F) -> Result<Option<T>, Error>
+        where F: FnOnce(&mut Cursor<&[u8]>) -> Result<T, DecodeError>
+    {
+        let mut cur = Cursor::new(&self.buf[self.pos..]);
+        match f(&mut cur) {
+            Ok(decoded) => {
+                let consumed = cur.position() as usize;
+                self.pos += consumed;
+                self.offset += consumed;
+                Ok(Some(decoded))
+            }
+            Err(DecodeError::Incomplete) => Ok(None),
+            Err(DecodeError::Invalid(e)) => Err(e),
+        }
+    }
+
+    /// Ends the stream, failing when it stopped in the middle of the header or a record.
+    pub fn finish(self) -> Result<MTHeader, Error> {
+        match (self.header, self.pos == self.buf.len()) {
+            (Some(header), true) => Ok(header),
+            _ => Err(Error::MalformedMTEF("truncated MTEF")),
+        }
+    }
+}
+
+/// Bytes read from the reader of `MtefEvents` at a time.
+#[cfg(feature = "std")]
+const CHUNK_SIZE: usize = 8192;
+
+/// Structure of an equation, as `MtefEvents` reads it.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// Always the first event.
+    Header(MTHeader),
+    /// A line, or a template slot. Null lines end right away.
+    StartLine(MTLine),
+    EndLine,
+    StartTemplate(MTTmpl),
+    EndTemplate,
+    StartPile(MTPile),
+    EndPile,
+    Char(MTChar),
+    /// An embellishment of the last character.
+    Embell(MTEmbell),
+    /// Sizes, colors, definitions and records of unknown types.
+    Record(MTRecords),
+}
+
+/// What an END record closes.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+enum Scope {
+    Line,
+    Template,
+    Pile,
+    Embells,
+}
+
+/// Pull parser over the MTEF of a reader, handing out events, the structure of the
+/// equation as it unfolds, without keeping its records. Ends after the first error.
+///
+/// ```ignore
+/// for event in MtefEvents::new(file) {
+///     match event? {
+///         Event::StartTemplate(tmpl) => ...,
+///         Event::Char(ch) => ...,
+///         _ => {}
+///     }
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct MtefEvents<R> {
+    reader: R,
+    parser: StreamParser,
+    scopes: Vec<Scope>,
+    /// Events of the last record not handed out yet.
+    pending: VecDeque<Event>,
+    header_sent: bool,
+    done: bool,
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> MtefEvents<R> {
+    pub fn new(reader: R) -> MtefEvents<R> {
+        MtefEvents {
+            reader,
+            parser: StreamParser::new(),
+            scopes: vec![],
+            pending: VecDeque::new(),
+            header_sent: false,
+            done: false,
+        }
+    }
+
+    fn queue(&mut self, record: MTRecords) {
+        let event = match record {
+            MTRecords::END => match self.scopes.pop() {
+                Some(Scope::Line) => Event::EndLine,
+                Some(Scope::Template) => Event::EndTemplate,
+                Some(Scope::Pile) => Event::EndPile,
+                // embellishment lists and the equation end without an event
+                Some(Scope::Embells) | None => return,
+            },
+            MTRecords::LINE(line) => {
+                let null = line.null;
+                self.pending.push_back(Event::StartLine(line));
+                match null {
+                    true => Event::EndLine,
+                    false => {
+                        self.scopes.push(Scope::Line);
+                        return;
+                    }
+                }
+            }
+            MTRecords::TMPL(tmpl) => {
+                self.scopes.push(Scope::Template);
+                Event::StartTemplate(tmpl)
+            }
+            MTRecords::PILE(pile) => {
+                self.scopes.push(Scope::Pile);
+                Event::StartPile(pile)
+            }
+            MTRecords::CHAR(ch) => {
+                if ch.options.contains(CharOptions::EMBELL) {
+                    self.scopes.push(Scope::Embells);
+                }
+                Event::Char(ch)
+            }
+            MTRecords::EMBELL(embell) => Event::Embell(embell),
+            record => Event::Record(record),
+        };
+        self.pending.push_back(event);
+    }
+
+    fn fail(&mut self, e: Error) -> Option<Result<Event, Error>> {
+        self.done = true;
+        Some(Err(e))
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Iterator for MtefEvents<R> {
+    type Item = Result<Event, Error>;
+
+    fn next(&mut self) -> Option<Result<Event, Error>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.done {
+                return None;
+            }
+            let record = self.parser.next_record();
+            if !self.header_sent {
+                if let Some(header) = self.parser.header() {
+                    self.pending.push_back(Event::Header(header.clone()));
+                    self.header_sent = true;
+                }
+            }
+            match record {
+                Ok(Some(record)) => self.queue(record),
+                Ok(None) => {
+                    let mut chunk = [0; CHUNK_SIZE];
+                    match self.reader.read(&mut chunk) {
+                        Ok(0) => {
+                            self.done = true;
+                            if let Err(e) = std::mem::take(&mut self.parser).finish() {
+                                return Some(Err(e));
+                            }
+                        }
+                        Ok(n) => self.parser.push(&chunk[..n]),
+                        Err(e) => return self.fail(e.into()),
+                    }
+                }
+                Err(e) => return self.fail(e),
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use super::super::eqn::MTEquation;
+
+    #[test]
+    fn records_of_small_chunks() {
+        let mtef = super::super::extract::raw_mtef("assets/oleObject1.bin").unwrap().remove(0);
+        let mut parser = StreamParser::new();
+        let mut records = vec![];
+        // chunks of 1, 2 and 3 bytes in turn
+        let mut start = 0;
+        for len in (1..=3).cycle() {
+            if start >= mtef.len() {
+                break;
+            }
+            let end = mtef.len().min(start + len);
+            parser.push(&mtef[start..end]);
+            start = end;
+            while let Some(record) = parser.next_record().unwrap() {
+                records.push(record);
+            }
+        }
+        parser.finish().unwrap();
+        let eqn = MTEquation::from_bytes(&mtef).unwrap();
+        assert_eq!(records.iter().collect::<Vec<_>>(), eqn.records().collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,230 @@
+//! MTEF version upgrade: equations written by Equation Editor 3.0 (MTEF 3) and
+//! MathType 4 (MTEF 4) are read into the MTEF 5 model, `MTEquation::to_bytes`
+//! then writes them as MTEF 5.
+//!
+//! MTEF 3 packs the options of a record in the high nibble of its tag byte, has
+//! no MTCode (characters are positions in the typeface's font), a one byte template
+//! variation and FONT records instead of FONT_DEF/FONT_STYLE_DEF. MTEF 4 has the
+//! MTEF 5 record layout except for FONT records, and both lack the application key,
+//! the inline flag and the encoding definitions of the MTEF 5 header.
+
+use std::io::Cursor;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use super::charmap::symbol_font;
+use super::constants::typeface::*;
+use super::eqn::{decode_record, read_nudge_values, read_null_terminated_string, read_ruler, read_size,
+                 CharOptions, DecodeError,
+                 MTChar, MTEmbell, MTEquation, MTHeader, MTLine, MTPile, MTRecords, MTTmpl, TemplateOptions, Typeface};
+use super::error::Error;
+
+/// MTEF 3 record tags, the low nibble of the tag byte.
+const V3_END: u8 = 0;
+const V3_LINE: u8 = 1;
+const V3_CHAR: u8 = 2;
+const V3_TMPL: u8 = 3;
+const V3_PILE: u8 = 4;
+const V3_MATRIX: u8 = 5;
+const V3_EMBELL: u8 = 6;
+const V3_RULER: u8 = 7;
+const V3_FONT: u8 = 8;
+const V3_SIZE: u8 = 9;
+const V3_FULL: u8 = 10;
+const V3_SUB: u8 = 11;
+const V3_SUB2: u8 = 12;
+const V3_SYM: u8 = 13;
+const V3_SUBSYM: u8 = 14;
+
+/// MTEF 3 options, the high nibble of the tag byte.
+/// nudge values follow the tag
+const XF_LMOVE: u8 = 0x8;
+/// CHAR: character starts a function (sin, cos, ...)
+const XF_AUTO: u8 = 0x1;
+/// CHAR: embellishment list follows
+const XF_EMBELL: u8 = 0x2;
+/// LINE: null line
+const XF_NULL: u8 = 0x1;
+/// LINE: RULER record follows
+const XF_RULER: u8 = 0x2;
+/// LINE: line spacing follows
+const XF_LSPACE: u8 = 0x4;
+
+/// Whether `mtef` is older than MTEF 5 and must go through `upgrade`.
+pub(crate) fn needs_upgrade(mtef: &[u8]) -> bool {
+    mtef.first().is_some_and(|&ver| ver < 5)
+}
+
+/// Reads MTEF of any version into the MTEF 5 model.
+pub fn upgrade(buf: &[u8]) -> Result<MTEquation, Error> {
+    let mut cur = Cursor::new(buf);
+    let header = match buf.first() {
+        Some(5) => return MTEquation::parse(buf.to_vec()),
+        Some(3) | Some(4) => MTHeader {
+            mtef_ver: 5,
+            platform: byte(&mut cur).and_then(|_version| byte(&mut cur))?,
+            product: byte(&mut cur)?,
+            version: byte(&mut cur)?,
+            version_sub: byte(&mut cur)?,
+            application: "DSMT4".to_string(),
+            inline: 0,
+        },
+        _ => return Err(Error::MalformedMTEF("unknown MTEF version")),
+    };
+    let mut records = vec![default_prefs(), MTRecords::FULL];
+    let mut fonts = Fonts::default();
+    while (cur.position() as usize) < buf.len() {
+        let record = match buf[0] {
+            3 => v3_record(&mut cur, &mut fonts)?,
+            _ => v4_record(&mut cur, &mut fonts)?,
+        };
+        records.extend(record);
+    }
+    Ok(MTEquation::from_parts(header, records))
+}
+
+/// Definitions made out of MTEF 3/4 FONT records: a FONT_DEF and a FONT_STYLE_DEF
+/// for each, in order, so explicit font typefaces (-1, -2, ...) keep pointing at them.
+#[derive(Default)]
+struct Fonts {
+    count: u8,
+}
+
+impl Fonts {
+    fn font(&mut self, cur: &mut Cursor<&[u8]>) -> Result<Vec<MTRecords>, Error> {
+        let _typeface = byte(cur)?;
+        let char_style = byte(cur)?;
+        let name = read_null_terminated_string(cur).map_err(invalid)?;
+        self.count += 1;
+        Ok(vec![
+            // encoding 2 is "Unknown", the font's own
+            MTRecords::FONT_DEF { enc_def_index: 2, name },
+            MTRecords::FONT_STYLE_DEF { font_def_index: self.count, char_style },
+        ])
+    }
+}
+
+fn v4_record(cur: &mut Cursor<&[u8]>, fonts: &mut Fonts) -> Result<Vec<MTRecords>, Error> {
+    let pos = cur.position() as usize;
+    if cur.get_ref()[pos] == V3_FONT {
+        cur.set_position(pos as u64 + 1);
+        return fonts.font(cur);
+    }
+    Ok(decode_record(cur).map_err(invalid)?.into_iter().collect())
+}
+
+fn v3_record(cur: &mut Cursor<&[u8]>, fonts: &mut Fonts) -> Result<Vec<MTRecords>, Error> {
+    let tag = byte(cur)?;
+    let options = tag >> 4;
+    let nudge = match XF_LMOVE == XF_LMOVE & options && tag & 0x0F != V3_FONT {
+        true => read_nudge_values(cur).map_err(invalid)?,
+        false => (0, 0),
+    };
+    let record = match tag & 0x0F {
+        V3_END => MTRecords::END,
+        V3_LINE => {
+            let mut line = MTLine { nudge, line_spacing: 0, null: XF_NULL == XF_NULL & options, ruler: None };
+            if XF_LSPACE == XF_LSPACE & options {
+                line.line_spacing = byte(cur)?;
+            }
+            if XF_RULER == XF_RULER & options {
+                if byte(cur)? & 0x0F != V3_RULER {
+                    return Err(Error::MalformedMTEF("RULER record expected"));
+                }
+                line.ruler = Some(read_ruler(cur).map_err(invalid)?);
+            }
+            MTRecords::LINE(line)
+        }
+        V3_CHAR => {
+            let typeface = byte(cur)?;
+            let code = cur.read_u16::<LittleEndian>().map_err(|_| truncated())?;
+            let mut ch = MTChar { options: CharOptions::default(), nudge, typeface: Typeface::from_biased(typeface),
+                                  mtcode: None, fp8: None, fp16: None };
+            if nudge != (0, 0) {
+                ch.options |= CharOptions::NUDGE;
+            }
+            if XF_AUTO == XF_AUTO & options {
+                ch.options |= CharOptions::FUNC_START;
+            }
+            if XF_EMBELL == XF_EMBELL & options {
+                ch.options |= CharOptions::EMBELL;
+            }
+            match mtcode(typeface, code) {
+                Some(mtcode) => ch.mtcode = Some(mtcode),
+                // kept as a font position, for a custom CharMapper
+                None => {
+                    ch.options |= CharOptions::ENC_NO_MTCODE | CharOptions::ENC_CHAR_16;
+                    ch.fp16 = Some(code);
+                }
+            }
+            MTRecords::CHAR(ch)
+        }
+        V3_TMPL => {
+            let selector = byte(cur)?;
+            let variation = byte(cur)? as u16;
+            let options = TemplateOptions::decode(selector, byte(cur)?);
+            MTRecords::TMPL(MTTmpl { nudge, selector, variation, options })
+        }
+        V3_PILE => {
+            let mut pile = MTPile { nudge, halign: byte(cur)?, valign: byte(cur)?, ruler: None };
+            if XF_RULER == XF_RULER & options {
+                if byte(cur)? & 0x0F != V3_RULER {
+                    return Err(Error::MalformedMTEF("RULER record expected"));
+                }
+                pile.ruler = Some(read_ruler(cur).map_err(invalid)?);
+            }
+            MTRecords::PILE(pile)
+        }
+        V3_EMBELL => MTRecords::EMBELL(MTEmbell { nudge, embell: byte(cur)? }),
+        V3_FONT => return fonts.font(cur),
+        V3_RULER => {
+            read_ruler(cur).map_err(invalid)?;
+            return Ok(vec![]);
+        }
+        V3_SIZE => MTRecords::SIZE(read_size(cur).map_err(invalid)?),
+        V3_FULL => MTRecords::FULL,
+        V3_SUB => MTRecords::SUB,
+        V3_SUB2 => MTRecords::SUB2,
+        V3_SYM => MTRecords::SYM,
+        V3_SUBSYM => MTRecords::SUBSYM,
+        // not part of the MTEF 5 model yet
+        V3_MATRIX => return Err(Error::NotImplementedYet),
+        _ => return Err(Error::MalformedMTEF("unknown MTEF 3 record")),
+    };
+    Ok(vec![record])
+}
+
+/// MTCode of an MTEF 3 character: Greek and symbol typefaces use Symbol font
+/// positions, text typefaces Windows ANSI (Latin-1 for what equations use).
+fn mtcode(typeface: u8, code: u16) -> Option<u16> {
+    match typeface.checked_sub(128) {
+        Some(FN_LCGREEK) | Some(FN_UCGREEK) | Some(FN_SYMBOL) => symbol_font(code as u8).map(|c| c as u16),
+        Some(FN_MTEXTRA) => None,
+        _ => Some(code),
+    }
+}
+
+/// Default sizes for upgraded equations, which have no EQN_PREFS: full size 12pt,
+/// subscript 58%, sub-subscript 42%, symbol 150%, sub-symbol 100%.
+fn default_prefs() -> MTRecords {
+    MTRecords::EQN_PREFS {
+        sizes: ["pt12", "%58", "%42", "%150", "%100"].iter().map(|s| s.to_string()).collect(),
+        spaces: vec![],
+        styles: vec![],
+    }
+}
+
+fn byte(cur: &mut Cursor<&[u8]>) -> Result<u8, Error> {
+    cur.read_u8().map_err(|_| truncated())
+}
+
+fn truncated() -> Error {
+    Error::MalformedMTEF("truncated MTEF")
+}
+
+fn invalid(e: DecodeError) -> Error {
+    match e {
+        DecodeError::Incomplete => truncated(),
+        DecodeError::Invalid(e) => e,
+    }
+}
@@ -0,0 +1,135 @@
+//! Walking the structure of an equation, for exporters living outside the crate.
+
+use super::eqn::{CharOptions, MTChar, MTEmbell, MTEquation, MTLine, MTPile, MTRecords, MTTmpl};
+
+/// Callbacks of `MTEquation::walk`, all doing nothing by default.
+#[allow(unused_variables)]
+pub trait MtefVisitor {
+    /// A line, or a template slot. Null lines are left right away.
+    fn enter_line(&mut self, line: &MTLine) {}
+    fn leave_line(&mut self, line: &MTLine) {}
+    fn enter_tmpl(&mut self, tmpl: &MTTmpl) {}
+    fn leave_tmpl(&mut self, tmpl: &MTTmpl) {}
+    fn enter_pile(&mut self, pile: &MTPile) {}
+    fn leave_pile(&mut self, pile: &MTPile) {}
+    fn visit_char(&mut self, ch: &MTChar) {}
+    /// An embellishment of the character visited last.
+    fn visit_embell(&mut self, embell: &MTEmbell) {}
+    /// Sizes, colors, definitions and records of unknown types.
+    fn visit_record(&mut self, record: &MTRecords) {}
+}
+
+/// Callbacks of `MTEquation::walk_mut`, as `MtefVisitor` with the records mutable.
+#[allow(unused_variables)]
+pub trait MtefVisitorMut {
+    fn enter_line(&mut self, line: &mut MTLine) {}
+    fn leave_line(&mut self, line: &mut MTLine) {}
+    fn enter_tmpl(&mut self, tmpl: &mut MTTmpl) {}
+    fn leave_tmpl(&mut self, tmpl: &mut MTTmpl) {}
+    fn enter_pile(&mut self, pile: &mut MTPile) {}
+    fn leave_pile(&mut self, pile: &mut MTPile) {}
+    /// The embellishment list of a character flagged with `CharOptions::EMBELL` is
+    /// visited as such even when the callback clears the flag.
+    fn visit_char(&mut self, ch: &mut MTChar) {}
+    fn visit_embell(&mut self, embell: &mut MTEmbell) {}
+    fn visit_record(&mut self, record: &mut MTRecords) {}
+}
+
+/// What an END record closes.
+enum Scope<'a> {
+    Line(&'a MTLine),
+    Tmpl(&'a MTTmpl),
+    Pile(&'a MTPile),
+    Embells,
+}
+
+impl MTEquation {
+    /// Walks the structure of the equation, for exporters living outside the crate.
+    ///
+    /// The records are gone through in stream order, calling `visitor` as lines,
+    /// templates and piles are entered and left, and for each character and
+    /// embellishment. Characters a template draws itself (fences, big operators) are
+    /// visited inside the template, where MathType wrote them. END records closing
+    /// nothing open are ignored.
+    pub fn walk<V: MtefVisitor>(&self, visitor: &mut V) {
+        let mut scopes = vec![];
+        for record in &self.records {
+            match record {
+                MTRecords::END => match scopes.pop() {
+                    Some(Scope::Line(line)) => visitor.leave_line(line),
+                    Some(Scope::Tmpl(tmpl)) => visitor.leave_tmpl(tmpl),
+                    Some(Scope::Pile(pile)) => visitor.leave_pile(pile),
+                    Some(Scope::Embells) | None => {}
+                },
+                MTRecords::LINE(line) => {
+                    visitor.enter_line(line);
+                    match line.null {
+                        true => visitor.leave_line(line),
+                        false => scopes.push(Scope::Line(line)),
+                    }
+                }
+                MTRecords::TMPL(tmpl) => {
+                    visitor.enter_tmpl(tmpl);
+                    scopes.push(Scope::Tmpl(tmpl));
+                }
+                MTRecords::PILE(pile) => {
+                    visitor.enter_pile(pile);
+                    scopes.push(Scope::Pile(pile));
+                }
+                MTRecords::CHAR(ch) => {
+                    visitor.visit_char(ch);
+                    if ch.options.contains(CharOptions::EMBELL) {
+                        scopes.push(Scope::Embells);
+                    }
+                }
+                MTRecords::EMBELL(embell) => visitor.visit_embell(embell),
+                record => visitor.visit_record(record),
+            }
+        }
+    }
+}
+
+impl MTEquation {
+    /// `walk`, with an `MtefVisitorMut` that may change the records it is given in
+    /// place: retype or rewrite characters, move nudges... Records are added or
+    /// dropped through `MTEquation::records_mut`.
+    pub fn walk_mut<V: MtefVisitorMut>(&mut self, visitor: &mut V) {
+        // indices of the records opening the scopes, END records borrow them again
+        let mut scopes: Vec<Option<usize>> = vec![];
+        for i in 0..self.records.len() {
+            match &mut self.records[i] {
+                MTRecords::END => match scopes.pop().flatten().map(|open| &mut self.records[open]) {
+                    Some(MTRecords::LINE(line)) => visitor.leave_line(line),
+                    Some(MTRecords::TMPL(tmpl)) => visitor.leave_tmpl(tmpl),
+                    Some(MTRecords::PILE(pile)) => visitor.leave_pile(pile),
+                    _ => {}
+                },
+                MTRecords::LINE(line) => {
+                    let null = line.null;
+                    visitor.enter_line(line);
+                    match null {
+                        true => visitor.leave_line(line),
+                        false => scopes.push(Some(i)),
+                    }
+                }
+                MTRecords::TMPL(tmpl) => {
+                    visitor.enter_tmpl(tmpl);
+                    scopes.push(Some(i));
+                }
+                MTRecords::PILE(pile) => {
+                    visitor.enter_pile(pile);
+                    scopes.push(Some(i));
+                }
+                MTRecords::CHAR(ch) => {
+                    let embells = ch.options.contains(CharOptions::EMBELL);
+                    visitor.visit_char(ch);
+                    if embells {
+                        scopes.push(None);
+                    }
+                }
+                MTRecords::EMBELL(embell) => visitor.visit_embell(embell),
+                record => visitor.visit_record(record),
+            }
+        }
+    }
+}
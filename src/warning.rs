@@ -0,0 +1,204 @@
+//! Warnings about equations converted anyway: the repairs of lenient parsing, the
+//! matrices left out and the characters no mapper knows. Batch conversions of large corpora meet the same
+//! benign ones over and over; a `WarningFilter` silences them by category, by
+//! character code or by the type of the record a repair was made at.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use super::charmap::{self, CharMapper};
+use super::constants::RecordType;
+use super::eqn::{MTEquation, MTRecords};
+use super::repair::Repair;
+
+/// Something about an equation worth a look, though it was read and translated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// What `ParseMode::Lenient` skipped or closed, see `MTEquation::warnings`.
+    Repair(Repair),
+    /// A matrix left out with all of its cells, MATRIX records not being decoded yet,
+    /// by the offset of its record in the MTEF.
+    SkippedMatrix { offset: usize },
+    /// A character neither the mapper of the translation nor the default mapping
+    /// knows, by encoding and code; reported once per equation.
+    UnmappedChar { encoding: String, code: u16 },
+}
+
+impl Warning {
+    pub fn category(&self) -> WarningCategory {
+        match self {
+            Warning::Repair(Repair::SizeClamped { .. }) => WarningCategory::SizeClamped,
+            Warning::Repair(Repair::TruncatedRecord { .. }) => WarningCategory::TruncatedRecord,
+            Warning::Repair(Repair::InvalidRecord { .. }) => WarningCategory::InvalidRecord,
+            Warning::Repair(Repair::ClosedScopes(_)) => WarningCategory::ClosedScopes,
+            Warning::SkippedMatrix { .. } => WarningCategory::SkippedMatrix,
+            Warning::UnmappedChar { .. } => WarningCategory::UnmappedChar,
+        }
+    }
+
+    /// Type of the record a repair was made at, for truncated and invalid records
+    /// whose tag is a defined type, or MATRIX for skipped matrices.
+    pub fn record_type(&self) -> Option<RecordType> {
+        match self {
+            Warning::Repair(Repair::TruncatedRecord { tag, .. }) | Warning::Repair(Repair::InvalidRecord { tag, .. }) => {
+                RecordType::try_from(*tag).ok()
+            }
+            Warning::SkippedMatrix { .. } => Some(RecordType::Matrix),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Warning::Repair(repair) => write!(f, "{}: {}", self.category(), repair),
+            Warning::SkippedMatrix { offset } => write!(f, "{}: matrix at offset {} left out", self.category(), offset),
+            Warning::UnmappedChar { encoding, code } => write!(f, "{}: {} 0x{:04X}", self.category(), encoding, code),
+        }
+    }
+}
+
+/// Kind of a `Warning`, written as in `WarningFilter` specifications.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningCategory {
+    SizeClamped,
+    TruncatedRecord,
+    InvalidRecord,
+    ClosedScopes,
+    SkippedMatrix,
+    UnmappedChar,
+}
+
+const CATEGORIES: &[(WarningCategory, &str)] = &[
+    (WarningCategory::SizeClamped, "size-clamped"),
+    (WarningCategory::TruncatedRecord, "truncated-record"),
+    (WarningCategory::InvalidRecord, "invalid-record"),
+    (WarningCategory::ClosedScopes, "closed-scopes"),
+    (WarningCategory::SkippedMatrix, "skipped-matrix"),
+    (WarningCategory::UnmappedChar, "unmapped-char"),
+];
+
+impl fmt::Display for WarningCategory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = CATEGORIES.iter().find(|(category, _)| category == self).map(|(_, name)| *name);
+        f.write_str(name.unwrap_or_default())
+    }
+}
+
+/// Warnings to leave out: those of the listed categories, unmapped characters of
+/// the listed codes, and repairs made at records of the listed types.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WarningFilter {
+    pub categories: Vec<WarningCategory>,
+    pub codes: Vec<u16>,
+    pub record_types: Vec<RecordType>,
+}
+
+impl WarningFilter {
+    /// Whether `warning` gets through.
+    pub fn allows(&self, warning: &Warning) -> bool {
+        if self.categories.contains(&warning.category()) {
+            return false;
+        }
+        if let Warning::UnmappedChar { code, .. } = warning {
+            if self.codes.contains(code) {
+                return false;
+            }
+        }
+        !warning.record_type().is_some_and(|record_type| self.record_types.contains(&record_type))
+    }
+}
+
+/// Comma-separated categories ("unmapped-char"), character codes ("0xE000") and
+/// record types ("TMPL"), as given to `mtef convert --ignore`.
+impl FromStr for WarningFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<WarningFilter, String> {
+        let mut filter = WarningFilter::default();
+        for spec in s.split(',').map(str::trim).filter(|spec| !spec.is_empty()) {
+            if let Some((category, _)) = CATEGORIES.iter().find(|(_, name)| *name == spec) {
+                filter.categories.push(*category);
+            } else if let Some(hex) = spec.strip_prefix("0x").or_else(|| spec.strip_prefix("U+")) {
+                filter.codes.push(u16::from_str_radix(hex, 16).map_err(|_| format!("bad character code {}", spec))?);
+            } else {
+                let record_type = (0..=u8::MAX).filter_map(|tag| RecordType::try_from(tag).ok())
+                    .find(|record_type| record_type.name() == spec)
+                    .ok_or_else(|| format!("{} is no warning category nor record type", spec))?;
+                filter.record_types.push(record_type);
+            }
+        }
+        Ok(filter)
+    }
+}
+
+impl MTEquation {
+    /// Warnings about the equation that `filter` lets through: its repairs, its
+    /// skipped matrices, then the characters neither `mapper` nor the default mapping know.
+    pub fn filtered_warnings(&self, filter: &WarningFilter, mapper: Option<&dyn CharMapper>) -> Vec<Warning> {
+        let mut warnings: Vec<Warning> = self.warnings().iter().cloned().map(Warning::Repair).collect();
+        warnings.extend(self.skipped_matrices.iter().map(|&offset| Warning::SkippedMatrix { offset }));
+        for record in self.records() {
+            if let MTRecords::CHAR(ch) = record {
+                if charmap::resolve(self, ch, mapper).is_some() {
+                    continue;
+                }
+                if let Some((encoding, code)) = charmap::char_code(self, ch) {
+                    let warning = Warning::UnmappedChar { encoding: encoding.to_string(), code };
+                    if !warnings.contains(&warning) {
+                        warnings.push(warning);
+                    }
+                }
+            }
+        }
+        warnings.retain(|warning| filter.allows(warning));
+        warnings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::eqn::{ParseMode, ParseOptions};
+
+    /// x then a character known by its position in a font of unknown encoding only,
+    /// and a TMPL record cut short.
+    fn damaged() -> MTEquation {
+        let mut buf = vec![5, 1, 0, 6, 9, b'D', b'S', b'M', b'T', b'6', 0, 0, 17, 2];
+        buf.extend_from_slice(b"Times New Roman\0");
+        buf.extend_from_slice(&[18, 0, 1, 0x22, 0xf0, 0, 3, 1, 0, 0, 1, 1]);
+        buf.extend_from_slice(&[1, 0, 2, 0, 131, b'x', 0, 2, 0x24, 131, 0xFE, 3, 0]);
+        let options = ParseOptions { mode: ParseMode::Lenient, ..Default::default() };
+        MTEquation::parse_with(buf, &options).unwrap()
+    }
+
+    fn categories(warnings: &[Warning]) -> Vec<WarningCategory> {
+        warnings.iter().map(Warning::category).collect()
+    }
+
+    #[test]
+    fn all_warnings() {
+        let warnings = damaged().filtered_warnings(&WarningFilter::default(), None);
+        assert_eq!(categories(&warnings),
+                   [WarningCategory::TruncatedRecord, WarningCategory::ClosedScopes, WarningCategory::UnmappedChar]);
+        assert_eq!(warnings[0].record_type(), Some(RecordType::Tmpl));
+        assert_eq!(warnings[2].to_string(), "unmapped-char: Unknown 0x00FE");
+    }
+
+    #[test]
+    fn filtered_warnings() {
+        let eqn = damaged();
+        let filter: WarningFilter = "closed-scopes, TMPL".parse().unwrap();
+        assert_eq!(categories(&eqn.filtered_warnings(&filter, None)), [WarningCategory::UnmappedChar]);
+        let filter: WarningFilter = "0xFE,truncated-record,closed-scopes".parse().unwrap();
+        assert!(eqn.filtered_warnings(&filter, None).is_empty());
+    }
+
+    #[test]
+    fn bad_filters() {
+        assert!("0xZZ".parse::<WarningFilter>().is_err());
+        assert!("everything".parse::<WarningFilter>().is_err());
+    }
+}
@@ -0,0 +1,574 @@
+//! MTEF serialization, the reverse of `MTEquation::parse`.
+//!
+//! MathType does not validate what it reads and may crash on malformed MTEF, so
+//! `to_bytes_strict` checks its output with `validate_strict` before handing it out.
+//! Equations built record by record may need `canonicalize` first, to put their
+//! definition records where MathType expects them.
+
+use std::io::{Cursor, Read};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use encoding::{EncoderTrap, Encoding};
+use encoding::all::GBK;
+
+use super::constants::embellishments::{EMB_1DOT, EMB_U_L1ARROW};
+use super::constants::options::*;
+use super::constants::pile_align::{PHA_DECIMAL, PHA_LEFT, PVA_MATH};
+use super::constants::record_types::*;
+use super::constants::selectors::TM_BOX;
+use super::constants::tab_stops::TAB_DECIMAL;
+use super::constants::typesize::SZ_DELTA;
+use super::eqn::{CharOptions, ColorDefOptions, ColorValue, LineOptions, MTEquation, MTRecords, MTRuler, MTSize, PileOptions};
+use super::error::Error;
+
+/// Encodings known without an ENCODING_DEF record: MTCode, Unknown, Symbol and MTExtra.
+const PREDEFINED_ENCODINGS: usize = 4;
+
+impl MTEquation {
+    /// MTEF bytes of the equation, without the 28-byte OLE file header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = vec![
+            self.m_mtef_ver, self.m_platform, self.m_product, self.m_version, self.m_version_sub,
+        ];
+        write_string(&mut buf, &self.m_application);
+        buf.push(self.m_inline);
+        for record in &self.records {
+            write_record(&mut buf, record);
+        }
+        buf
+    }
+
+    /// Same as `to_bytes`, refusing output that fails `validate_strict`.
+    pub fn to_bytes_strict(&self) -> Result<Vec<u8>, Error> {
+        let buf = self.to_bytes();
+        validate_strict(&buf)?;
+        Ok(buf)
+    }
+
+    /// Moves the ENCODING_DEF, FONT_DEF and EQN_PREFS records in front of all others,
+    /// in that order, dropping duplicate definitions and all EQN_PREFS but the first.
+    /// FONT_DEF, FONT_STYLE_DEF and EQN_PREFS records referring to a moved or dropped
+    /// definition are remapped, so characters keep their fonts.
+    pub fn canonicalize(&mut self) {
+        let records = std::mem::take(&mut self.records);
+
+        let mut encodings: Vec<String> = vec![];
+        // new index of each ENCODING_DEF, in stream order
+        let mut encoding_index = vec![];
+        for record in &records {
+            if let MTRecords::ENCODING_DEF(name) = record {
+                let i = encodings.iter().position(|e| e == name).unwrap_or_else(|| {
+                    encodings.push(name.clone());
+                    encodings.len() - 1
+                });
+                encoding_index.push((PREDEFINED_ENCODINGS + i + 1) as u8);
+            }
+        }
+        let encoding = |old: u8| match (old as usize).checked_sub(PREDEFINED_ENCODINGS + 1) {
+            Some(i) => encoding_index.get(i).copied().unwrap_or(old),
+            None => old,
+        };
+
+        let mut fonts: Vec<(u8, String)> = vec![];
+        // new index of each FONT_DEF, in stream order
+        let mut font_index = vec![];
+        for record in &records {
+            if let MTRecords::FONT_DEF { enc_def_index, name } = record {
+                let font = (encoding(*enc_def_index), name.clone());
+                let i = fonts.iter().position(|f| *f == font).unwrap_or_else(|| {
+                    fonts.push(font);
+                    fonts.len() - 1
+                });
+                font_index.push((i + 1) as u8);
+            }
+        }
+        // indices are 1-based
+        let font = |old: u8| match (old as usize).checked_sub(1) {
+            Some(i) => font_index.get(i).copied().unwrap_or(old),
+            None => old,
+        };
+
+        let mut prefs = None;
+        let mut rest = vec![];
+        for record in records {
+            match record {
+                MTRecords::ENCODING_DEF(_) | MTRecords::FONT_DEF { .. } => {}
+                MTRecords::EQN_PREFS { sizes, spaces, styles } => {
+                    if prefs.is_none() {
+                        let styles = styles.into_iter().map(|style| style.map(|(f, s)| (font(f), s))).collect();
+                        prefs = Some(MTRecords::EQN_PREFS { sizes, spaces, styles });
+                    }
+                }
+                MTRecords::FONT_STYLE_DEF { font_def_index, char_style } => {
+                    rest.push(MTRecords::FONT_STYLE_DEF { font_def_index: font(font_def_index), char_style });
+                }
+                record => rest.push(record),
+            }
+        }
+
+        self.records = encodings.into_iter().map(MTRecords::ENCODING_DEF)
+            .chain(fonts.into_iter().map(|(enc_def_index, name)| MTRecords::FONT_DEF { enc_def_index, name }))
+            .chain(prefs)
+            .chain(rest)
+            .collect();
+    }
+}
+
+pub(crate) fn write_record(buf: &mut Vec<u8>, record: &MTRecords) {
+    match record {
+        MTRecords::END => buf.push(END),
+        MTRecords::LINE(line) => {
+            buf.extend_from_slice(&[LINE, line.options().bits()]);
+            if line.nudge != (0, 0) {
+                write_nudge(buf, line.nudge);
+            }
+            if line.line_spacing != 0 {
+                buf.push(line.line_spacing);
+            }
+            if let Some(ruler) = &line.ruler {
+                write_ruler(buf, ruler);
+            }
+        }
+        MTRecords::PILE(pile) => {
+            buf.extend_from_slice(&[PILE, pile.options().bits()]);
+            if pile.nudge != (0, 0) {
+                write_nudge(buf, pile.nudge);
+            }
+            buf.extend_from_slice(&[pile.halign, pile.valign]);
+            if let Some(ruler) = &pile.ruler {
+                write_ruler(buf, ruler);
+            }
+        }
+        MTRecords::CHAR(ch) => {
+            buf.extend_from_slice(&[CHAR, ch.options.bits()]);
+            if ch.options.contains(CharOptions::NUDGE) {
+                write_nudge(buf, ch.nudge);
+            }
+            buf.push(ch.typeface.biased());
+            if !ch.options.contains(CharOptions::ENC_NO_MTCODE) {
+                buf.write_u16::<LittleEndian>(ch.mtcode.unwrap_or(0)).unwrap();
+            }
+            if ch.options.contains(CharOptions::ENC_CHAR_8) {
+                buf.push(ch.fp8.unwrap_or(0));
+            }
+            if ch.options.contains(CharOptions::ENC_CHAR_16) {
+                buf.write_u16::<LittleEndian>(ch.fp16.unwrap_or(0)).unwrap();
+            }
+        }
+        MTRecords::TMPL(tmpl) => {
+            let options = match tmpl.nudge != (0, 0) {
+                true => MTEF_OPT_NUDGE,
+                false => 0,
+            };
+            buf.extend_from_slice(&[TMPL, options]);
+            if tmpl.nudge != (0, 0) {
+                write_nudge(buf, tmpl.nudge);
+            }
+            buf.push(tmpl.selector);
+            // variation, 1 or 2 bytes
+            match tmpl.variation < 0x80 {
+                true => buf.push(tmpl.variation as u8),
+                false => buf.extend_from_slice(&[(tmpl.variation & 0x7F) as u8 | 0x80, (tmpl.variation >> 8) as u8]),
+            }
+            buf.push(tmpl.options.bits());
+        }
+        MTRecords::EMBELL(embell) => {
+            let options = match embell.nudge != (0, 0) {
+                true => MTEF_OPT_NUDGE,
+                false => 0,
+            };
+            buf.extend_from_slice(&[EMBELL, options]);
+            if embell.nudge != (0, 0) {
+                write_nudge(buf, embell.nudge);
+            }
+            buf.push(embell.embell);
+        }
+        MTRecords::ENCODING_DEF(name) => {
+            buf.push(ENCODING_DEF);
+            write_string(buf, name);
+        }
+        MTRecords::FONT_DEF { enc_def_index, name } => {
+            buf.extend_from_slice(&[FONT_DEF, *enc_def_index]);
+            write_string(buf, name);
+        }
+        MTRecords::FONT_STYLE_DEF { font_def_index, char_style } => {
+            buf.extend_from_slice(&[FONT_STYLE_DEF, *font_def_index, *char_style]);
+        }
+        MTRecords::EQN_PREFS { sizes, spaces, styles } => {
+            buf.extend_from_slice(&[EQN_PREFS, 0]);
+            write_dimension_arrays(buf, sizes);
+            write_dimension_arrays(buf, spaces);
+            buf.push(styles.len() as u8);
+            for style in styles {
+                match style {
+                    Some((font_def_index, char_style)) => buf.extend_from_slice(&[*font_def_index, *char_style]),
+                    None => buf.push(0),
+                }
+            }
+        }
+        MTRecords::SIZE(size) => {
+            buf.push(SIZE);
+            match *size {
+                MTSize::Points(points) => {
+                    buf.push(101);
+                    buf.write_u16::<LittleEndian>(points).unwrap();
+                }
+                // small deltas take a byte, biased by 128
+                MTSize::Delta { lsize, delta } if (-128..=127).contains(&delta) => {
+                    buf.extend_from_slice(&[lsize, (delta + 128) as u8]);
+                }
+                MTSize::Delta { lsize, delta } => {
+                    buf.extend_from_slice(&[100, lsize]);
+                    buf.write_i16::<LittleEndian>(delta).unwrap();
+                }
+            }
+        }
+        MTRecords::FULL => buf.push(FULL),
+        MTRecords::SUB => buf.push(SUB),
+        MTRecords::SUB2 => buf.push(SUB2),
+        MTRecords::SYM => buf.push(SYM),
+        MTRecords::SUBSYM => buf.push(SUBSYM),
+        MTRecords::COLOR(color_def_index) => buf.extend_from_slice(&[COLOR, *color_def_index]),
+        MTRecords::COLOR_DEF(def) => {
+            buf.extend_from_slice(&[COLOR_DEF, def.options().bits()]);
+            let components: &[u16] = match &def.value {
+                ColorValue::Rgb(rgb) => rgb,
+                ColorValue::Cmyk(cmyk) => cmyk,
+            };
+            for component in components {
+                buf.write_u16::<LittleEndian>(*component).unwrap();
+            }
+            if let Some(name) = &def.name {
+                write_string(buf, name);
+            }
+        }
+        MTRecords::FUTURE(record) => {
+            buf.push(record.tag);
+            if record.tag >= FUTURE {
+                buf.write_u16::<LittleEndian>(record.bytes.len() as u16).unwrap();
+                buf.extend_from_slice(&record.bytes);
+            }
+        }
+    }
+}
+
+fn write_ruler(buf: &mut Vec<u8>, ruler: &MTRuler) {
+    buf.extend_from_slice(&[RULER, ruler.tab_stops.len() as u8]);
+    for stop in &ruler.tab_stops {
+        buf.push(stop.kind);
+        buf.write_i16::<LittleEndian>(stop.offset).unwrap();
+    }
+}
+
+fn write_string(buf: &mut Vec<u8>, s: &str) {
+    buf.extend(GBK.encode(s, EncoderTrap::Replace).unwrap());
+    buf.push(0);
+}
+
+/// Nudges fitting in a byte are written biased by 128, others after a 128, 128
+/// escape. A byte of 128 being the escape, offsets of 0 take the long form too.
+fn write_nudge(buf: &mut Vec<u8>, (dx, dy): (i16, i16)) {
+    let short = |offset: i16| (-128..=127).contains(&offset) && offset != 0;
+    match short(dx) && short(dy) {
+        true => buf.extend_from_slice(&[(dx + 128) as u8, (dy + 128) as u8]),
+        false => {
+            buf.extend_from_slice(&[128, 128]);
+            buf.write_i16::<LittleEndian>(dx).unwrap();
+            buf.write_i16::<LittleEndian>(dy).unwrap();
+        }
+    }
+}
+
+/// Dimensions as parsed by `read_dimension_arrays`, unit first ("pt12"), packed
+/// into nibbles with 0xF closing each value.
+fn write_dimension_arrays(buf: &mut Vec<u8>, dims: &[String]) {
+    let mut nibbles = vec![];
+    for dim in dims {
+        let (unit, value) = ["in", "cm", "pt", "pc", "%"].iter().enumerate()
+            .find(|(_, u)| dim.starts_with(*u))
+            .map(|(i, u)| (i as u8, &dim[u.len()..]))
+            .unwrap_or((2, dim.as_str()));
+        nibbles.push(unit);
+        for c in value.chars() {
+            match c {
+                '0'..='9' => nibbles.push(c as u8 - b'0'),
+                '.' => nibbles.push(0x0a),
+                '-' => nibbles.push(0x0b),
+                _ => {}
+            }
+        }
+        nibbles.push(0x0f);
+    }
+    buf.push(dims.len() as u8);
+    for pair in nibbles.chunks(2) {
+        buf.push(pair[0] << 4 | pair.get(1).cloned().unwrap_or(0));
+    }
+}
+
+/// What an END record closes.
+#[derive(Debug, PartialEq)]
+enum Scope {
+    Equation,
+    Line,
+    Tmpl,
+    Pile,
+    Embells,
+}
+
+/// Checks MTEF bytes against the constraints MathType relies on when reading:
+///
+/// - a version 5 header with a terminated application name,
+/// - ENCODING_DEF, FONT_DEF and EQN_PREFS before the first line, EQN_PREFS exactly once,
+/// - font and style definitions referring to already defined encodings and fonts,
+/// - rulers only right after their line or pile, with known tab-stop types,
+/// - known template selectors, embellishments, pile alignments and typesizes,
+/// - EMBELL records only in an embellishment list, piles only in a line, as a template
+///   slot or at the top level,
+/// - every line, template, pile and embellishment list closed by an END, the equation by
+///   a last END, and nothing after it.
+///
+/// MATRIX records, which the writer never produces, are not checked and refused.
+pub fn validate_strict(buf: &[u8]) -> Result<(), Error> {
+    let mut cur = Cursor::new(buf);
+    let mut header = [0u8; 5];
+    cur.read_exact(&mut header).map_err(|_| truncated())?;
+    if header[0] != 5 {
+        return Err(Error::MalformedMTEF("only MTEF version 5 is supported"));
+    }
+    skip_string(&mut cur)?;
+    byte(&mut cur)?;
+
+    let mut encodings = PREDEFINED_ENCODINGS;
+    let mut fonts = 0;
+    let mut prefs = false;
+    let mut content = false;
+    let mut scopes = vec![Scope::Equation];
+    while let Ok(tag) = cur.read_u8() {
+        let top = match scopes.last() {
+            Some(top) => top,
+            None => return Err(Error::MalformedMTEF("records after the closing END")),
+        };
+        if *top == Scope::Embells && tag != EMBELL && tag != END {
+            return Err(Error::MalformedMTEF("embellishment list holds other records"));
+        }
+        match tag {
+            END => { scopes.pop(); }
+            LINE => {
+                if !prefs {
+                    return Err(Error::MalformedMTEF("EQN_PREFS must precede the first line"));
+                }
+                content = true;
+                let options = byte(&mut cur)?;
+                skip_nudge(&mut cur, options)?;
+                let options = LineOptions::from_bits(options);
+                if options.contains(LineOptions::LSPACE) {
+                    byte(&mut cur)?;
+                }
+                if options.contains(LineOptions::RULER) {
+                    skip_ruler(&mut cur)?;
+                }
+                if !options.contains(LineOptions::NULL) {
+                    scopes.push(Scope::Line);
+                }
+            }
+            CHAR => {
+                // templates hold their fence and operator characters next to the slots
+                if *top != Scope::Line && *top != Scope::Tmpl {
+                    return Err(Error::MalformedMTEF("character outside of a line or template"));
+                }
+                let options = byte(&mut cur)?;
+                skip_nudge(&mut cur, options)?;
+                byte(&mut cur)?;
+                let options = CharOptions::from_bits(options);
+                if !options.contains(CharOptions::ENC_NO_MTCODE) {
+                    skip(&mut cur, 2)?;
+                }
+                if options.contains(CharOptions::ENC_CHAR_8) {
+                    skip(&mut cur, 1)?;
+                }
+                if options.contains(CharOptions::ENC_CHAR_16) {
+                    skip(&mut cur, 2)?;
+                }
+                if options.contains(CharOptions::EMBELL) {
+                    scopes.push(Scope::Embells);
+                }
+            }
+            TMPL => {
+                if *top != Scope::Line {
+                    return Err(Error::MalformedMTEF("template outside of a line"));
+                }
+                let options = byte(&mut cur)?;
+                skip_nudge(&mut cur, options)?;
+                if byte(&mut cur)? > TM_BOX {
+                    return Err(Error::MalformedMTEF("unknown template selector"));
+                }
+                if 0x80 == byte(&mut cur)? & 0x80 {
+                    byte(&mut cur)?;
+                }
+                byte(&mut cur)?;
+                scopes.push(Scope::Tmpl);
+            }
+            PILE => {
+                // a pile may stand for a template slot
+                if *top != Scope::Line && *top != Scope::Equation && *top != Scope::Tmpl {
+                    return Err(Error::MalformedMTEF("pile outside of a line or template"));
+                }
+                if !prefs {
+                    return Err(Error::MalformedMTEF("EQN_PREFS must precede the first line"));
+                }
+                content = true;
+                let options = byte(&mut cur)?;
+                skip_nudge(&mut cur, options)?;
+                if !(PHA_LEFT..=PHA_DECIMAL).contains(&byte(&mut cur)?) || byte(&mut cur)? > PVA_MATH {
+                    return Err(Error::MalformedMTEF("unknown pile alignment"));
+                }
+                if PileOptions::from_bits(options).contains(PileOptions::RULER) {
+                    skip_ruler(&mut cur)?;
+                }
+                scopes.push(Scope::Pile);
+            }
+            EMBELL => {
+                if *top != Scope::Embells {
+                    return Err(Error::MalformedMTEF("embellishment outside of an embellishment list"));
+                }
+                let options = byte(&mut cur)?;
+                skip_nudge(&mut cur, options)?;
+                let embell = byte(&mut cur)?;
+                if !(EMB_1DOT..=EMB_U_L1ARROW).contains(&embell) {
+                    return Err(Error::MalformedMTEF("unknown embellishment"));
+                }
+            }
+            FONT_STYLE_DEF => {
+                if byte(&mut cur)? as usize > fonts {
+                    return Err(Error::MalformedMTEF("FONT_STYLE_DEF refers to an undefined font"));
+                }
+                byte(&mut cur)?;
+            }
+            FULL | SUB | SUB2 | SYM | SUBSYM => {}
+            COLOR => {
+                byte(&mut cur)?;
+            }
+            COLOR_DEF => {
+                let options = ColorDefOptions::from_bits(byte(&mut cur)?);
+                skip(&mut cur, match options.contains(ColorDefOptions::CMYK) {
+                    true => 8,
+                    false => 6,
+                })?;
+                if options.contains(ColorDefOptions::NAME) {
+                    skip_string(&mut cur)?;
+                }
+            }
+            SIZE => match byte(&mut cur)? {
+                101 => skip(&mut cur, 2)?,
+                100 => {
+                    if byte(&mut cur)? > SZ_DELTA {
+                        return Err(Error::MalformedMTEF("unknown typesize"));
+                    }
+                    skip(&mut cur, 2)?;
+                }
+                lsize if lsize > SZ_DELTA => return Err(Error::MalformedMTEF("unknown typesize")),
+                _ => skip(&mut cur, 1)?,
+            },
+            ENCODING_DEF | FONT_DEF | EQN_PREFS if content => {
+                return Err(Error::MalformedMTEF("definition record after the first line"));
+            }
+            ENCODING_DEF => {
+                skip_string(&mut cur)?;
+                encodings += 1;
+            }
+            FONT_DEF => {
+                let enc_def_index = byte(&mut cur)? as usize;
+                if enc_def_index == 0 || enc_def_index > encodings {
+                    return Err(Error::MalformedMTEF("FONT_DEF refers to an undefined encoding"));
+                }
+                skip_string(&mut cur)?;
+                fonts += 1;
+            }
+            EQN_PREFS => {
+                if prefs {
+                    return Err(Error::MalformedMTEF("more than one EQN_PREFS"));
+                }
+                prefs = true;
+                byte(&mut cur)?;
+                skip_dimension_arrays(&mut cur)?;
+                skip_dimension_arrays(&mut cur)?;
+                for _ in 0..byte(&mut cur)? {
+                    let font_def_index = byte(&mut cur)? as usize;
+                    if font_def_index > fonts {
+                        return Err(Error::MalformedMTEF("EQN_PREFS style refers to an undefined font"));
+                    }
+                    if font_def_index != 0 {
+                        byte(&mut cur)?;
+                    }
+                }
+            }
+            tag if tag >= FUTURE => {
+                let len = cur.read_u16::<LittleEndian>().map_err(|_| truncated())?;
+                skip(&mut cur, len as usize)?;
+            }
+            _ => return Err(Error::NotImplementedYet),
+        }
+    }
+    match scopes.is_empty() {
+        true => Ok(()),
+        false => Err(Error::MalformedMTEF("unbalanced END records")),
+    }
+}
+
+fn truncated() -> Error {
+    Error::MalformedMTEF("truncated record")
+}
+
+fn byte(cur: &mut Cursor<&[u8]>) -> Result<u8, Error> {
+    cur.read_u8().map_err(|_| truncated())
+}
+
+fn skip(cur: &mut Cursor<&[u8]>, n: usize) -> Result<(), Error> {
+    for _ in 0..n {
+        byte(cur)?;
+    }
+    Ok(())
+}
+
+fn skip_string(cur: &mut Cursor<&[u8]>) -> Result<(), Error> {
+    while byte(cur)? != 0 {}
+    Ok(())
+}
+
+fn skip_nudge(cur: &mut Cursor<&[u8]>, options: u8) -> Result<(), Error> {
+    if MTEF_OPT_NUDGE == MTEF_OPT_NUDGE & options {
+        let (dx, dy) = (byte(cur)?, byte(cur)?);
+        if dx == 128 || dy == 128 {
+            skip(cur, 4)?;
+        }
+    }
+    Ok(())
+}
+
+/// RULER record of a line or pile, the tag included.
+fn skip_ruler(cur: &mut Cursor<&[u8]>) -> Result<(), Error> {
+    if byte(cur)? != RULER {
+        return Err(Error::MalformedMTEF("RULER record expected"));
+    }
+    for _ in 0..byte(cur)? {
+        if byte(cur)? > TAB_DECIMAL {
+            return Err(Error::MalformedMTEF("unknown tab-stop type"));
+        }
+        skip(cur, 2)?;
+    }
+    Ok(())
+}
+
+fn skip_dimension_arrays(cur: &mut Cursor<&[u8]>) -> Result<(), Error> {
+    let count = byte(cur)?;
+    let mut closed = 0;
+    while closed < count {
+        let b = byte(cur)?;
+        for nibble in &[b >> 4, b & 0x0f] {
+            if closed < count && *nibble == 0x0f {
+                closed += 1;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Every legal combination of CHAR options, read and written back byte for byte.
+//!
+//! A character has an MTCode, a font position or both, and at most one font
+//! position: 5 encodings, each with or without EMBELL, FUNC_START and NUDGE.
+
+#![cfg(feature = "std")]
+
+use mtef::parser::{CharOptions, MTEquation, MTRecords};
+
+/// Header, a font and the equation preferences.
+const PREFIX: &[u8] = &[
+    5, 1, 0, 6, 9, b'D', b'S', b'M', b'T', b'6', 0, 0,
+    17, 1, b'T', b'i', b'm', b'e', b's', 0,
+    18, 0, 1, 0x22, 0xf0, 0, 3, 1, 0, 0, 1, 1,
+];
+
+fn legal_options() -> Vec<CharOptions> {
+    let encodings = [
+        CharOptions::default(),
+        CharOptions::ENC_CHAR_8,
+        CharOptions::ENC_CHAR_16,
+        CharOptions::ENC_NO_MTCODE | CharOptions::ENC_CHAR_8,
+        CharOptions::ENC_NO_MTCODE | CharOptions::ENC_CHAR_16,
+    ];
+    let mut all = vec![];
+    for encoding in encodings {
+        for extra in 0..8 {
+            let mut options = encoding;
+            for (bit, flag) in [CharOptions::EMBELL, CharOptions::FUNC_START, CharOptions::NUDGE].iter().enumerate() {
+                if extra & 1 << bit != 0 {
+                    options |= *flag;
+                }
+            }
+            all.push(options);
+        }
+    }
+    all
+}
+
+/// A line holding one CHAR record with `options`, each field in its own value so a
+/// field read in the place of another shows.
+fn equation(options: CharOptions) -> Vec<u8> {
+    let mut buf = PREFIX.to_vec();
+    buf.extend_from_slice(&[1, 0, 2, options.bits()]);
+    if options.contains(CharOptions::NUDGE) {
+        // 3 right, 2 up, biased by 128
+        buf.extend_from_slice(&[131, 126]);
+    }
+    // variable
+    buf.push(131);
+    if !options.contains(CharOptions::ENC_NO_MTCODE) {
+        buf.extend_from_slice(&[0xB1, 0x03]);
+    }
+    if options.contains(CharOptions::ENC_CHAR_8) {
+        buf.push(0x61);
+    }
+    if options.contains(CharOptions::ENC_CHAR_16) {
+        buf.extend_from_slice(&[0x62, 0x01]);
+    }
+    if options.contains(CharOptions::EMBELL) {
+        // a prime, then the END of the embellishment list
+        buf.extend_from_slice(&[6, 0, 5, 0]);
+    }
+    buf.extend_from_slice(&[0, 0]);
+    buf
+}
+
+#[test]
+fn there_are_40_legal_combinations() {
+    assert_eq!(legal_options().len(), 40);
+}
+
+#[test]
+fn every_combination_is_read() {
+    for options in legal_options() {
+        let eqn = MTEquation::from_bytes(&equation(options)).unwrap();
+        let ch = eqn.records().find_map(|record| match record {
+            MTRecords::CHAR(ch) => Some(ch),
+            _ => None,
+        }).unwrap();
+        assert_eq!(ch.options(), options);
+        let mtcode = match options.contains(CharOptions::ENC_NO_MTCODE) {
+            true => None,
+            false => Some(0x03B1),
+        };
+        assert_eq!(ch.mtcode(), mtcode, "options {:#04x}", options.bits());
+        let embells = eqn.records().filter(|record| matches!(record, MTRecords::EMBELL(_))).count();
+        assert_eq!(embells, options.contains(CharOptions::EMBELL) as usize, "options {:#04x}", options.bits());
+    }
+}
+
+#[test]
+fn every_combination_round_trips() {
+    for options in legal_options() {
+        let buf = equation(options);
+        let eqn = MTEquation::from_bytes(&buf).unwrap();
+        assert_eq!(eqn.to_bytes(), buf, "options {:#04x}", options.bits());
+        mtef::output::validate_strict(&buf).unwrap();
+    }
+}
+
+/// A CHAR nudged by `nudge` bytes, its font position only.
+fn nudged(nudge: &[u8]) -> Vec<u8> {
+    let mut buf = PREFIX.to_vec();
+    buf.extend_from_slice(&[1, 0, 2, (CharOptions::ENC_NO_MTCODE | CharOptions::ENC_CHAR_8 | CharOptions::NUDGE).bits()]);
+    buf.extend_from_slice(nudge);
+    buf.extend_from_slice(&[131, 0x61, 0, 0]);
+    buf
+}
+
+#[test]
+fn long_form_nudges_keep_their_offsets() {
+    // 5 right and 7 down, written long though a byte would do
+    let eqn = MTEquation::from_bytes(&nudged(&[128, 128, 5, 0, 7, 0])).unwrap();
+    assert_eq!(eqn.to_bytes(), nudged(&[133, 135]));
+    assert_eq!(MTEquation::from_bytes(&eqn.to_bytes()).unwrap(), eqn);
+    // 300 left and 2 down, too far for a byte
+    let buf = nudged(&[128, 128, 0xD4, 0xFE, 2, 0]);
+    assert_eq!(MTEquation::from_bytes(&buf).unwrap().to_bytes(), buf);
+}
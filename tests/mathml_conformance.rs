@@ -0,0 +1,109 @@
+//! Structure of the MathML written for every fixture and for every template the
+//! MathML backend can meet: well-formed XML, presentation MathML elements only,
+//! each with the children the MathML 3 schema allows it.
+//!
+//! Run with `cargo test --features conformance-tests`.
+
+#![cfg(feature = "conformance-tests")]
+
+use std::path::Path;
+
+use mtef::parser::MTEquation;
+use roxmltree::{Document, Node};
+
+const MATHML_NS: &str = "http://www.w3.org/1998/Math/MathML";
+
+/// Elements with their number of children, `None` for any number.
+const ELEMENTS: &[(&str, Option<usize>)] = &[
+    ("math", None), ("mrow", None), ("mstyle", None), ("mpadded", None), ("mphantom", None),
+    ("mi", None), ("mn", None), ("mo", None), ("mtext", None), ("mspace", Some(0)),
+    ("mfrac", Some(2)), ("msqrt", None), ("mroot", Some(2)), ("menclose", None),
+    ("msub", Some(2)), ("msup", Some(2)), ("msubsup", Some(3)),
+    ("munder", Some(2)), ("mover", Some(2)), ("munderover", Some(3)),
+    ("mmultiscripts", None), ("mprescripts", Some(0)), ("none", Some(0)),
+    ("mtable", None), ("mtr", None), ("mtd", None),
+];
+
+const TOKENS: &[&str] = &["mi", "mn", "mo", "mtext"];
+
+fn check(mathml: &str, what: &str) {
+    let doc = Document::parse(mathml).unwrap_or_else(|e| panic!("{}: {}\n{}", what, e, mathml));
+    let root = doc.root_element();
+    assert_eq!(root.tag_name().name(), "math", "{}", what);
+    for node in root.descendants().filter(Node::is_element) {
+        let context = || format!("{}: <{}> in\n{}", what, node.tag_name().name(), mathml);
+        assert_eq!(node.tag_name().namespace(), Some(MATHML_NS), "{}", context());
+        let name = node.tag_name().name();
+        let arity = ELEMENTS.iter().find(|(element, _)| *element == name)
+            .unwrap_or_else(|| panic!("not presentation MathML, {}", context())).1;
+        let children: Vec<Node> = node.children().filter(Node::is_element).collect();
+        if TOKENS.contains(&name) {
+            assert!(children.is_empty(), "token with elements, {}", context());
+            continue;
+        }
+        assert!(node.children().all(|child| child.is_element() || child.text().unwrap_or("").trim().is_empty()),
+                "text outside of a token, {}", context());
+        if let Some(arity) = arity {
+            assert_eq!(children.len(), arity, "{}", context());
+        }
+        let names: Vec<&str> = children.iter().map(|child| child.tag_name().name()).collect();
+        match name {
+            "mtable" => assert!(names.iter().all(|child| *child == "mtr"), "{}", context()),
+            "mtr" => assert!(names.iter().all(|child| *child == "mtd"), "{}", context()),
+            "mtd" | "math" | "mrow" => assert!(!names.iter().any(|child| ["mtr", "mtd"].contains(child)), "{}", context()),
+            "mmultiscripts" => {
+                // a base, then pairs of scripts on either side of one <mprescripts/>
+                assert!(names.iter().filter(|child| **child == "mprescripts").count() <= 1, "{}", context());
+                assert!(!names.is_empty() && names[0] != "mprescripts", "{}", context());
+                assert_eq!(names.len() % 2, 0, "{}", context());
+            }
+            _ => {}
+        }
+    }
+}
+
+fn mtef_files(dir: &Path) -> Vec<std::path::PathBuf> {
+    let mut files: Vec<_> = std::fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "mtef"))
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn sample_object() {
+    let eqn = MTEquation::from_ole("assets/oleObject1.bin").unwrap();
+    check(&eqn.to_mathml().unwrap(), "assets/oleObject1.bin");
+}
+
+#[test]
+fn fixtures() {
+    let files: Vec<_> = ["tests/fixtures", "tests/regressions"].iter().flat_map(|dir| mtef_files(Path::new(dir))).collect();
+    assert!(!files.is_empty());
+    for file in files {
+        let eqn = MTEquation::from_bytes(&std::fs::read(&file).unwrap()).unwrap();
+        check(&eqn.to_mathml().unwrap(), &file.display().to_string());
+    }
+}
+
+/// Every template selector with every one-byte variation, its first three slots
+/// holding x, 2 and a pile of y and z.
+#[test]
+fn templates() {
+    let mut prefix = vec![5, 1, 0, 6, 9, b'D', b'S', b'M', b'T', b'6', 0, 0, 17, 1];
+    prefix.extend_from_slice(b"Times New Roman\0");
+    prefix.extend_from_slice(&[18, 0, 1, 0x22, 0xf0, 0, 3, 1, 0, 0, 1, 1]);
+    for selector in 0..=37 {
+        for variation in 0..0x80 {
+            let mut buf = prefix.clone();
+            buf.extend_from_slice(&[1, 0, 3, 0, selector, variation, 0]);
+            buf.extend_from_slice(&[1, 0, 2, 0, 131, b'x', 0, 0]);
+            buf.extend_from_slice(&[1, 0, 2, 0, 136, b'2', 0, 0]);
+            buf.extend_from_slice(&[4, 0, 1, 1, 1, 0, 2, 0, 131, b'y', 0, 0, 1, 0, 2, 0, 131, b'z', 0, 0, 0]);
+            buf.extend_from_slice(&[0, 2, 0, 131, b'a', 0, 0, 0]);
+            let eqn = MTEquation::from_bytes(&buf).unwrap();
+            check(&eqn.to_mathml().unwrap(), &format!("selector {} variation {:#04x}", selector, variation));
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Equations from bug reports, each with the behavior expected of it.
+//!
+//! Reported files are reduced with `parser::minimize_failing_input` to the records
+//! the bug needs, saved here as `<what went wrong>.mtef` (MTEF with its header, no
+//! OLE wrapping), and get a test below.
+
+#![cfg(feature = "std")]
+
+use mtef::parser::{minimize_failing_input, MTEquation, ParseMode, ParseOptions};
+use mtef::error::Error;
+use mtef::output::{Warning, WarningFilter};
+
+fn strict(buf: &[u8]) -> Result<MTEquation, Error> {
+    MTEquation::parse_with(buf.to_vec(), &ParseOptions { mode: ParseMode::Strict, ..Default::default() })
+}
+
+/// A pile standing for the denominator of a fraction, instead of a LINE, was
+/// dropped along with the rest of the fraction.
+#[test]
+fn pile_template_slot() {
+    let eqn = MTEquation::from_bytes(include_bytes!("pile_template_slot.mtef")).unwrap();
+    assert_eq!(eqn.to_plain_text(), "x/(2; 3)y");
+    mtef::output::validate_strict(&eqn.to_bytes()).unwrap();
+}
+
+/// The records after a matrix were read from the middle of its payload. The matrix
+/// itself is left out with a warning.
+#[test]
+fn matrix_then_char() {
+    let buf = include_bytes!("matrix_then_char.mtef");
+    let eqn = MTEquation::from_bytes(buf).unwrap();
+    assert_eq!(eqn.to_plain_text(), "x");
+    assert_eq!(eqn.filtered_warnings(&WarningFilter::default(), None), [Warning::SkippedMatrix { offset: 44 }]);
+    assert!(eqn.filtered_warnings(&"skipped-matrix".parse().unwrap(), None).is_empty());
+    match strict(buf) {
+        Err(Error::MalformedMTEF(message)) => assert_eq!(message, "MATRIX records are not supported"),
+        other => panic!("strict parsing gave {:?}", other),
+    }
+}
+
+/// Minimizing keeps the header and the matrix, the only record strict parsing needs
+/// to refuse the equation for its matrix.
+#[test]
+fn minimize_matrix_then_char() {
+    let buf = include_bytes!("matrix_then_char.mtef");
+    let refused = |buf: &[u8]| matches!(strict(buf), Err(Error::MalformedMTEF("MATRIX records are not supported")));
+    let minimized = minimize_failing_input(buf, refused);
+    // header, then the matrix from its tag to its END, after the font, the
+    // preferences and the LINE opening the equation
+    assert_eq!(minimized, [&buf[..12], &buf[44..97]].concat());
+    assert!(refused(&minimized));
+}